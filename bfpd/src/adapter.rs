@@ -0,0 +1,792 @@
+//! The Store and Ingest implementations the diesel backends share.  The queries are
+//! built by the query module and run the same way on each backend, so a backend
+//! implements Dialect for the SQL that genuinely differs and invokes diesel_store!
+//! with its full-text search for the rest.
+use crate::csv::{insert_batches, Ingest, Progress, Rows};
+use crate::filter::FoodFilter;
+use crate::models::{Brand, Food, Foodgroup, Nutrientdata};
+use crate::query;
+use diesel::{Connection, QueryResult};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+/// The queries a backend runs its own way
+pub trait Dialect {
+    /// The backend's connection, which its pool hands out
+    type Connection: diesel::Connection;
+    /// Whether suggest matches prefixes on the lower cased columns, for a backend
+    /// that compares case
+    const LOWER_PREFIX: bool = false;
+    /// Gives the connection an empty search_hits table, by default a temporary
+    /// one created the first time
+    const CLEAR_HITS: &'static str = "CREATE TEMPORARY TABLE IF NOT EXISTS search_hits (id INTEGER PRIMARY KEY, score DOUBLE PRECISION NOT NULL); DELETE FROM search_hits";
+    /// The table a search index's hits are inserted in for search_hits to read
+    const HITS_TABLE: &'static str = "search_hits";
+    /// Returns the brands with the owners most like an owner, one per owner, with
+    /// their similarity, most similar first
+    fn owners_like(
+        &self,
+        owner: &str,
+        max: i64,
+    ) -> Result<Vec<(Brand, f64)>, Box<dyn Error + Send + Sync>>;
+    /// Returns the food groups most like a description as owners_like does owners
+    fn food_groups_like(
+        &self,
+        group: &str,
+        max: i64,
+    ) -> Result<Vec<(Foodgroup, f64)>, Box<dyn Error + Send + Sync>>;
+    /// Runs after a brand or food group is inserted with its id, e.g. to keep the
+    /// table's id sequence past it
+    fn id_inserted(_conn: &Self::Connection, _table: &str) -> QueryResult<()> {
+        Ok(())
+    }
+    /// The brands and food groups of a bulk load of foods, for a backend whose bulk
+    /// loader inserts them itself
+    fn deferred(&self) -> Option<&Deferred> {
+        None
+    }
+    /// Loads a stream of foods for Ingest::load_foods.  By default they're saved
+    /// BATCH_SIZE at a time;  a backend with a bulk loader uses it instead.
+    fn bulk_load_foods(
+        &self,
+        foods: Rows<Food>,
+        at: Progress,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>>
+    where
+        Self: Ingest,
+    {
+        insert_batches(foods, |batch| self.save_foods(batch, &at()))
+    }
+    /// Loads a stream of nutrient values as bulk_load_foods does foods
+    fn bulk_load_nutrient_data(
+        &self,
+        nd: Rows<Nutrientdata>,
+        at: Progress,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>>
+    where
+        Self: Ingest,
+    {
+        insert_batches(nd, |batch| self.save_nutrient_data(batch, &at()))
+    }
+}
+
+/// A filter's search index hits, held in its connection's search_hits table for
+/// the queries run meanwhile.  The table is emptied when it's dropped.
+pub struct Hits<'a, C: Connection> {
+    conn: Option<&'a C>,
+    clear: &'static str,
+}
+impl<'a, C: Connection> Hits<'a, C> {
+    /// Fills a connection's search_hits with a filter's hits, if it has any.
+    /// They're inserted once for all the queries of a call, e.g. each facet.
+    pub fn load<D>(conn: &'a C, filter: &FoodFilter) -> QueryResult<Self>
+    where
+        D: Dialect<Connection = C> + ?Sized,
+    {
+        let conn = match &filter.hits {
+            Some(hits) => {
+                conn.batch_execute(D::CLEAR_HITS)?;
+                for chunk in hits.chunks(query::HITS_CHUNK) {
+                    conn.batch_execute(&query::insert_hits(D::HITS_TABLE, chunk))?;
+                }
+                Some(conn)
+            }
+            None => None,
+        };
+        Ok(Hits {
+            conn,
+            clear: D::CLEAR_HITS,
+        })
+    }
+}
+impl<C: Connection> Drop for Hits<'_, C> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn {
+            // the next load empties the table anyway
+            let _ = conn.batch_execute(self.clear);
+        }
+    }
+}
+
+/// The brands and food groups created while a bulk load of foods runs.  They're
+/// kept for the loader to insert in the transaction of the chunk of foods that
+/// has them, so they're rolled back with it, rather than on their own.
+#[derive(Debug, Default, Clone)]
+pub struct Deferred(Arc<Mutex<Option<Kept>>>);
+type Kept = (Vec<Brand>, Vec<Foodgroup>);
+impl Deferred {
+    /// Runs a load, keeping the brands and food groups created meanwhile
+    pub fn during<T>(&self, load: impl FnOnce() -> T) -> T {
+        *self.0.lock().unwrap() = Some(Default::default());
+        let loaded = load();
+        *self.0.lock().unwrap() = None;
+        loaded
+    }
+    /// Keeps a brand if a load is running and returns whether it's kept
+    pub fn brand(&self, b: &Brand) -> bool {
+        match self.0.lock().unwrap().as_mut() {
+            Some((brands, _)) => {
+                brands.push(b.clone());
+                true
+            }
+            None => false,
+        }
+    }
+    /// Keeps a food group if a load is running and returns whether it's kept
+    pub fn food_group(&self, fg: &Foodgroup) -> bool {
+        match self.0.lock().unwrap().as_mut() {
+            Some((_, food_groups)) => {
+                food_groups.push(fg.clone());
+                true
+            }
+            None => false,
+        }
+    }
+    /// Takes the brands and food groups kept since the last take
+    pub fn take(&self) -> Kept {
+        self.0
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+}
+
+/// Implements Get and Browse of a model for diesel_store!, given the query module's
+/// builders of the model's get and browse queries
+#[doc(hidden)]
+#[macro_export]
+macro_rules! diesel_table {
+    ($store:ty, $backend:ty, $model:ty, $get:ident, $browse:ident) => {
+        impl $crate::Get<$model> for $store {
+            type Item = $model;
+            fn get(
+                &self,
+                m: &$model,
+            ) -> Result<Vec<$model>, Box<dyn std::error::Error + Send + Sync>> {
+                use diesel::RunQueryDsl;
+                let conn = self.pool.get()?;
+                Ok($crate::query::$get::<$backend>(m).load::<$model>(&conn)?)
+            }
+        }
+        impl $crate::Browse<$model> for $store {
+            type Item = $model;
+            fn browse(
+                &self,
+                _m: &$model,
+                max: i64,
+                off: i64,
+                sort: String,
+                order: String,
+            ) -> Result<Vec<$model>, Box<dyn std::error::Error + Send + Sync>> {
+                use diesel::RunQueryDsl;
+                let conn = self.pool.get()?;
+                let q = $crate::query::$browse::<$backend>(max, off, &sort, &order, None);
+                Ok(q.load::<$model>(&conn)?)
+            }
+            fn browse_page(
+                &self,
+                _m: &$model,
+                max: i64,
+                cursor: Option<&$crate::cursor::Cursor>,
+                sort: String,
+                order: String,
+            ) -> Result<$crate::cursor::Page<$model>, Box<dyn std::error::Error + Send + Sync>>
+            {
+                use diesel::RunQueryDsl;
+                let conn = self.pool.get()?;
+                let q = $crate::query::$browse::<$backend>(max + 1, 0, &sort, &order, cursor);
+                let rows = q.load::<$model>(&conn)?;
+                Ok($crate::cursor::Page::create(rows, max, cursor, &sort, &order))
+            }
+        }
+    };
+}
+
+/// Implements Get, Browse, Count, Store and Ingest for a store with a `pool` of
+/// connections to a diesel backend, given the backend and its FullText search, e.g.
+/// `diesel_store!(PgStore, Pg, Fts);`.  The store implements Dialect.  The queries
+/// are built by the query module for any FoodQueryBackend and the rows made into
+/// models by their own constructors, so the macro only runs them on the store's
+/// connections, which diesel can't do generically across backends.
+#[macro_export]
+macro_rules! diesel_store {
+    ($store:ty, $backend:ty, $fts:expr) => {
+        const _: () = {
+            use diesel::dsl::{count_star, insert_into};
+            use diesel::prelude::*;
+            use std::collections::HashMap;
+            use std::error::Error;
+            use $crate::adapter::{Dialect, Hits};
+            use $crate::csv::{FoodId, Ingest, Progress, Rows};
+            use $crate::cursor::{Cursor, Page, Ranked};
+            use $crate::facet::{FacetCount, FacetField, Facets};
+            use $crate::filter::{Basis, FoodFilter, NutrientSort};
+            use $crate::histogram::{self, Bins, Histogram};
+            use $crate::loader::FoodDetails;
+            use $crate::models::{
+                Brand, Checkpoint, Derivation, Food, Foodgroup, NewFood, NewNutrientdata, Nutrient,
+                Nutrientdata, NutrientdataForm, NutrientsReportForm, ReportForm, ReportRow,
+            };
+            use $crate::query::{self, FullText};
+            use $crate::schema::{
+                brands, derivations, food_groups, foods, ingest_checkpoints, nutrient_data,
+                nutrients,
+            };
+            use $crate::stats::{self, GroupBy, NutrientStats};
+            use $crate::suggest::Kind;
+            use $crate::{Browse, Count, Get, Store};
+
+            type Conn = <$store as Dialect>::Connection;
+
+            impl Get<Food> for $store {
+                type Item = Food;
+                fn get(&self, food: &Food) -> Result<Vec<Food>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    Ok(query::food::<$backend>(food).load::<Food>(&conn)?)
+                }
+            }
+            impl Browse<FoodFilter> for $store {
+                type Item = Food;
+                fn browse(
+                    &self,
+                    filter: &FoodFilter,
+                    max: i64,
+                    off: i64,
+                    sort: String,
+                    order: String,
+                ) -> Result<Vec<Food>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let _hits = Hits::load::<$store>(&conn, filter)?;
+                    let q = query::browse_foods(filter, &$fts, max, off, &sort, &order, None);
+                    Ok(q.load::<Food>(&conn)?)
+                }
+                fn browse_page(
+                    &self,
+                    filter: &FoodFilter,
+                    max: i64,
+                    cursor: Option<&Cursor>,
+                    sort: String,
+                    order: String,
+                ) -> Result<Page<Food>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let _hits = Hits::load::<$store>(&conn, filter)?;
+                    let q = query::browse_foods(filter, &$fts, max + 1, 0, &sort, &order, cursor);
+                    // relevance and nutrient cursors hold a key the query computes, so
+                    // it's read along with the foods
+                    let key: Option<query::SortKey<$backend>> = match NutrientSort::parse(&sort) {
+                        Some(by) => Some(query::nutrient_key(&by)),
+                        None if sort == "relevance" => {
+                            query::rank(filter, &$fts).map(|r| Box::new(r.nullable()) as _)
+                        }
+                        None => None,
+                    };
+                    match key {
+                        Some(key) => {
+                            let rows = q
+                                .select((foods::all_columns, key))
+                                .load::<(Food, Option<f64>)>(&conn)?;
+                            let rows: Vec<Ranked<Food>> =
+                                rows.into_iter().map(Ranked::from).collect();
+                            Ok(Page::create(rows, max, cursor, &sort, &order).map(|r| r.item))
+                        }
+                        _ => {
+                            let rows = q.load::<Food>(&conn)?;
+                            Ok(Page::create(rows, max, cursor, &sort, &order))
+                        }
+                    }
+                }
+            }
+            impl Count<FoodFilter> for $store {
+                fn query_count(
+                    &self,
+                    filter: &FoodFilter,
+                ) -> Result<i64, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let _hits = Hits::load::<$store>(&conn, filter)?;
+                    let q = query::filter_foods(filter, $fts.search(filter));
+                    Ok(q.select(count_star()).first::<i64>(&conn)?)
+                }
+            }
+            $crate::diesel_table!($store, $backend, Brand, brand, browse_brands);
+            $crate::diesel_table!($store, $backend, Foodgroup, food_group, browse_food_groups);
+            $crate::diesel_table!($store, $backend, Nutrient, nutrient, browse_nutrients);
+            impl Browse<Nutrientdata> for $store {
+                type Item = Nutrientdata;
+                fn browse(
+                    &self,
+                    nd: &Nutrientdata,
+                    max: i64,
+                    off: i64,
+                    sort: String,
+                    order: String,
+                ) -> Result<Vec<Nutrientdata>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let q =
+                        query::browse_nutrient_data::<$backend>(nd, max, off, &sort, &order, None);
+                    Ok(q.load::<Nutrientdata>(&conn)?)
+                }
+                fn browse_page(
+                    &self,
+                    nd: &Nutrientdata,
+                    max: i64,
+                    cursor: Option<&Cursor>,
+                    sort: String,
+                    order: String,
+                ) -> Result<Page<Nutrientdata>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let q = query::browse_nutrient_data::<$backend>(
+                        nd,
+                        max + 1,
+                        0,
+                        &sort,
+                        &order,
+                        cursor,
+                    );
+                    let rows = q.load::<Nutrientdata>(&conn)?;
+                    Ok(Page::create(rows, max, cursor, &sort, &order))
+                }
+            }
+            impl Store for $store {
+                fn nutrient_data(
+                    &self,
+                    food: &Food,
+                    nids: &[String],
+                ) -> Result<Vec<NutrientdataForm>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let data = query::food_nutrient_data::<$backend>(food, nids).load::<(
+                        Nutrientdata,
+                        Nutrient,
+                        Derivation,
+                    )>(
+                        &conn
+                    )?;
+                    Ok(data
+                        .iter()
+                        .map(|(nd, n, d)| NutrientdataForm::create((nd, n, d)))
+                        .collect())
+                }
+                fn food_details(
+                    &self,
+                    foods: &[Food],
+                    nids: &[String],
+                ) -> Result<FoodDetails, Box<dyn Error + Send + Sync>> {
+                    if foods.is_empty() {
+                        return Ok(FoodDetails::default());
+                    }
+                    let conn = self.pool.get()?;
+                    let data = query::foods_nutrient_data::<$backend>(foods, nids).load::<(
+                        Nutrientdata,
+                        Nutrient,
+                        Derivation,
+                    )>(
+                        &conn
+                    )?;
+                    let brands = query::food_brands::<$backend>(foods).load::<Brand>(&conn)?;
+                    let groups =
+                        query::food_food_groups::<$backend>(foods).load::<Foodgroup>(&conn)?;
+                    Ok(FoodDetails::create(&data, &brands, &groups))
+                }
+                fn similar_owners(
+                    &self,
+                    owner: &str,
+                    max: i64,
+                ) -> Result<Vec<(Brand, f64)>, Box<dyn Error + Send + Sync>> {
+                    self.owners_like(owner, max)
+                }
+                fn similar_food_groups(
+                    &self,
+                    group: &str,
+                    max: i64,
+                ) -> Result<Vec<(Foodgroup, f64)>, Box<dyn Error + Send + Sync>> {
+                    self.food_groups_like(group, max)
+                }
+                fn suggest(
+                    &self,
+                    prefix: &str,
+                    kind: Kind,
+                    max: i64,
+                ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let lower = <$store as Dialect>::LOWER_PREFIX;
+                    let rows = match kind {
+                        Kind::Food => query::suggest_foods::<$backend>(prefix, max, lower)
+                            .load::<(i32, String)>(&conn)?,
+                        Kind::Owner => query::suggest_owners::<$backend>(prefix, max, lower)
+                            .load::<(i32, String)>(&conn)?,
+                        Kind::Group => query::suggest_food_groups::<$backend>(prefix, max, lower)
+                            .load::<(i32, String)>(&conn)?,
+                    };
+                    Ok(rows.into_iter().map(|(_, name)| name).collect())
+                }
+                fn facets(
+                    &self,
+                    filter: &FoodFilter,
+                    fields: &[FacetField],
+                    max: i64,
+                ) -> Result<Facets, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let _hits = Hits::load::<$store>(&conn, filter)?;
+                    let mut facets = Facets::default();
+                    for field in fields {
+                        let search = $fts.search(filter);
+                        match field {
+                            FacetField::FoodGroup => {
+                                let rows =
+                                    query::facet_food_groups::<$backend>(filter, search, max)
+                                        .load::<(i32, i64)>(&conn)?;
+                                let ids = rows.iter().map(|r| r.0).collect();
+                                let names = query::food_groups_by_id::<$backend>(ids)
+                                    .load::<Foodgroup>(&conn)?
+                                    .into_iter()
+                                    .map(|fg| (fg.id, fg.description))
+                                    .collect();
+                                facets.food_groups = Some(FacetCount::named(rows, &names));
+                            }
+                            FacetField::Owner => {
+                                let rows = query::facet_owners::<$backend>(filter, search, max)
+                                    .load::<(Option<String>, i64)>(&conn)?;
+                                facets.owners = Some(FacetCount::nullable(rows));
+                            }
+                            FacetField::Country => {
+                                let rows = query::facet_countries::<$backend>(filter, search, max)
+                                    .load::<(Option<String>, i64)>(&conn)?;
+                                facets.countries = Some(FacetCount::nullable(rows));
+                            }
+                            FacetField::Datasource => {
+                                let rows =
+                                    query::facet_datasources::<$backend>(filter, search, max)
+                                        .load::<(String, i64)>(&conn)?;
+                                facets.datasources = Some(
+                                    rows.into_iter()
+                                        .map(|(value, count)| FacetCount { value, count })
+                                        .collect(),
+                                );
+                            }
+                        }
+                    }
+                    Ok(facets)
+                }
+                fn report(
+                    &self,
+                    max: i64,
+                    off: i64,
+                    sort: String,
+                    ord: String,
+                    min: f64,
+                    mx: f64,
+                    nid: i32,
+                ) -> Result<Vec<ReportForm>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let data = query::report::<$backend>(max, off, &sort, &ord, min, mx, nid)
+                        .load::<ReportRow>(&conn)?;
+                    Ok(data.iter().map(ReportForm::create).collect())
+                }
+                fn nutrients_report(
+                    &self,
+                    filter: &FoodFilter,
+                    sort: &str,
+                    order: &str,
+                    max: i64,
+                    off: i64,
+                ) -> Result<Vec<NutrientsReportForm>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let _hits = Hits::load::<$store>(&conn, filter)?;
+                    let search = $fts.search(filter);
+                    let foods = query::nutrients_report(filter, search, sort, order, max, off)
+                        .load::<Food>(&conn)?;
+                    let nids: Vec<String> = filter
+                        .nutrients
+                        .iter()
+                        .map(|r| r.nutrientno.to_string())
+                        .collect();
+                    let data = query::foods_nutrient_data::<$backend>(&foods, &nids).load::<(
+                        Nutrientdata,
+                        Nutrient,
+                        Derivation,
+                    )>(
+                        &conn
+                    )?;
+                    Ok(NutrientsReportForm::create_all(&foods, &data))
+                }
+                fn nutrient_stats(
+                    &self,
+                    filter: &FoodFilter,
+                    nutrientno: &str,
+                    by: GroupBy,
+                    percentiles: &[f64],
+                    max: i64,
+                ) -> Result<Vec<NutrientStats>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let _hits = Hits::load::<$store>(&conn, filter)?;
+                    let search = $fts.search(filter);
+                    Ok(match by {
+                        GroupBy::FoodGroup => {
+                            let rows = query::nutrient_values_by_food_group::<$backend>(
+                                filter, search, nutrientno,
+                            )
+                            .load::<(i32, f64)>(&conn)?;
+                            let groups = stats::largest_groups(rows, max);
+                            let ids = groups.iter().map(|g| g.0).collect();
+                            let names: HashMap<i32, String> =
+                                query::food_groups_by_id::<$backend>(ids)
+                                    .load::<Foodgroup>(&conn)?
+                                    .into_iter()
+                                    .map(|fg| (fg.id, fg.description))
+                                    .collect();
+                            NutrientStats::named(groups, &names, percentiles)
+                        }
+                        GroupBy::Owner => {
+                            let rows = query::nutrient_values_by_owner::<$backend>(
+                                filter, search, nutrientno,
+                            )
+                            .load::<(Option<String>, f64)>(&conn)?;
+                            NutrientStats::nullable(stats::largest_groups(rows, max), percentiles)
+                        }
+                        GroupBy::Country => {
+                            let rows = query::nutrient_values_by_country::<$backend>(
+                                filter, search, nutrientno,
+                            )
+                            .load::<(Option<String>, f64)>(&conn)?;
+                            NutrientStats::nullable(stats::largest_groups(rows, max), percentiles)
+                        }
+                    })
+                }
+                fn histogram(
+                    &self,
+                    filter: &FoodFilter,
+                    nutrientno: &str,
+                    basis: Basis,
+                    bins: &Bins,
+                    log: bool,
+                ) -> Result<Histogram, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    let _hits = Hits::load::<$store>(&conn, filter)?;
+                    let edges = match bins {
+                        Bins::Edges(edges) => edges.clone(),
+                        Bins::Count(n) => {
+                            let range = query::nutrient_value_range::<$backend>(
+                                filter,
+                                $fts.search(filter),
+                                nutrientno,
+                                basis,
+                            )
+                            .first::<(Option<f64>, Option<f64>)>(&conn)?;
+                            match range {
+                                (Some(min), Some(max)) => histogram::even_edges(min, max, *n, log),
+                                // no food has the nutrient
+                                _ => return Ok(Histogram::default()),
+                            }
+                        }
+                    };
+                    let counts = query::histogram_bins::<$backend>(
+                        filter,
+                        $fts.search(filter),
+                        nutrientno,
+                        basis,
+                        &edges,
+                    )
+                    .load::<(i32, i64)>(&conn)?;
+                    Ok(Histogram::create(&edges, counts))
+                }
+            }
+            impl Ingest for $store {
+                fn insert_brand(&self, b: &Brand) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    if self.deferred().is_some_and(|d| d.brand(b)) {
+                        return Ok(1);
+                    }
+                    let pooled = self.pool.get()?;
+                    let conn: &Conn = &pooled;
+                    let count = insert_into(brands::table).values(b).execute(conn)?;
+                    <$store as Dialect>::id_inserted(conn, "brands")?;
+                    Ok(count)
+                }
+                fn insert_food_group(
+                    &self,
+                    fg: &Foodgroup,
+                ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    if self.deferred().is_some_and(|d| d.food_group(fg)) {
+                        return Ok(1);
+                    }
+                    let pooled = self.pool.get()?;
+                    let conn: &Conn = &pooled;
+                    let count = insert_into(food_groups::table).values(fg).execute(conn)?;
+                    <$store as Dialect>::id_inserted(conn, "food_groups")?;
+                    Ok(count)
+                }
+                fn insert_foods(&self, f: &[Food]) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    let pooled = self.pool.get()?;
+                    let conn: &Conn = &pooled;
+                    let rows: Vec<NewFood> = f.iter().map(|f| f.insertable()).collect();
+                    Ok(insert_into(foods::table).values(&rows).execute(conn)?)
+                }
+                fn insert_nutrient_data(
+                    &self,
+                    nd: &[Nutrientdata],
+                ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    let pooled = self.pool.get()?;
+                    let conn: &Conn = &pooled;
+                    let rows: Vec<NewNutrientdata> = nd.iter().map(|n| n.insertable()).collect();
+                    Ok(insert_into(nutrient_data::table)
+                        .values(&rows)
+                        .execute(conn)?)
+                }
+                fn insert_nutrients(
+                    &self,
+                    n: &[Nutrient],
+                ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    let pooled = self.pool.get()?;
+                    let conn: &Conn = &pooled;
+                    Ok(insert_into(nutrients::table).values(n).execute(conn)?)
+                }
+                fn insert_derivations(
+                    &self,
+                    d: &[Derivation],
+                ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    let pooled = self.pool.get()?;
+                    let conn: &Conn = &pooled;
+                    Ok(insert_into(derivations::table).values(d).execute(conn)?)
+                }
+                fn save_foods(
+                    &self,
+                    f: &[Food],
+                    at: &Checkpoint,
+                ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    let pooled = self.pool.get()?;
+                    let conn: &Conn = &pooled;
+                    let rows: Vec<NewFood> = f.iter().map(|f| f.insertable()).collect();
+                    Ok(conn.transaction::<_, diesel::result::Error, _>(|| {
+                        let count = insert_into(foods::table).values(&rows).execute(conn)?;
+                        save_checkpoint(conn, at)?;
+                        Ok(count)
+                    })?)
+                }
+                fn save_nutrient_data(
+                    &self,
+                    nd: &[Nutrientdata],
+                    at: &Checkpoint,
+                ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    let pooled = self.pool.get()?;
+                    let conn: &Conn = &pooled;
+                    let rows: Vec<NewNutrientdata> = nd.iter().map(|n| n.insertable()).collect();
+                    Ok(conn.transaction::<_, diesel::result::Error, _>(|| {
+                        let count = insert_into(nutrient_data::table)
+                            .values(&rows)
+                            .execute(conn)?;
+                        save_checkpoint(conn, at)?;
+                        Ok(count)
+                    })?)
+                }
+                fn load_foods(
+                    &self,
+                    foods: Rows<Food>,
+                    at: Progress,
+                ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    self.bulk_load_foods(foods, at)
+                }
+                fn load_nutrient_data(
+                    &self,
+                    nd: Rows<Nutrientdata>,
+                    at: Progress,
+                ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    self.bulk_load_nutrient_data(nd, at)
+                }
+                fn checkpoint(
+                    &self,
+                    phase: &str,
+                ) -> Result<Option<Checkpoint>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    Ok(ingest_checkpoints::table
+                        .find(phase)
+                        .first::<Checkpoint>(&conn)
+                        .optional()?)
+                }
+                fn clear_checkpoint(
+                    &self,
+                    phase: &str,
+                ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    Ok(diesel::delete(ingest_checkpoints::table.find(phase)).execute(&conn)?)
+                }
+                fn brands(&self) -> Result<Vec<Brand>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    Ok(brands::table.load::<Brand>(&conn)?)
+                }
+                fn food_groups(&self) -> Result<Vec<Foodgroup>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    Ok(food_groups::table.load::<Foodgroup>(&conn)?)
+                }
+                fn nutrients(&self) -> Result<Vec<Nutrient>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    Ok(nutrients::table.load::<Nutrient>(&conn)?)
+                }
+                fn derivations(&self) -> Result<Vec<Derivation>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    Ok(derivations::table.load::<Derivation>(&conn)?)
+                }
+                fn food_ids(
+                    &self,
+                    fdc_ids: &[String],
+                ) -> Result<Vec<FoodId>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    Ok(foods::table
+                        .select((foods::fdc_id, foods::id, foods::serving_size))
+                        .filter(foods::fdc_id.eq_any(fdc_ids))
+                        .load(&conn)?)
+                }
+                fn foods_by_fdc_id(
+                    &self,
+                    fdc_ids: &[String],
+                ) -> Result<Vec<Food>, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    Ok(foods::table
+                        .filter(foods::fdc_id.eq_any(fdc_ids))
+                        .load::<Food>(&conn)?)
+                }
+                fn update_foods(&self, f: &[Food]) -> Result<usize, Box<dyn Error + Send + Sync>> {
+                    let conn = self.pool.get()?;
+                    Ok(conn.transaction::<_, diesel::result::Error, _>(|| {
+                        let mut count = 0;
+                        for row in f {
+                            count += diesel::update(foods::table.find(row.id))
+                                .set(&row.insertable())
+                                .execute(&conn)?;
+                        }
+                        Ok(count)
+                    })?)
+                }
+                fn replace_nutrient_data(
+                    &self,
+                    food_ids: &[i32],
+                    nd: &[Nutrientdata],
+                ) -> Result<(usize, usize), Box<dyn Error + Send + Sync>> {
+                    let pooled = self.pool.get()?;
+                    let conn: &Conn = &pooled;
+                    let rows: Vec<NewNutrientdata> = nd.iter().map(|n| n.insertable()).collect();
+                    Ok(conn.transaction::<_, diesel::result::Error, _>(|| {
+                        let deleted = match food_ids.is_empty() {
+                            true => 0,
+                            false => diesel::delete(
+                                nutrient_data::table
+                                    .filter(nutrient_data::food_id.eq_any(food_ids)),
+                            )
+                            .execute(conn)?,
+                        };
+                        let count = insert_into(nutrient_data::table)
+                            .values(&rows)
+                            .execute(conn)?;
+                        Ok((deleted, count))
+                    })?)
+                }
+            }
+            /// Saves a load's checkpoint, replacing the phase's last one
+            fn save_checkpoint(conn: &Conn, at: &Checkpoint) -> QueryResult<usize> {
+                diesel::delete(ingest_checkpoints::table.find(&at.phase)).execute(conn)?;
+                insert_into(ingest_checkpoints::table)
+                    .values(at)
+                    .execute(conn)
+            }
+        };
+    };
+}
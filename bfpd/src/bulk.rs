@@ -0,0 +1,162 @@
+//! Rows written as csv for the backends' bulk loaders, PostgreSQL's COPY and
+//! MariaDB's LOAD DATA.  Each reads NULLs its own way, so a record is written
+//! in the form of the loader it's for.
+use crate::models::{Food, Nutrientdata};
+use csv::WriterBuilder;
+use std::error::Error;
+use std::io::Write;
+
+/// The foods columns a food record holds, in order
+pub const FOOD_COLUMNS: [&str; 20] = [
+    "publication_date",
+    "modified_date",
+    "available_date",
+    "upc",
+    "fdc_id",
+    "description",
+    "food_group_id",
+    "brand_id",
+    "datasource",
+    "serving_size",
+    "serving_unit",
+    "serving_description",
+    "country",
+    "ingredients",
+    "discontinued_date",
+    "package_weight",
+    "preparation_state_code",
+    "trade_channel",
+    "short_description",
+    "fdc_release",
+];
+/// The nutrient_data columns a nutrient value record holds, in order
+pub const NUTRIENT_DATA_COLUMNS: [&str; 13] = [
+    "value",
+    "portion_value",
+    "standard_error",
+    "minimum",
+    "maximum",
+    "median",
+    "data_points",
+    "footnote",
+    "min_year",
+    "derivation_id",
+    "nutrient_id",
+    "food_id",
+    "fdc_release",
+];
+const TIMESTAMP: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Returns a food's FOOD_COLUMNS
+pub fn food_record(f: &Food) -> Vec<Option<String>> {
+    vec![
+        Some(f.publication_date.format(TIMESTAMP).to_string()),
+        Some(f.modified_date.format(TIMESTAMP).to_string()),
+        Some(f.available_date.format(TIMESTAMP).to_string()),
+        Some(f.upc.to_string()),
+        Some(f.fdc_id.to_string()),
+        Some(f.description.to_string()),
+        Some(f.food_group_id.to_string()),
+        Some(f.brand_id.to_string()),
+        Some(f.datasource.to_string()),
+        f.serving_size.map(|s| s.to_string()),
+        f.serving_unit.clone(),
+        f.serving_description.clone(),
+        f.country.clone(),
+        f.ingredients.clone(),
+        f.discontinued_date.map(|d| d.format(TIMESTAMP).to_string()),
+        f.package_weight.clone(),
+        f.preparation_state_code.clone(),
+        f.trade_channel.clone(),
+        f.short_description.clone(),
+        f.fdc_release.clone(),
+    ]
+}
+/// Returns a nutrient value's NUTRIENT_DATA_COLUMNS
+pub fn nutrientdata_record(nd: &Nutrientdata) -> Vec<Option<String>> {
+    vec![
+        Some(nd.value.to_string()),
+        Some(nd.portion_value.to_string()),
+        nd.standard_error.map(|v| v.to_string()),
+        nd.minimum.map(|v| v.to_string()),
+        nd.maximum.map(|v| v.to_string()),
+        nd.median.map(|v| v.to_string()),
+        nd.data_points.map(|v| v.to_string()),
+        nd.footnote.clone(),
+        nd.min_year.map(|v| v.to_string()),
+        Some(nd.derivation_id.to_string()),
+        Some(nd.nutrient_id.to_string()),
+        Some(nd.food_id.to_string()),
+        nd.fdc_release.clone(),
+    ]
+}
+/// The csv form a bulk loader reads
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// NULL is an unquoted \N and backslashes are doubled, as LOAD DATA reads
+    /// them as escapes
+    LoadData,
+    /// NULL is an unquoted empty field and every value is quoted, so COPY reads
+    /// an empty or \N value as text
+    Copy,
+}
+/// Writes rows as csv records in a loader's format
+pub fn write_csv<T, W: Write>(
+    mut out: W,
+    rows: &[T],
+    record: fn(&T) -> Vec<Option<String>>,
+    format: Format,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if format == Format::Copy {
+        // the csv writer quotes all fields or only those needing it, empty ones
+        // included, so these are written by hand
+        for r in rows {
+            let fields: Vec<String> = record(r)
+                .iter()
+                .map(|v| match v {
+                    Some(v) => format!("\"{}\"", v.replace('"', "\"\"")),
+                    None => String::new(),
+                })
+                .collect();
+            writeln!(out, "{}", fields.join(","))?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+    let mut w = WriterBuilder::new().has_headers(false).from_writer(out);
+    for r in rows {
+        w.write_record(record(r).iter().map(|v| match v {
+            Some(v) => v.replace('\\', "\\\\"),
+            None => "\\N".to_string(),
+        }))?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn write_records() {
+        let mut f = Food::new();
+        f.description = "CAFE \"AU LAIT\", 2\\3".to_string();
+        f.serving_size = Some(28.0);
+        assert_eq!(food_record(&f).len(), FOOD_COLUMNS.len());
+        assert_eq!(nutrientdata_record(&Nutrientdata::new()).len(), NUTRIENT_DATA_COLUMNS.len());
+        let mut out = Vec::new();
+        write_csv(&mut out, &[f.clone()], food_record, Format::LoadData).unwrap();
+        let line = String::from_utf8(out).unwrap();
+        assert!(line.starts_with("1970-01-01 00:00:00,"));
+        assert!(line.contains(",\"CAFE \"\"AU LAIT\"\", 2\\\\3\","));
+        assert!(line.contains(",28,\\N,"));
+        // COPY reads an unquoted empty field as NULL, and a quoted \N as text
+        f.description = "\\N".to_string();
+        let mut out = Vec::new();
+        write_csv(&mut out, &[f], food_record, Format::Copy).unwrap();
+        let line = String::from_utf8(out).unwrap();
+        assert!(line.starts_with("\"1970-01-01 00:00:00\","));
+        assert!(line.contains(",\"\\N\","));
+        assert!(line.contains(",\"28\",,"));
+    }
+}
@@ -0,0 +1,97 @@
+//! Lookups for ingest.  The brands, food groups, nutrients and derivations are
+//! read into maps once rather than queried for every csv row, and new brands
+//! and food groups are given the next id here and inserted with it so they
+//! needn't be read back.  There are too many foods to hold this way;  the
+//! nutrient data loads look theirs up a chunk of rows at a time.
+use crate::csv::Ingest;
+use crate::models::{Brand, Foodgroup};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+/// A brand's owner, brand name and sub-brand
+type BrandKey = (String, Option<String>, Option<String>);
+
+#[derive(Debug, Default)]
+pub struct Cache {
+    brands: HashMap<BrandKey, i32>,
+    next_brand: i32,
+    food_groups: HashMap<String, i32>,
+    next_food_group: i32,
+    /// nutrient ids by nutrient number
+    nutrients: HashMap<String, i32>,
+    nutrient_ids: HashSet<i32>,
+    derivations: HashSet<i32>,
+}
+impl Cache {
+    /// Reads the brands, food groups, nutrients and derivations
+    pub fn load(db: &dyn Ingest) -> Result<Cache, Box<dyn Error + Send + Sync>> {
+        let mut c = Cache::default();
+        for b in db.brands()? {
+            c.next_brand = c.next_brand.max(b.id);
+            c.brands.insert((b.owner, b.brand, b.subbrand), b.id);
+        }
+        for fg in db.food_groups()? {
+            c.next_food_group = c.next_food_group.max(fg.id);
+            c.food_groups.insert(fg.description, fg.id);
+        }
+        for n in db.nutrients()? {
+            c.nutrient_ids.insert(n.id);
+            c.nutrients.insert(n.nutrientno, n.id);
+        }
+        c.derivations = db.derivations()?.iter().map(|d| d.id).collect();
+        Ok(c)
+    }
+    /// Returns the id of a brand, inserting it if it's new
+    pub fn brand_id(
+        &mut self,
+        owner: &str,
+        brand: Option<String>,
+        subbrand: Option<String>,
+        db: &dyn Ingest,
+    ) -> Result<i32, Box<dyn Error + Send + Sync>> {
+        let key = (owner.to_string(), brand, subbrand);
+        if let Some(id) = self.brands.get(&key) {
+            return Ok(*id);
+        }
+        let b = Brand {
+            id: self.next_brand + 1,
+            owner: key.0.to_string(),
+            brand: key.1.clone(),
+            subbrand: key.2.clone(),
+        };
+        db.insert_brand(&b)?;
+        self.next_brand = b.id;
+        self.brands.insert(key, b.id);
+        Ok(b.id)
+    }
+    /// Returns the id of a food group, inserting it if it's new
+    pub fn food_group_id(
+        &mut self,
+        description: &str,
+        db: &dyn Ingest,
+    ) -> Result<i32, Box<dyn Error + Send + Sync>> {
+        if let Some(id) = self.food_groups.get(description) {
+            return Ok(*id);
+        }
+        let fg = Foodgroup {
+            id: self.next_food_group + 1,
+            description: description.to_string(),
+        };
+        db.insert_food_group(&fg)?;
+        self.next_food_group = fg.id;
+        self.food_groups.insert(fg.description, fg.id);
+        Ok(fg.id)
+    }
+    /// Returns the id of a nutrient given its number or, failing that, its id.
+    /// food_nutrient.csv has some of each.
+    pub fn nutrient_id(&self, nutrient: i32) -> Option<i32> {
+        match self.nutrients.get(&nutrient.to_string()) {
+            Some(id) => Some(*id),
+            None => self.nutrient_ids.get(&nutrient).copied(),
+        }
+    }
+    /// Whether a derivation is loaded
+    pub fn has_derivation(&self, id: i32) -> bool {
+        self.derivations.contains(&id)
+    }
+}
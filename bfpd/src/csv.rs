@@ -0,0 +1,889 @@
+use crate::cache::Cache;
+use crate::models::{Brand, Checkpoint, Derivation, Food, Foodgroup, Nutrient, Nutrientdata};
+use crate::reject::Rejects;
+use crate::Store;
+use chrono::NaiveDateTime;
+use crate::merge::{Join, SortedCsv, RUN_SIZE};
+use csv::{Reader, ReaderBuilder, StringRecord};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+/// The rows inserted, or bulk loaded, in each transaction
+pub const BATCH_SIZE: usize = 2000;
+/// The checkpointed phases of a load
+pub const FOODS: &str = "foods";
+pub const NUTRIENT_DATA: &str = "nutrient_data";
+/// The csv files of a release
+const FOOD_CSV: &str = "food.csv";
+const BRANDED_FOOD_CSV: &str = "branded_food.csv";
+const FOOD_NUTRIENT_CSV: &str = "food_nutrient.csv";
+const NUTRIENT_CSV: &str = "nutrient.csv";
+const DERIVATION_CSV: &str = "food_nutrient_derivation.csv";
+/// Ingest is implemented by each backend to write rows created from the csv files.
+/// The rows looked up while loading are read once into a Cache.
+pub trait Ingest: Store {
+    /// Inserts a brand with its id
+    fn insert_brand(&self, b: &Brand) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    /// Inserts a food group with its id
+    fn insert_food_group(&self, fg: &Foodgroup) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    fn insert_foods(&self, foods: &[Food]) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    fn insert_nutrient_data(
+        &self,
+        nd: &[Nutrientdata],
+    ) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    fn insert_nutrients(&self, n: &[Nutrient]) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    fn insert_derivations(
+        &self,
+        d: &[Derivation],
+    ) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    /// Inserts foods and saves the checkpoint they reach in one transaction
+    fn save_foods(
+        &self,
+        foods: &[Food],
+        at: &Checkpoint,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    /// Inserts nutrient values and saves the checkpoint they reach in one transaction
+    fn save_nutrient_data(
+        &self,
+        nd: &[Nutrientdata],
+        at: &Checkpoint,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    /// Loads a stream of foods, returning the number loaded.  They're saved BATCH_SIZE at a
+    /// time with the checkpoint at each batch's end, by default with save_foods;  a backend
+    /// with a bulk loader, e.g. PostgreSQL's COPY, uses it for each batch instead.
+    fn load_foods(
+        &self,
+        foods: Rows<Food>,
+        at: Progress,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        insert_batches(foods, |batch| self.save_foods(batch, &at()))
+    }
+    /// Loads a stream of nutrient values as load_foods does foods
+    fn load_nutrient_data(
+        &self,
+        nd: Rows<Nutrientdata>,
+        at: Progress,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        insert_batches(nd, |batch| self.save_nutrient_data(batch, &at()))
+    }
+    /// Returns the last checkpoint saved in a phase, if any
+    fn checkpoint(&self, phase: &str) -> Result<Option<Checkpoint>, Box<dyn Error + Send + Sync>>;
+    /// Removes a phase's checkpoint so its next load starts from the beginning
+    fn clear_checkpoint(&self, phase: &str) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    fn brands(&self) -> Result<Vec<Brand>, Box<dyn Error + Send + Sync>>;
+    fn food_groups(&self) -> Result<Vec<Foodgroup>, Box<dyn Error + Send + Sync>>;
+    fn nutrients(&self) -> Result<Vec<Nutrient>, Box<dyn Error + Send + Sync>>;
+    fn derivations(&self) -> Result<Vec<Derivation>, Box<dyn Error + Send + Sync>>;
+    /// Returns the fdc_id, id and serving size of the foods with the fdc_ids
+    fn food_ids(&self, fdc_ids: &[String]) -> Result<Vec<FoodId>, Box<dyn Error + Send + Sync>>;
+    /// Returns the foods with the fdc_ids
+    fn foods_by_fdc_id(
+        &self,
+        fdc_ids: &[String],
+    ) -> Result<Vec<Food>, Box<dyn Error + Send + Sync>>;
+    /// Updates foods in place, keeping their ids
+    fn update_foods(&self, foods: &[Food]) -> Result<usize, Box<dyn Error + Send + Sync>>;
+    /// Deletes the nutrient data of the foods with the ids and inserts nutrient values in
+    /// one transaction, returning the number of rows deleted and inserted
+    fn replace_nutrient_data(
+        &self,
+        food_ids: &[i32],
+        nd: &[Nutrientdata],
+    ) -> Result<(usize, usize), Box<dyn Error + Send + Sync>>;
+}
+/// A food's fdc_id, id and serving size
+pub type FoodId = (String, i32, Option<f64>);
+/// The rows created from a csv file as it's read
+pub type Rows<'a, T> = &'a mut dyn Iterator<Item = Result<T, Box<dyn Error + Send + Sync>>>;
+/// Returns the checkpoint of the last row taken from a load's Rows
+pub type Progress<'a> = &'a dyn Fn() -> Checkpoint;
+
+/// Inserts rows BATCH_SIZE at a time and returns the number inserted
+pub fn insert_batches<T>(
+    rows: Rows<T>,
+    mut insert: impl FnMut(&[T]) -> Result<usize, Box<dyn Error + Send + Sync>>,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let mut count: usize = 0;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    for r in rows {
+        batch.push(r?);
+        if batch.len() == BATCH_SIZE {
+            count += insert(&batch)?;
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        count += insert(&batch)?;
+    }
+    Ok(count)
+}
+/// Foodcsv for deserializing the merged food.csv and branded_food.csv output.  Columns are
+/// mapped by their header names so the columns a release adds, drops or reorders don't
+/// break the loader;  columns not named here are ignored and missing ones are empty.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct Foodcsv {
+    fdc_id: String,
+    description: String,
+    #[serde(rename = "publication_date")]
+    date_published: String,
+    #[serde(rename = "brand_owner")]
+    owner: String,
+    #[serde(rename = "brand_name")]
+    brand: String,
+    #[serde(rename = "subbrand_name")]
+    subbrand: String,
+    #[serde(rename = "gtin_upc")]
+    upc: String,
+    ingredients: String,
+    serving_size: String,
+    #[serde(rename = "serving_size_unit")]
+    serving_unit: String,
+    #[serde(rename = "household_serving_fulltext")]
+    serving_description: String,
+    #[serde(rename = "branded_food_category")]
+    foodgroup: String,
+    #[serde(rename = "data_source")]
+    datasource: String,
+    #[serde(rename = "modified_date")]
+    date_modified: String,
+    #[serde(rename = "available_date")]
+    date_available: String,
+    #[serde(rename = "market_country")]
+    country: String,
+    discontinued_date: String,
+    package_weight: String,
+    preparation_state_code: String,
+    trade_channel: String,
+    short_description: String,
+}
+
+impl Foodcsv {
+    /// Creates a Food struct from the contents of Foodcsv, or returns why it can't be.  The
+    /// food group and brand ids are left for the caller.
+    fn create_food(&self) -> Result<Food, String> {
+        if self.fdc_id.trim().is_empty() {
+            return Err("missing fdc_id".to_string());
+        }
+        let mut f = Food::new();
+        f.serving_size = match self.serving_size.trim() {
+            "" => Some(0.0),
+            s => Some(
+                s.parse()
+                    .map_err(|_| format!("invalid serving_size '{}'", s))?,
+            ),
+        };
+        f.upc = self.upc.to_string();
+        f.fdc_id = self.fdc_id.to_string();
+        f.description = self.description.to_string();
+        f.datasource = self.datasource.to_string();
+        f.serving_unit = Some(self.serving_unit.to_string());
+        f.serving_description = Some(self.serving_description.to_string());
+        f.country = Some(self.country.to_string());
+        f.ingredients = Some(self.ingredients.to_string());
+        f.publication_date = date("publication_date", &self.date_published)?;
+        f.modified_date = date("modified_date", &self.date_modified)?;
+        f.available_date = date("available_date", &self.date_available)?;
+        f.discontinued_date = match self.discontinued_date.trim() {
+            "" => None,
+            d => Some(date("discontinued_date", d)?),
+        };
+        f.package_weight = optional(&self.package_weight);
+        f.preparation_state_code = optional(&self.preparation_state_code);
+        f.trade_channel = optional(&self.trade_channel);
+        f.short_description = optional(&self.short_description);
+        Ok(f)
+    }
+
+    /// Returns the database id for a brand as identified by the owner, brand name and
+    /// sub-brand.  A brand that's not in the cache is inserted
+    fn create_brand_id(
+        &self,
+        db: &dyn Ingest,
+        cache: &mut Cache,
+    ) -> Result<i32, Box<dyn Error + Send + Sync>> {
+        let owner = match self.owner.as_str() {
+            "" => "Unknown",
+            o => o,
+        };
+        cache.brand_id(owner, optional(&self.brand), optional(&self.subbrand), db)
+    }
+    /// Returns the database id for a food group as identified by the food group description.
+    /// A food group that's not in the cache is inserted
+    fn create_foodgroup_id(
+        &self,
+        db: &dyn Ingest,
+        cache: &mut Cache,
+    ) -> Result<i32, Box<dyn Error + Send + Sync>> {
+        let description = match self.foodgroup.as_str() {
+            "" => "Unknown",
+            d => d,
+        };
+        cache.food_group_id(description, db)
+    }
+}
+/// Parses a csv date column as 19:00 that day, 1970-01-01 if it's empty
+fn date(name: &str, column: &str) -> Result<NaiveDateTime, String> {
+    let d = match column.trim() {
+        "" => "1970-01-01",
+        d => d,
+    };
+    NaiveDateTime::parse_from_str(&format!("{} 19:00:00", d), "%Y-%m-%d %H:%M:%S")
+        .map_err(|_| format!("invalid {} '{}'", name, d))
+}
+/// A BFPD food is created from 2 csv files:  food.csv and branded_food.csv.  The two files
+/// are sorted by fdc_id a run at a time and merge joined as they're read, then each joined
+/// record is deserialized into a data transfer struct and finally into a Food struct which
+/// the backend loads.  Neither file is held in memory.  The foods are marked with the
+/// release they're loaded from.  Records that can't be made into foods are rejected.
+pub fn process_foods(
+    path: String,
+    release: &str,
+    resume: bool,
+    db: &dyn Ingest,
+    rejects: &mut Rejects,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let foodfile = format!("{}{}", path, FOOD_CSV);
+    let from = resume_from(FOODS, &foodfile, resume, db)?;
+    if let Some(c) = &from {
+        rejects.resumed(FOOD_CSV, c.last_row);
+    }
+    let after = from.as_ref().map(|c| c.last_fdc_id.to_string());
+    let progress = RefCell::new(from.unwrap_or_else(|| Checkpoint::new(FOODS, &foodfile)));
+    let mut cache = Cache::load(db)?;
+    let join = join_foods(&path, rejects)?;
+    let headers = join.headers().clone();
+    // the foods are in fdc_id order so a resumed load skips those up to the checkpoint
+    let count = {
+        let mut foods = join
+            .filter_map(|r| {
+                food_row(r, &headers, after.as_deref(), release, db, &mut cache, rejects)
+                    .transpose()
+            })
+            .inspect(|f| {
+                if let Ok(f) = f {
+                    let mut p = progress.borrow_mut();
+                    p.last_row += 1;
+                    p.last_fdc_id = f.fdc_id.to_string();
+                }
+            });
+        db.load_foods(&mut foods, &|| progress.borrow().clone())?
+    };
+    rejects.loaded(FOOD_CSV, count);
+    Ok(count)
+}
+/// Updates the foods loaded from an earlier release with a later one's food.csv and
+/// branded_food.csv.  Foods are matched by fdc_id:  new ones are inserted and changed ones
+/// updated in place, both marked with the release, and the rest are left as they were.
+/// An update that stops part way can be run again.
+pub fn update_foods(
+    path: String,
+    release: &str,
+    db: &dyn Ingest,
+    rejects: &mut Rejects,
+) -> Result<UpdateReport, Box<dyn Error + Send + Sync>> {
+    let mut cache = Cache::load(db)?;
+    let join = join_foods(&path, rejects)?;
+    let headers = join.headers().clone();
+    let mut report = UpdateReport::default();
+    {
+        let mut foods = join.filter_map(|r| {
+            food_row(r, &headers, None, release, db, &mut cache, rejects).transpose()
+        });
+        insert_batches(&mut foods, |batch| update_batch(batch, db, &mut report))?;
+    }
+    rejects.loaded(FOOD_CSV, report.added + report.changed);
+    rejects.skipped(FOOD_CSV, report.unchanged);
+    Ok(report)
+}
+/// Creates the Food of a joined food.csv and branded_food.csv record marked with the
+/// release.  None if its fdc_id isn't after the one given, when it's skipped, or if it's
+/// rejected.
+fn food_row(
+    r: Result<StringRecord, Box<dyn Error + Send + Sync>>,
+    headers: &StringRecord,
+    after: Option<&str>,
+    release: &str,
+    db: &dyn Ingest,
+    cache: &mut Cache,
+    rejects: &mut Rejects,
+) -> Result<Option<Food>, Box<dyn Error + Send + Sync>> {
+    let r = r?;
+    if let Some(after) = after {
+        let fdc_id = headers.iter().position(|h| h == "fdc_id").and_then(|i| r.get(i));
+        if fdc_id.unwrap_or_default() <= after {
+            rejects.skipped(FOOD_CSV, 1);
+            return Ok(None);
+        }
+    }
+    let parsed = match r.len() == headers.len() {
+        true => r
+            .deserialize::<Foodcsv>(Some(headers))
+            .map_err(|e| e.to_string())
+            .and_then(|fcsv| Ok((fcsv.create_food()?, fcsv))),
+        false => Err(fields(headers.len(), r.len())),
+    };
+    let (mut f, fcsv) = match parsed {
+        Ok(parsed) => parsed,
+        Err(reason) => {
+            rejects.reject(FOOD_CSV, None, &r, &reason)?;
+            return Ok(None);
+        }
+    };
+    f.food_group_id = fcsv.create_foodgroup_id(db, cache)?;
+    f.brand_id = fcsv.create_brand_id(db, cache)?;
+    f.fdc_release = Some(release.to_string());
+    Ok(Some(f))
+}
+/// The reason a record with the wrong number of fields is rejected
+fn fields(expected: usize, found: usize) -> String {
+    format!("expected {} fields, found {}", expected, found)
+}
+/// Returns the checkpoint to resume a phase from, if resuming and there is one.  Otherwise
+/// the phase starts over and its checkpoint is cleared.
+fn resume_from(
+    phase: &str,
+    file: &str,
+    resume: bool,
+    db: &dyn Ingest,
+) -> Result<Option<Checkpoint>, Box<dyn Error + Send + Sync>> {
+    if !resume {
+        db.clear_checkpoint(phase)?;
+        return Ok(None);
+    }
+    match db.checkpoint(phase)? {
+        Some(c) if c.file != file => Err(format!(
+            "Cannot resume {} from {}:  the checkpoint is for {}",
+            phase, file, c.file
+        )
+        .into()),
+        c => Ok(c),
+    }
+}
+/// The foods an update added, changed and left as they were
+#[derive(Debug, Default, PartialEq)]
+pub struct UpdateReport {
+    pub added: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+}
+impl fmt::Display for UpdateReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} foods added, {} changed, {} unchanged",
+            self.added, self.changed, self.unchanged
+        )
+    }
+}
+/// Sorts food.csv and branded_food.csv by fdc_id and joins them
+fn join_foods(path: &str, rejects: &mut Rejects) -> Result<Join, Box<dyn Error + Send + Sync>> {
+    let foodfile = format!("{}{}", path, FOOD_CSV);
+    let food = SortedCsv::sort(Path::new(&foodfile), "fdc_id", RUN_SIZE, rejects)?;
+    let brandedfile = format!("{}{}", path, BRANDED_FOOD_CSV);
+    let branded = SortedCsv::sort(Path::new(&brandedfile), "fdc_id", RUN_SIZE, rejects)?;
+    Ok(Join::new(food, branded))
+}
+/// Inserts the new foods of a batch and updates the changed ones
+fn update_batch(
+    foods: &[Food],
+    db: &dyn Ingest,
+    report: &mut UpdateReport,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let fdc_ids: Vec<String> = foods.iter().map(|f| f.fdc_id.to_string()).collect();
+    let loaded: HashMap<String, Food> = db
+        .foods_by_fdc_id(&fdc_ids)?
+        .into_iter()
+        .map(|f| (f.fdc_id.to_string(), f))
+        .collect();
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    for f in foods {
+        match loaded.get(&f.fdc_id) {
+            Some(old) if same_food(f, old) => report.unchanged += 1,
+            Some(old) => changed.push(Food {
+                id: old.id,
+                ..f.clone()
+            }),
+            None => added.push(f.clone()),
+        }
+    }
+    if !added.is_empty() {
+        report.added += db.insert_foods(&added)?;
+    }
+    if !changed.is_empty() {
+        report.changed += db.update_foods(&changed)?;
+    }
+    Ok(foods.len())
+}
+/// Whether a food read from a release is the one already loaded, whichever release that
+/// came from
+fn same_food(f: &Food, loaded: &Food) -> bool {
+    *loaded
+        == Food {
+            id: loaded.id,
+            fdc_release: loaded.fdc_release.clone(),
+            ..f.clone()
+        }
+}
+/// NutdataCsv for deserializing the csv.  The columns are read in order so the
+/// ones that aren't used are read too.
+#[derive(Deserialize, Debug)]
+struct NutdataCsv {
+    _id: i32,
+    fdc_id: String,
+    nutrient_id: i32,
+    amount: f64,
+    data_points: String,
+    derivation_id: i32,
+    min: String,
+    max: String,
+    median: String,
+    footnote: String,
+    min_year: String,
+}
+impl NutdataCsv {
+    // transfers a NutdataCsv to a Nutrientdata struct of a release
+    fn create_nutdata(&self, fid: i32, release: &str) -> Nutrientdata {
+        Nutrientdata {
+            id: 0,
+            value: self.amount,
+            portion_value: self.amount,
+            standard_error: None,
+            minimum: optional(&self.min),
+            maximum: optional(&self.max),
+            median: optional(&self.median),
+            data_points: optional(&self.data_points),
+            footnote: optional(&self.footnote),
+            min_year: optional(&self.min_year),
+            derivation_id: self.derivation_id,
+            nutrient_id: self.nutrient_id,
+            food_id: fid,
+            fdc_release: Some(release.to_string()),
+        }
+    }
+}
+/// Parses an optional csv column, None if it's empty or can't be parsed
+fn optional<T: FromStr>(column: &str) -> Option<T> {
+    match column.trim() {
+        "" => None,
+        c => c.parse().ok(),
+    }
+}
+/// Streams the food_nutrient.csv data a row at a time into NutdataCsv structs then into
+/// Nutrientdata structs of the release which the backend loads.  Rows whose food, nutrient
+/// or derivation isn't loaded are rejected.
+pub fn process_nutdata(
+    path: String,
+    release: &str,
+    resume: bool,
+    db: &dyn Ingest,
+    rejects: &mut Rejects,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let ndfile = format!("{}{}", path, FOOD_NUTRIENT_CSV);
+    let mut ndcsv = ReaderBuilder::new().flexible(true).from_path(&ndfile)?;
+    let from = resume_from(NUTRIENT_DATA, &ndfile, resume, db)?;
+    if let Some(c) = &from {
+        rejects.resumed(FOOD_NUTRIENT_CSV, c.last_row);
+    }
+    let progress = RefCell::new(from.unwrap_or_else(|| Checkpoint::new(NUTRIENT_DATA, &ndfile)));
+    let cache = Cache::load(db)?;
+    // a resumed load skips the records read up to the checkpoint, rejected ones included
+    let skip = progress.borrow().last_row as usize;
+    let fields = ndcsv.headers()?.len();
+    let count = {
+        // the checkpoint counts the records as they're taken, not as their foods are
+        // looked up a chunk ahead
+        let mut nds = WithFoods::new(ndcsv.records().skip(skip), db)
+            .inspect(|r| {
+                if let Ok((r, _)) = r {
+                    let mut p = progress.borrow_mut();
+                    p.last_row += 1;
+                    if let Ok(r) = r {
+                        p.last_fdc_id = r.get(1).unwrap_or_default().to_string();
+                    }
+                }
+            })
+            .filter_map(|r| nutdata_row(r, fields, release, &cache, rejects).transpose());
+        db.load_nutrient_data(&mut nds, &|| progress.borrow().clone())?
+    };
+    rejects.skipped(FOOD_NUTRIENT_CSV, skip);
+    rejects.loaded(FOOD_NUTRIENT_CSV, count);
+    Ok(count)
+}
+/// Replaces the nutrient data of the foods in a later release's food_nutrient.csv with rows
+/// marked with the release.  A food's rows are deleted in the same transaction as its first
+/// new ones are inserted.  The summary has the release and the number of rows replaced.
+pub fn update_nutdata(
+    path: String,
+    release: &str,
+    db: &dyn Ingest,
+    rejects: &mut Rejects,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let ndfile = format!("{}{}", path, FOOD_NUTRIENT_CSV);
+    let mut ndcsv = ReaderBuilder::new().flexible(true).from_path(&ndfile)?;
+    let cache = Cache::load(db)?;
+    let fields = ndcsv.headers()?.len();
+    // the ids of the foods replaced so far, 4 bytes a food, so their rows further on in
+    // the file are added to the new ones
+    let mut replaced: HashSet<i32> = HashSet::new();
+    let mut deleted: usize = 0;
+    let count = {
+        let mut nds = WithFoods::new(ndcsv.records(), db)
+            .filter_map(|r| nutdata_row(r, fields, release, &cache, rejects).transpose());
+        insert_batches(&mut nds, |batch| {
+            let food_ids: Vec<i32> = batch
+                .iter()
+                .map(|nd| nd.food_id)
+                .filter(|id| replaced.insert(*id))
+                .collect();
+            let (d, count) = db.replace_nutrient_data(&food_ids, batch)?;
+            deleted += d;
+            Ok(count)
+        })
+    };
+    rejects.replaced(FOOD_NUTRIENT_CSV, release, deleted);
+    let count = count?;
+    rejects.loaded(FOOD_NUTRIENT_CSV, count);
+    Ok(count)
+}
+/// The id and serving size of a food
+type FoodKey = (i32, Option<f64>);
+/// A food_nutrient.csv record read with its food, if it's loaded, or the error looking the
+/// foods up
+type NutdataRecord =
+    Result<(Result<StringRecord, csv::Error>, Option<FoodKey>), Box<dyn Error + Send + Sync>>;
+/// Reads food_nutrient.csv records with their foods.  The foods are looked up BATCH_SIZE
+/// records at a time with one query, so only those of the records read ahead are held in
+/// memory rather than every food's.
+struct WithFoods<'a, I> {
+    records: I,
+    db: &'a dyn Ingest,
+    chunk: std::vec::IntoIter<Result<StringRecord, csv::Error>>,
+    foods: HashMap<String, FoodKey>,
+}
+impl<'a, I: Iterator<Item = Result<StringRecord, csv::Error>>> WithFoods<'a, I> {
+    fn new(records: I, db: &'a dyn Ingest) -> Self {
+        WithFoods {
+            records,
+            db,
+            chunk: Vec::new().into_iter(),
+            foods: HashMap::new(),
+        }
+    }
+}
+impl<'a, I: Iterator<Item = Result<StringRecord, csv::Error>>> Iterator for WithFoods<'a, I> {
+    type Item = NutdataRecord;
+    fn next(&mut self) -> Option<NutdataRecord> {
+        if self.chunk.len() == 0 {
+            let chunk: Vec<_> = self.records.by_ref().take(BATCH_SIZE).collect();
+            if chunk.is_empty() {
+                return None;
+            }
+            let fdc_ids: HashSet<String> = chunk
+                .iter()
+                .filter_map(|r| r.as_ref().ok()?.get(1).map(|f| f.to_string()))
+                .collect();
+            let fdc_ids: Vec<String> = fdc_ids.into_iter().collect();
+            match self.db.food_ids(&fdc_ids) {
+                Ok(foods) => {
+                    self.foods = foods
+                        .into_iter()
+                        .map(|(fdc_id, id, serving_size)| (fdc_id, (id, serving_size)))
+                        .collect()
+                }
+                Err(e) => return Some(Err(e)),
+            }
+            self.chunk = chunk.into_iter();
+        }
+        let r = self.chunk.next()?;
+        let food = match &r {
+            Ok(r) => r.get(1).and_then(|f| self.foods.get(f)).copied(),
+            Err(_) => None,
+        };
+        Some(Ok((r, food)))
+    }
+}
+/// Creates the Nutrientdata of a food_nutrient.csv record, None if it's rejected
+fn nutdata_row(
+    r: NutdataRecord,
+    fields: usize,
+    release: &str,
+    cache: &Cache,
+    rejects: &mut Rejects,
+) -> Result<Option<Nutrientdata>, Box<dyn Error + Send + Sync>> {
+    let (r, food) = r?;
+    let r = match record(r, FOOD_NUTRIENT_CSV, rejects)? {
+        Some(r) => r,
+        None => return Ok(None),
+    };
+    match nutdata(&r, food, fields, release, cache) {
+        Ok(nd) => Ok(Some(nd)),
+        Err(reason) => {
+            rejects.reject(FOOD_NUTRIENT_CSV, line(&r), &r, &reason)?;
+            Ok(None)
+        }
+    }
+}
+/// Creates the Nutrientdata of a food_nutrient.csv record or returns why it can't be
+fn nutdata(
+    r: &StringRecord,
+    food: Option<FoodKey>,
+    fields: usize,
+    release: &str,
+    cache: &Cache,
+) -> Result<Nutrientdata, String> {
+    if r.len() != fields {
+        return Err(self::fields(fields, r.len()));
+    }
+    let ndsv: NutdataCsv = r.deserialize(None).map_err(|e| e.to_string())?;
+    let (fid, serving_size) = food.ok_or_else(|| format!("unknown fdc_id {}", ndsv.fdc_id))?;
+    let mut nd = ndsv.create_nutdata(fid, release);
+    nd.portion_value = match serving_size {
+        Some(x) => (x / 100.0) * nd.value,
+        None => 0.0,
+    };
+    // some nutrient_id's in the csv are nutrientno's and others are nutrient ids
+    nd.nutrient_id = cache
+        .nutrient_id(ndsv.nutrient_id)
+        .ok_or_else(|| format!("unknown nutrient_id {}", ndsv.nutrient_id))?;
+    if !cache.has_derivation(ndsv.derivation_id) {
+        return Err(format!("unknown derivation_id {}", ndsv.derivation_id));
+    }
+    Ok(nd)
+}
+/// Returns a record read from a csv file, None if it can't be read, e.g. it isn't UTF-8,
+/// when it's rejected.  It's an error if the file can't be read at all.
+fn record(
+    r: Result<StringRecord, csv::Error>,
+    file: &str,
+    rejects: &mut Rejects,
+) -> Result<Option<StringRecord>, Box<dyn Error + Send + Sync>> {
+    match r {
+        Ok(r) => Ok(Some(r)),
+        Err(e) if e.is_io_error() => Err(Box::new(e)),
+        Err(e) => {
+            let line = e.position().map(|p| p.line());
+            rejects.reject(file, line, &StringRecord::new(), &e.to_string())?;
+            Ok(None)
+        }
+    }
+}
+/// Returns the line of a record in its file
+fn line(r: &StringRecord) -> Option<u64> {
+    r.position().map(|p| p.line())
+}
+#[derive(Deserialize, Debug)]
+struct Nutcsv {
+    id: i32,
+    name: String,
+    unit: String,
+    nbr: String,
+    _order: String,
+}
+impl Nutcsv {
+    fn create_nutrient(&self) -> Result<Nutrient, Box<dyn Error + Send + Sync>> {
+        let n = Nutrient {
+            id: self.id,
+            description: self.name.to_string(),
+            unit: self.unit.to_string(),
+            nutrientno: self.nbr.to_string(),
+        };
+        Ok(n)
+    }
+}
+/// Inserts nutrients csv into the database.  Nutrients already loaded are skipped and those
+/// without a nutrient_nbr rejected.
+pub fn process_nutrients(
+    path: String,
+    db: &dyn Ingest,
+    rejects: &mut Rejects,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let nutfile = format!("{}{}", path, NUTRIENT_CSV);
+    let mut recs = Reader::from_path(&nutfile)?;
+    let loaded: HashSet<i32> = db.nutrients()?.iter().map(|n| n.id).collect();
+    let mut nuts: Vec<Nutrient> = Vec::new();
+    for n in recs.records() {
+        let r = match record(n, NUTRIENT_CSV, rejects)? {
+            Some(r) => r,
+            None => continue,
+        };
+        let nut = match r.deserialize::<Nutcsv>(None) {
+            Ok(ncsv) => ncsv.create_nutrient()?,
+            Err(e) => {
+                rejects.reject(NUTRIENT_CSV, line(&r), &r, &e.to_string())?;
+                continue;
+            }
+        };
+        if loaded.contains(&nut.id) {
+            rejects.skipped(NUTRIENT_CSV, 1);
+        } else if nut.nutrientno.is_empty() {
+            rejects.reject(NUTRIENT_CSV, line(&r), &r, "missing nutrient_nbr")?;
+        } else {
+            nuts.push(nut);
+        }
+    }
+    let count = db.insert_nutrients(&nuts)?;
+    rejects.loaded(NUTRIENT_CSV, count);
+    Ok(count)
+}
+#[derive(Deserialize, Debug)]
+struct Dervcsv {
+    id: i32,
+    code: String,
+    description: String,
+    _source: String,
+}
+impl Dervcsv {
+    fn create_derivation(&self) -> Result<Derivation, Box<dyn Error + Send + Sync>> {
+        let d = Derivation {
+            id: self.id,
+            code: self.code.to_string(),
+            description: self.description.to_string(),
+        };
+        Ok(d)
+    }
+}
+/// Inserts derivation csv into the database.  Derivations already loaded are skipped.
+pub fn process_derivations(
+    path: String,
+    db: &dyn Ingest,
+    rejects: &mut Rejects,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let dervfile = format!("{}{}", path, DERIVATION_CSV);
+    let mut recs = Reader::from_path(&dervfile)?;
+    let loaded: HashSet<i32> = db.derivations()?.iter().map(|d| d.id).collect();
+    let mut dervs: Vec<Derivation> = Vec::new();
+    for d in recs.records() {
+        let r = match record(d, DERIVATION_CSV, rejects)? {
+            Some(r) => r,
+            None => continue,
+        };
+        let derv = match r.deserialize::<Dervcsv>(None) {
+            Ok(dcsv) => dcsv.create_derivation()?,
+            Err(e) => {
+                rejects.reject(DERIVATION_CSV, line(&r), &r, &e.to_string())?;
+                continue;
+            }
+        };
+        if loaded.contains(&derv.id) {
+            rejects.skipped(DERIVATION_CSV, 1);
+        } else {
+            dervs.push(derv);
+        }
+    }
+    let count = db.insert_derivations(&dervs)?;
+    rejects.loaded(DERIVATION_CSV, count);
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::CsvDir;
+    #[test]
+    fn join_by_header() {
+        let dir = CsvDir::new("join");
+        let food = ["fdc_id,data_type,description,publication_date"];
+        dir.write(FOOD_CSV, &[food[0], "1105904,branded_food,GRANOLA,2020-11-13"]);
+        // a later release's columns in another order, with one unknown
+        let branded =
+            "brand_owner,fdc_id,gtin_upc,new_column,market_country,package_weight,discontinued_date";
+        dir.write(BRANDED_FOOD_CSV, &[branded, "Acme,1105904,00012345,?,United States,12 oz/340 g,"]);
+        let mut rejects = Rejects::new(&dir.join("rejects.csv"), None);
+        let join = join_foods(&dir.path(), &mut rejects).unwrap();
+        let headers = join.headers().clone();
+        assert_eq!(headers.len(), 10);
+        let rows: Vec<_> = join.map(|r| r.unwrap()).collect();
+        let f: Foodcsv = rows[0].deserialize(Some(&headers)).unwrap();
+        assert_eq!((f.fdc_id.as_str(), f.description.as_str()), ("1105904", "GRANOLA"));
+        assert_eq!((f.owner.as_str(), f.upc.as_str()), ("Acme", "00012345"));
+        assert_eq!(f.country, "United States");
+        assert_eq!(f.package_weight, "12 oz/340 g");
+        assert!(f.brand.is_empty() && f.discontinued_date.is_empty());
+    }
+    #[test]
+    fn create_food() {
+        let csv = Foodcsv {
+            fdc_id: String::from("1105904"),
+            serving_size: String::from("32"),
+            date_published: String::from("2020-11-13"),
+            package_weight: String::from("12 oz/340 g"),
+            ..Default::default()
+        };
+        let f = csv.create_food().unwrap();
+        assert_eq!(f.serving_size, Some(32.0));
+        assert_eq!(f.publication_date.to_string(), "2020-11-13 19:00:00");
+        // empty dates are the epoch, other than the discontinued date
+        assert_eq!(f.modified_date.to_string(), "1970-01-01 19:00:00");
+        assert_eq!(f.discontinued_date, None);
+        assert_eq!(f.package_weight.as_deref(), Some("12 oz/340 g"));
+        assert_eq!(f.trade_channel, None);
+        let reason = |csv: Foodcsv| csv.create_food().unwrap_err();
+        assert_eq!(reason(Foodcsv::default()), "missing fdc_id");
+        let csv = Foodcsv {
+            fdc_id: String::from("1105904"),
+            ..Default::default()
+        };
+        assert_eq!(csv.create_food().unwrap().serving_size, Some(0.0));
+        let bad = |f: fn(&mut Foodcsv) -> &mut String, value: &str| {
+            let mut csv = Foodcsv {
+                fdc_id: String::from("1105904"),
+                ..Default::default()
+            };
+            *f(&mut csv) = value.to_string();
+            reason(csv)
+        };
+        assert_eq!(bad(|c| &mut c.serving_size, "a lot"), "invalid serving_size 'a lot'");
+        assert_eq!(
+            bad(|c| &mut c.date_published, "2020-13-01"),
+            "invalid publication_date '2020-13-01'"
+        );
+        assert_eq!(
+            bad(|c| &mut c.discontinued_date, "soon"),
+            "invalid discontinued_date 'soon'"
+        );
+    }
+    #[test]
+    fn same_food() {
+        let csv = Foodcsv {
+            fdc_id: String::from("1105904"),
+            description: String::from("GRANOLA"),
+            ..Default::default()
+        };
+        let mut f = csv.create_food().unwrap();
+        f.fdc_release = Some(String::from("2021-10"));
+        // the loaded food has its own id and release
+        let mut loaded = f.clone();
+        loaded.id = 7;
+        loaded.fdc_release = Some(String::from("2021-04"));
+        assert!(super::same_food(&f, &loaded));
+        loaded.description = String::from("OAT GRANOLA");
+        assert!(!super::same_food(&f, &loaded));
+    }
+    #[test]
+    fn create_nutdata() {
+        let csv = NutdataCsv {
+            _id: 1,
+            fdc_id: String::from("1105904"),
+            nutrient_id: 1003,
+            amount: 2.5,
+            data_points: String::from("12"),
+            derivation_id: 1,
+            min: String::from("1.9"),
+            max: String::from(" "),
+            median: String::from("2.4"),
+            footnote: String::from("Analyzed as eaten"),
+            min_year: String::from("n/a"),
+        };
+        let nd = csv.create_nutdata(7, "2021-10");
+        assert_eq!((nd.minimum, nd.maximum, nd.median), (Some(1.9), None, Some(2.4)));
+        assert_eq!((nd.data_points, nd.min_year), (Some(12), None));
+        assert_eq!(nd.footnote.as_deref(), Some("Analyzed as eaten"));
+        assert_eq!((nd.value, nd.food_id), (2.5, 7));
+        assert_eq!(nd.fdc_release.as_deref(), Some("2021-10"));
+    }
+}
@@ -0,0 +1,321 @@
+//! Keyset (cursor) pagination.  A page is read from a position in the sort
+//! order, i.e. the sort column value and id of a row, rather than skipping
+//! `offset` rows, so deep pages are as quick as the first and rows added or
+//! removed under a client don't shift what it sees next.
+use crate::filter::NutrientSort;
+use crate::models::{Brand, Food, Foodgroup, Nutrient, Nutrientdata};
+use std::error::Error;
+use std::fmt;
+
+/// The value of the sort column at a cursor position
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Key {
+    Number(f64),
+    Text(String),
+}
+impl Key {
+    pub fn text(&self) -> String {
+        match self {
+            Key::Text(s) => s.to_string(),
+            Key::Number(n) => n.to_string(),
+        }
+    }
+    pub fn number(&self) -> f64 {
+        match self {
+            Key::Number(n) => *n,
+            Key::Text(s) => s.parse().unwrap_or(0.0),
+        }
+    }
+}
+/// True if a browse order is descending.  An order is read here, whatever its
+/// case, so the queries and cursors agree on it.
+pub fn descending(order: &str) -> bool {
+    order.eq_ignore_ascii_case("desc")
+}
+/// Reads a browse order, asc or desc whatever its case.  It's the default if none, or an
+/// empty one, is given and None if it's neither.
+pub fn parse_order(order: Option<&str>, default: &'static str) -> Option<&'static str> {
+    match order.map(|o| o.trim()).filter(|o| !o.is_empty()) {
+        None => Some(default),
+        Some(o) if o.eq_ignore_ascii_case("asc") => Some("asc"),
+        Some(o) if descending(o) => Some("desc"),
+        Some(_) => None,
+    }
+}
+/// A position in a browse.  The rows of the next page follow it or, if
+/// `before` is set, the rows of the previous page precede it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Cursor {
+    /// the sort the cursor was created for
+    pub sort: String,
+    pub order: String,
+    /// sort column value, none when sorting by id
+    pub key: Option<Key>,
+    pub id: i32,
+    pub before: bool,
+}
+impl Cursor {
+    /// Returns the opaque string handed to clients
+    pub fn encode(&self) -> String {
+        base64::encode_config(
+            serde_json::to_vec(self).unwrap_or_default(),
+            base64::URL_SAFE_NO_PAD,
+        )
+    }
+    /// Reads a cursor handed back by a client.  The cursor must have been
+    /// created for the same sort and order.
+    pub fn decode(s: &str, sort: &str, order: &str) -> Result<Cursor, Box<dyn Error + Send + Sync>> {
+        let c = base64::decode_config(s, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|b| serde_json::from_slice::<Cursor>(&b).ok())
+            .ok_or_else(|| CursorError(s.to_string()))?;
+        if c.sort != sort || descending(&c.order) != descending(order) {
+            return Err(Box::new(CursorError(s.to_string())));
+        }
+        Ok(c)
+    }
+    /// True if the rows wanted come after the cursor position in the sort
+    /// column's natural (ascending) order
+    pub fn ascending(&self) -> bool {
+        descending(&self.order) == self.before
+    }
+    fn at<T: Keyed>(row: &T, sort: &str, order: &str, before: bool) -> Self {
+        Self {
+            sort: sort.to_string(),
+            order: order.to_string(),
+            key: row.key(sort),
+            id: row.row_id(),
+            before,
+        }
+    }
+}
+/// Returned when a cursor is garbled or belongs to a different sort
+#[derive(Debug)]
+pub struct CursorError(pub String);
+
+impl fmt::Display for CursorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid cursor {}", self.0)
+    }
+}
+
+impl Error for CursorError {}
+
+/// A page of rows with the cursors for the pages either side of it
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+impl<T: Keyed> Page<T> {
+    /// Builds a page from up to max + 1 rows read from a cursor.  Rows read
+    /// backwards, i.e. for a before cursor, arrive in reverse order.  The extra
+    /// row, if any, says there is another page beyond this one.
+    pub fn create(
+        mut rows: Vec<T>,
+        max: i64,
+        cursor: Option<&Cursor>,
+        sort: &str,
+        order: &str,
+    ) -> Self {
+        let max = max.max(0) as usize;
+        let more = rows.len() > max;
+        rows.truncate(max);
+        let before = cursor.map(|c| c.before).unwrap_or(false);
+        if before {
+            rows.reverse();
+        }
+        let (more_next, more_prev) = match cursor {
+            None => (more, false),
+            Some(_) if before => (true, more),
+            Some(_) => (more, true),
+        };
+        let next = match rows.last() {
+            Some(r) if more_next => Some(Cursor::at(r, sort, order, false).encode()),
+            _ => None,
+        };
+        let prev = match rows.first() {
+            Some(r) if more_prev => Some(Cursor::at(r, sort, order, true).encode()),
+            _ => None,
+        };
+        Page {
+            items: rows,
+            next,
+            prev,
+        }
+    }
+}
+impl<T> Page<T> {
+    /// Converts the items, keeping the cursors
+    pub fn map<U, F: FnMut(T) -> U>(self, f: F) -> Page<U> {
+        Page {
+            items: self.items.into_iter().map(f).collect(),
+            next: self.next,
+            prev: self.prev,
+        }
+    }
+}
+/// Rows which can be paged with a cursor
+pub trait Keyed {
+    fn row_id(&self) -> i32;
+    /// Returns the value of a browse's sort column
+    fn key(&self, sort: &str) -> Option<Key>;
+}
+impl Keyed for Food {
+    fn row_id(&self) -> i32 {
+        self.id
+    }
+    fn key(&self, sort: &str) -> Option<Key> {
+        match sort {
+            "description" => Some(Key::Text(self.description.to_string())),
+            "upc" => Some(Key::Text(self.upc.to_string())),
+            "fdcId" => Some(Key::Text(self.fdc_id.to_string())),
+            _ => None,
+        }
+    }
+}
+impl Keyed for Brand {
+    fn row_id(&self) -> i32 {
+        self.id
+    }
+    fn key(&self, sort: &str) -> Option<Key> {
+        match sort {
+            "owner" => Some(Key::Text(self.owner.to_string())),
+            _ => None,
+        }
+    }
+}
+impl Keyed for Foodgroup {
+    fn row_id(&self) -> i32 {
+        self.id
+    }
+    fn key(&self, sort: &str) -> Option<Key> {
+        match sort {
+            "group" => Some(Key::Text(self.description.to_string())),
+            _ => None,
+        }
+    }
+}
+impl Keyed for Nutrient {
+    fn row_id(&self) -> i32 {
+        self.id
+    }
+    fn key(&self, sort: &str) -> Option<Key> {
+        match sort {
+            "name" => Some(Key::Text(self.description.to_string())),
+            "nbr" => Some(Key::Text(self.nutrientno.to_string())),
+            _ => None,
+        }
+    }
+}
+impl Keyed for Nutrientdata {
+    fn row_id(&self) -> i32 {
+        self.id
+    }
+    fn key(&self, sort: &str) -> Option<Key> {
+        match sort {
+            "portion" => Some(Key::Number(self.portion_value)),
+            _ => Some(Key::Number(self.value)),
+        }
+    }
+}
+/// A row read along with the key of a sort computed by the query, i.e. its
+/// search relevance for the "relevance" sort or its value of a nutrient, if it
+/// has one, for a nutrient sort
+#[derive(Debug)]
+pub struct Ranked<T> {
+    pub item: T,
+    pub rank: Option<f64>,
+}
+impl<T> From<(T, Option<f64>)> for Ranked<T> {
+    fn from((item, rank): (T, Option<f64>)) -> Self {
+        Self { item, rank }
+    }
+}
+impl<T: Keyed> Keyed for Ranked<T> {
+    fn row_id(&self) -> i32 {
+        self.item.row_id()
+    }
+    fn key(&self, sort: &str) -> Option<Key> {
+        match sort {
+            "relevance" => self.rank.map(Key::Number),
+            s if NutrientSort::parse(s).is_some() => self.rank.map(Key::Number),
+            _ => self.item.key(sort),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn food(id: i32, description: &str) -> Food {
+        let mut f = Food::new();
+        f.id = id;
+        f.description = description.to_string();
+        f
+    }
+    #[test]
+    fn encode_decode_cursor() {
+        let c = Cursor::at(&food(7, "APPLE"), "description", "asc", false);
+        let s = c.encode();
+        assert_eq!(c, Cursor::decode(&s, "description", "asc").unwrap());
+        assert!(Cursor::decode(&s, "upc", "asc").is_err());
+        assert!(Cursor::decode("garbage", "description", "asc").is_err());
+    }
+    #[test]
+    fn read_order() {
+        assert_eq!(parse_order(Some("DESC"), "asc"), Some("desc"));
+        assert_eq!(parse_order(Some("Asc"), "desc"), Some("asc"));
+        assert_eq!(parse_order(Some(""), "desc"), Some("desc"));
+        assert_eq!(parse_order(None, "asc"), Some("asc"));
+        assert_eq!(parse_order(Some("up"), "asc"), None);
+    }
+    #[test]
+    fn decode_exact_keys() {
+        // a relevance serde_json misreads by a bit without float_roundtrip
+        let mut c = Cursor::at(&food(7, "APPLE"), "relevance", "DESC", false);
+        c.key = Some(Key::Number(21.291890726713458));
+        let d = Cursor::decode(&c.encode(), "relevance", "desc").unwrap();
+        assert_eq!(c.key, d.key);
+        assert!(!d.ascending());
+    }
+    #[test]
+    fn page_cursors() {
+        let rows = vec![food(1, "A"), food(2, "B"), food(3, "C")];
+        let p = Page::create(rows, 2, None, "description", "asc");
+        assert_eq!(2, p.items.len());
+        assert!(p.prev.is_none());
+        let next = Cursor::decode(&p.next.unwrap(), "description", "asc").unwrap();
+        assert_eq!(2, next.id);
+        assert!(next.ascending());
+        let mut back = next.clone();
+        back.before = true;
+        let rows = vec![food(1, "A")];
+        let p = Page::create(rows, 2, Some(&back), "description", "asc");
+        assert_eq!(1, p.items[0].id);
+        assert!(p.prev.is_none());
+        assert!(p.next.is_some());
+        assert!(!back.ascending());
+    }
+    #[test]
+    fn ranked_page_cursors() {
+        // a relevance cursor holds the rank, which the food hasn't
+        let rows: Vec<Ranked<Food>> = vec![
+            (food(3, "CUPS"), Some(4.5)).into(),
+            (food(1, "BUTTER"), Some(2.0)).into(),
+            (food(2, "JAM"), Some(1.0)).into(),
+        ];
+        let p = Page::create(rows, 2, None, "relevance", "desc").map(|r| r.item);
+        assert_eq!(vec![3, 1], p.items.iter().map(|f| f.id).collect::<Vec<_>>());
+        let next = Cursor::decode(&p.next.unwrap(), "relevance", "desc").unwrap();
+        assert!(!next.ascending());
+        assert_eq!((next.id, next.key), (1, Some(Key::Number(2.0))));
+        // and a nutrient cursor has no key for a food without the nutrient
+        let rows: Vec<Ranked<Food>> =
+            vec![(food(2, "JAM"), None).into(), (food(5, "TEA"), None).into()];
+        let p = Page::create(rows, 1, None, "nutrient:203", "asc");
+        let next = Cursor::decode(&p.next.unwrap(), "nutrient:203", "asc").unwrap();
+        assert_eq!((next.id, next.key), (2, None));
+    }
+}
@@ -0,0 +1,135 @@
+//! Facet counts:  how many of the foods a filter matches fall in each food
+//! group, brand owner, country and datasource, e.g. for a search's sidebar.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// The values counted when none is asked for
+pub const DEFAULT_VALUES: i64 = 10;
+
+/// The food columns foods are counted by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FacetField {
+    FoodGroup,
+    Owner,
+    Country,
+    Datasource,
+}
+impl FacetField {
+    pub const ALL: [FacetField; 4] = [
+        FacetField::FoodGroup,
+        FacetField::Owner,
+        FacetField::Country,
+        FacetField::Datasource,
+    ];
+    pub fn parse(field: &str) -> Result<FacetField, Box<dyn Error + Send + Sync>> {
+        match field.trim().to_lowercase().as_str() {
+            "fg" | "group" | "foodgroup" => Ok(FacetField::FoodGroup),
+            "owner" | "brand" => Ok(FacetField::Owner),
+            "country" => Ok(FacetField::Country),
+            "datasource" => Ok(FacetField::Datasource),
+            _ => Err(Box::new(FacetError(field.to_string()))),
+        }
+    }
+    /// Parses a comma separated list of fields.  "all" is every field.
+    pub fn parse_list(fields: &str) -> Result<Vec<FacetField>, Box<dyn Error + Send + Sync>> {
+        if fields.trim().eq_ignore_ascii_case("all") {
+            return Ok(FacetField::ALL.to_vec());
+        }
+        let mut list = Vec::new();
+        for f in fields.split(',').filter(|f| !f.trim().is_empty()) {
+            let f = FacetField::parse(f)?;
+            if !list.contains(&f) {
+                list.push(f);
+            }
+        }
+        Ok(list)
+    }
+}
+/// The number of foods with a value
+#[derive(Serialize, Debug, PartialEq)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+impl FacetCount {
+    /// Names counts of a food's brand or food group id, "unknown" if an id
+    /// isn't in names
+    pub fn named(rows: Vec<(i32, i64)>, names: &HashMap<i32, String>) -> Vec<FacetCount> {
+        rows.into_iter()
+            .map(|(id, count)| FacetCount {
+                value: names
+                    .get(&id)
+                    .map(|n| n.to_string())
+                    .unwrap_or_else(|| "unknown".to_string()),
+                count,
+            })
+            .collect()
+    }
+    /// Counts of a column which may be null, "unknown" if it is
+    pub fn nullable(rows: Vec<(Option<String>, i64)>) -> Vec<FacetCount> {
+        rows.into_iter()
+            .map(|(v, count)| FacetCount {
+                value: v.unwrap_or_else(|| "unknown".to_string()),
+                count,
+            })
+            .collect()
+    }
+}
+/// The counts for each field asked for, largest first.  Fields not asked for
+/// are None.
+#[derive(Serialize, Debug, Default)]
+pub struct Facets {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub food_groups: Option<Vec<FacetCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owners: Option<Vec<FacetCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub countries: Option<Vec<FacetCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub datasources: Option<Vec<FacetCount>>,
+}
+/// Returned for an unknown facet field
+#[derive(Debug)]
+pub struct FacetError(pub String);
+
+impl fmt::Display for FacetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unknown facet {}.  Use fg, owner, country, datasource or all",
+            self.0
+        )
+    }
+}
+
+impl Error for FacetError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_facets() {
+        assert_eq!(FacetField::parse_list("all").unwrap(), FacetField::ALL.to_vec());
+        assert_eq!(
+            FacetField::parse_list("Country, fg,country").unwrap(),
+            vec![FacetField::Country, FacetField::FoodGroup]
+        );
+        assert!(FacetField::parse_list("").unwrap().is_empty());
+        assert!(FacetField::parse_list("fg,nutrient").is_err());
+        let names: HashMap<i32, String> = vec![(1, "Acme".to_string())].into_iter().collect();
+        assert_eq!(
+            FacetCount::named(vec![(1, 3), (2, 1)], &names),
+            vec![
+                FacetCount {
+                    value: "Acme".to_string(),
+                    count: 3
+                },
+                FacetCount {
+                    value: "unknown".to_string(),
+                    count: 1
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! Typed predicates for browsing and counting foods
+use crate::search::SearchQuery;
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::Regex;
+use std::error::Error;
+use std::fmt;
+
+/// Restricts a foods browse or count.  Unset fields don't filter.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FoodFilter {
+    /// search matched against the food description and ingredients
+    pub query: Option<SearchQuery>,
+    /// also match descriptions containing misspellings of the search terms
+    pub fuzzy: bool,
+    pub publication_date: Option<DateRange>,
+    pub brand_id: Option<i32>,
+    /// the brand owner, brand name and sub-brand, matched exactly
+    pub owner: Option<String>,
+    pub brand: Option<String>,
+    pub subbrand: Option<String>,
+    pub food_group_id: Option<i32>,
+    pub country: Option<String>,
+    pub datasource: Option<String>,
+    pub serving_unit: Option<String>,
+    /// ranges the foods' nutrient values must all fall in
+    pub nutrients: Vec<NutrientRange>,
+    /// the foods a search index matched, most relevant first, with their
+    /// scores.  An index sets these in place of query.
+    pub hits: Option<Vec<(i32, f64)>>,
+}
+impl FoodFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Parses and sets the search.  A blank search doesn't filter.
+    pub fn search(&mut self, q: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.query = match q.trim() {
+            "" => None,
+            q => Some(SearchQuery::parse(q)?),
+        };
+        Ok(())
+    }
+}
+/// An inclusive range of dates, from the start of the first day through the end
+/// of the last
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DateRange {
+    pub from: NaiveDateTime,
+    pub to: NaiveDateTime,
+}
+impl DateRange {
+    pub fn new(from: NaiveDate, to: NaiveDate) -> Self {
+        Self {
+            from: from.and_hms_opt(0, 0, 0).unwrap(),
+            to: to.and_hms_opt(23, 59, 59).unwrap(),
+        }
+    }
+    /// Parses a "from:to" range, e.g. "2020-01-01:2020-06-30".  Dates may be
+    /// separated by dashes, slashes or spaces.  The through date defaults to the
+    /// from date.
+    pub fn parse(range: &str) -> Result<DateRange, Box<dyn Error + Send + Sync>> {
+        let dv = range.split(':').collect::<Vec<&str>>();
+        if dv.len() > 2 {
+            return Err(Box::new(DateRangeError(range.to_string())));
+        }
+        let from = parse_date(dv[0]).ok_or_else(|| DateRangeError(range.to_string()))?;
+        let to = match dv.get(1) {
+            Some(d) => parse_date(d).ok_or_else(|| DateRangeError(range.to_string()))?,
+            None => from,
+        };
+        if to < from {
+            return Err(Box::new(DateRangeError(range.to_string())));
+        }
+        Ok(DateRange::new(from, to))
+    }
+}
+/// Whether a nutrient value is per 100 grams (or ml) or per serving
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Basis {
+    Unit,
+    Portion,
+}
+impl Basis {
+    /// The nutrient_data column holding values on this basis
+    pub fn column(&self) -> &'static str {
+        match self {
+            Basis::Unit => "value",
+            Basis::Portion => "portion_value",
+        }
+    }
+}
+/// A range a nutrient's value must fall in.  An unset bound is open.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NutrientRange {
+    pub nutrientno: String,
+    pub basis: Basis,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+impl NutrientRange {
+    /// Parses "nbr:min:max" with an optional ":portion" for per serving values,
+    /// e.g. "203:10:" for at least 10 g of protein per 100 g or "307::140:portion"
+    /// for at most 140 mg of sodium a serving
+    pub fn parse(range: &str) -> Result<NutrientRange, Box<dyn Error + Send + Sync>> {
+        let err = || NutrientRangeError(range.to_string());
+        let rv: Vec<&str> = range.split(':').map(|r| r.trim()).collect();
+        if rv.len() < 3 || rv.len() > 4 || rv[0].is_empty() {
+            return Err(Box::new(err()));
+        }
+        let bound = |b: &str| match b {
+            "" => Ok(None),
+            b => b.parse::<f64>().map(Some).map_err(|_e| err()),
+        };
+        let basis = match rv.get(3).map(|b| b.to_lowercase()) {
+            None => Basis::Unit,
+            Some(b) if b == "portion" => Basis::Portion,
+            Some(b) if b == "value" || b.is_empty() => Basis::Unit,
+            Some(_) => return Err(Box::new(err())),
+        };
+        NutrientRange::new(rv[0], basis, bound(rv[1])?, bound(rv[2])?)
+            .map_err(|_e| Box::new(err()) as Box<dyn Error + Send + Sync>)
+    }
+    /// Returns a range any value is in, i.e. matching the foods with the
+    /// nutrient
+    pub fn any(nutrientno: &str, basis: Basis) -> NutrientRange {
+        NutrientRange {
+            nutrientno: nutrientno.to_string(),
+            basis,
+            min: None,
+            max: None,
+        }
+    }
+    /// Returns a range with at least one bound, the min no greater than the max.
+    /// The bounds must be finite, as histogram edges are:  a NaN bound would
+    /// match nothing and backends disagree on infinities.
+    pub fn new(
+        nutrientno: &str,
+        basis: Basis,
+        min: Option<f64>,
+        max: Option<f64>,
+    ) -> Result<NutrientRange, Box<dyn Error + Send + Sync>> {
+        let r = NutrientRange {
+            nutrientno: nutrientno.to_string(),
+            basis,
+            min,
+            max,
+        };
+        if min.iter().chain(max.iter()).any(|b| !b.is_finite()) {
+            return Err(Box::new(NutrientRangeError(r.to_string())));
+        }
+        match (min, max) {
+            (None, None) => Err(Box::new(NutrientRangeError(r.to_string()))),
+            (Some(a), Some(b)) if a > b => Err(Box::new(NutrientRangeError(r.to_string()))),
+            _ => Ok(r),
+        }
+    }
+}
+impl fmt::Display for NutrientRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bound = |b: Option<f64>| b.map(|v| v.to_string()).unwrap_or_default();
+        write!(f, "{}:{}:{}", self.nutrientno, bound(self.min), bound(self.max))?;
+        match self.basis {
+            Basis::Portion => write!(f, ":portion"),
+            Basis::Unit => Ok(()),
+        }
+    }
+}
+/// A foods browse sorted by their values of a nutrient, written in the sort
+/// as "nutrient:nbr" or "nutrient:nbr:portion"
+#[derive(Debug, Clone, PartialEq)]
+pub struct NutrientSort {
+    pub nutrientno: String,
+    pub basis: Basis,
+}
+impl NutrientSort {
+    pub fn new(nutrientno: &str, basis: Basis) -> Self {
+        Self {
+            nutrientno: nutrientno.to_string(),
+            basis,
+        }
+    }
+    /// Returns the nutrient a sort is on, None if it's some other sort
+    pub fn parse(sort: &str) -> Option<NutrientSort> {
+        let sv: Vec<&str> = sort.split(':').collect();
+        if sv.len() < 2 || sv.len() > 3 || sv[0] != "nutrient" || sv[1].is_empty() {
+            return None;
+        }
+        match sv.get(2) {
+            None => Some(NutrientSort::new(sv[1], Basis::Unit)),
+            Some(b) if b.eq_ignore_ascii_case("portion") => {
+                Some(NutrientSort::new(sv[1], Basis::Portion))
+            }
+            Some(_) => None,
+        }
+    }
+}
+impl fmt::Display for NutrientSort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "nutrient:{}", self.nutrientno)?;
+        match self.basis {
+            Basis::Portion => write!(f, ":portion"),
+            Basis::Unit => Ok(()),
+        }
+    }
+}
+fn parse_date(d: &str) -> Option<NaiveDate> {
+    let re = Regex::new(r"^(?P<y>\d{4})[-/ ]?(?P<m>\d{2})[-/ ]?(?P<d>\d{2})$").unwrap();
+    let c = re.captures(d.trim())?;
+    NaiveDate::from_ymd_opt(
+        c["y"].parse().ok()?,
+        c["m"].parse().ok()?,
+        c["d"].parse().ok()?,
+    )
+}
+/// Returned when a date range can't be parsed
+#[derive(Debug)]
+pub struct DateRangeError(pub String);
+
+impl fmt::Display for DateRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid date range {}.  Use YYYY-MM-DD or YYYY-MM-DD:YYYY-MM-DD",
+            self.0
+        )
+    }
+}
+
+impl Error for DateRangeError {}
+
+/// Returned when a nutrient range can't be parsed
+#[derive(Debug)]
+pub struct NutrientRangeError(pub String);
+
+impl fmt::Display for NutrientRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid nutrient range {}.  Use nbr:min:max or nbr:min:max:portion with at least one of min and max",
+            self.0
+        )
+    }
+}
+
+impl Error for NutrientRangeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn parse_date_range() {
+        let r = DateRange::parse("2020-01-01:2020/06/30").unwrap();
+        assert_eq!(NaiveDate::from_ymd_opt(2020, 1, 1).unwrap(), r.from.date());
+        assert_eq!(NaiveDate::from_ymd_opt(2020, 6, 30).unwrap(), r.to.date());
+        let r = DateRange::parse("20200101").unwrap();
+        assert_eq!(r.from.date(), r.to.date());
+        assert!(DateRange::parse("2020-06-30:2020-01-01").is_err());
+        assert!(DateRange::parse("2020-13-01").is_err());
+        assert!(DateRange::parse("last week").is_err());
+    }
+    #[test]
+    fn parse_nutrient_range() {
+        let r = NutrientRange::parse("307::140:portion").unwrap();
+        assert_eq!(r.nutrientno, "307");
+        assert_eq!(r.basis, Basis::Portion);
+        assert_eq!((r.min, r.max), (None, Some(140.0)));
+        assert_eq!(r.to_string(), "307::140:portion");
+        let r = NutrientRange::parse("203:10:").unwrap();
+        assert_eq!((r.basis, r.min, r.max), (Basis::Unit, Some(10.0), None));
+        assert!(NutrientRange::parse("203::").is_err());
+        assert!(NutrientRange::parse("203:5:1").is_err());
+        assert!(NutrientRange::parse("203:lots:").is_err());
+        assert!(NutrientRange::parse("203:1:2:serving").is_err());
+        assert!(NutrientRange::parse("203:NaN:").is_err());
+        assert!(NutrientRange::parse("203::inf").is_err());
+        assert!(NutrientRange::parse("203:-inf:10").is_err());
+        assert!(NutrientRange::new("203", Basis::Unit, Some(f64::NAN), None).is_err());
+        let by = NutrientSort::parse("nutrient:291:Portion").unwrap();
+        assert_eq!(by, NutrientSort::new("291", Basis::Portion));
+        assert_eq!(by.to_string(), "nutrient:291:portion");
+        assert_eq!(NutrientSort::parse("nutrient:291").unwrap().basis, Basis::Unit);
+        assert!(NutrientSort::parse("nutrient:").is_none());
+        assert!(NutrientSort::parse("description").is_none());
+    }
+}
@@ -0,0 +1,127 @@
+//! Csv files for the ingest tests.  The backends' tests load the same few
+//! nutrients, derivations and foods, so the files are written here once rather
+//! than by each test.  Built for bfpd's own tests and, with the fixtures
+//! feature, for the backends' dev-dependencies.
+use crate::csv::{
+    process_derivations, process_foods, process_nutdata, process_nutrients, Ingest, BATCH_SIZE,
+    FOODS, NUTRIENT_DATA,
+};
+use crate::reject::Rejects;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+/// nutrient.csv's header
+pub const NUTRIENT_HEADER: &str = "id,name,unit_name,nutrient_nbr,rank";
+/// food_nutrient_derivation.csv's header
+pub const DERIVATION_HEADER: &str = "id,code,description,source_id";
+/// food_nutrient.csv's header
+pub const NUTDATA_HEADER: &str =
+    "id,fdc_id,nutrient_id,amount,data_points,derivation_id,min,max,median,footnote,min_year";
+/// Protein, nutrient 1003 numbered 203
+pub const PROTEIN: &str = "1003,Protein,G,203,600";
+/// A label derivation, id 70
+pub const LABEL: &str = "70,LCCS,Label,9";
+
+/// A test's own directory of csv files in the temp directory, removed when it's
+/// dropped
+pub struct CsvDir(PathBuf);
+impl CsvDir {
+    /// Creates an empty directory named after the test
+    pub fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("bfpd-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        CsvDir(dir)
+    }
+    /// Creates a directory with the supporting csv files:  protein and the label
+    /// derivation, which every food_nutrient.csv row of the tests refers to
+    pub fn supporting(name: &str) -> Self {
+        let dir = CsvDir::new(name);
+        dir.write("nutrient.csv", &[NUTRIENT_HEADER, PROTEIN]);
+        dir.write("food_nutrient_derivation.csv", &[DERIVATION_HEADER, LABEL]);
+        dir
+    }
+    /// Writes a file of the lines, replacing any already there
+    pub fn write(&self, file: &str, lines: &[&str]) {
+        fs::write(self.join(file), lines.join("\n")).unwrap();
+    }
+    /// Writes food_nutrient.csv of the rows after its header
+    pub fn nutdata(&self, rows: &[&str]) {
+        self.write("food_nutrient.csv", &[&[NUTDATA_HEADER], rows].concat());
+    }
+    /// Returns the path of a file in the directory
+    pub fn join(&self, file: &str) -> PathBuf {
+        self.0.join(file)
+    }
+    /// Returns the directory as the ingest functions take it, with a trailing /
+    pub fn path(&self) -> String {
+        format!("{}/", self.0.display())
+    }
+}
+impl Drop for CsvDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+/// Loads two foods and more than a batch of nutrient data, with a row for a food
+/// that isn't loaded, into an empty database and checks what's in it.  The
+/// backends run it against their bulk loaders, or SQLite its inserts.
+pub fn bulk_load(db: &dyn Ingest, name: &str) {
+    let dir = CsvDir::supporting(name);
+    let food = ["fdc_id,description", "1,PEANUT BUTTER", "2,GRANOLA"];
+    dir.write("food.csv", &food);
+    let branded = [
+        "fdc_id,brand_owner,serving_size",
+        "1,Acme,32",
+        "2,Acme Mills,40",
+    ];
+    dir.write("branded_food.csv", &branded);
+    let mut nd: Vec<String> = (1..=BATCH_SIZE)
+        .map(|i| format!("{},1,203,{},,70,,,,,", i, i))
+        .collect();
+    nd.push(format!("{},9,203,1,,70,,,,,", BATCH_SIZE + 1));
+    nd.push(format!("{},2,203,10,,70,,,,,", BATCH_SIZE + 2));
+    dir.nutdata(&nd.iter().map(String::as_str).collect::<Vec<_>>());
+    let path = dir.path();
+    let mut rejects = Rejects::new(&dir.join("rejects.csv"), None);
+    assert_eq!(
+        process_nutrients(path.clone(), db, &mut rejects).unwrap(),
+        1
+    );
+    assert_eq!(
+        process_derivations(path.clone(), db, &mut rejects).unwrap(),
+        1
+    );
+    assert_eq!(
+        process_foods(path.clone(), "2021-04", false, db, &mut rejects).unwrap(),
+        2
+    );
+    assert_eq!(db.checkpoint(FOODS).unwrap().unwrap().last_fdc_id, "2");
+    let count = process_nutdata(path, "2021-04", false, db, &mut rejects).unwrap();
+    assert_eq!(count, BATCH_SIZE + 1);
+    let at = db.checkpoint(NUTRIENT_DATA).unwrap().unwrap();
+    assert_eq!(
+        (at.last_row, at.last_fdc_id.as_str()),
+        (BATCH_SIZE as i64 + 2, "2")
+    );
+    let foods = db
+        .foods_by_fdc_id(&["1".to_string(), "2".to_string()])
+        .unwrap();
+    assert_eq!(foods.len(), 2);
+    assert!(foods
+        .iter()
+        .all(|f| f.fdc_release.as_deref() == Some("2021-04")));
+    // the brands are inserted with the foods that are the first of theirs
+    assert_ne!(foods[0].brand_id, foods[1].brand_id);
+    let granola = foods.iter().find(|f| f.fdc_id == "2").unwrap();
+    let values = db.nutrient_data(granola, &[]).unwrap();
+    assert_eq!(values.len(), 1);
+    assert_eq!(
+        (values[0].value, values[0].nutrient_no.as_str()),
+        (10.0, "203")
+    );
+    let butter = foods.iter().find(|f| f.fdc_id == "1").unwrap();
+    assert_eq!(db.nutrient_data(butter, &[]).unwrap().len(), BATCH_SIZE);
+}
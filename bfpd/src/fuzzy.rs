@@ -0,0 +1,123 @@
+//! Typo tolerant matching.  Strings are compared by their trigrams as in
+//! PostgreSQL's pg_trgm, which backs the PostgreSQL store.  The other stores use
+//! the functions here instead.
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+/// Names less similar than this aren't suggested
+pub const SUGGEST_THRESHOLD: f64 = 0.3;
+/// A brand owner or food group this similar to the one asked for is used in its
+/// place, i.e. the name is taken to be a typo
+pub const MATCH_THRESHOLD: f64 = 0.6;
+
+/// Returns a string's trigrams.  As with pg_trgm each word is lower cased and
+/// padded with two spaces in front and one behind, so "Oat" gives "  o", " oa",
+/// "oat" and "at ".
+pub fn trigrams(s: &str) -> HashSet<String> {
+    let mut t = HashSet::new();
+    for w in words(s) {
+        let padded: Vec<char> = format!("  {} ", w).chars().collect();
+        for g in padded.windows(3) {
+            t.insert(g.iter().collect());
+        }
+    }
+    t
+}
+/// Returns how alike two strings are, from 0 (no trigrams in common) to 1
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (trigrams(a), trigrams(b));
+    let all = a.union(&b).count();
+    if all == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / all as f64
+}
+/// Returns up to max of the rows whose name is at least SUGGEST_THRESHOLD
+/// similar to name, most similar first
+pub fn closest<T, F: Fn(&T) -> &str>(name: &str, rows: Vec<T>, key: F, max: usize) -> Vec<(T, f64)> {
+    let mut scored: Vec<(T, f64)> = rows
+        .into_iter()
+        .map(|r| {
+            let s = similarity(name, key(&r));
+            (r, s)
+        })
+        .filter(|(_, s)| *s >= SUGGEST_THRESHOLD)
+        .collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.truncate(max);
+    scored
+}
+/// Returns the LIKE patterns matching the inner trigrams of a word, e.g.
+/// "%pea%", "%ean%", "%anu%" and "%nut%" for peanut.  A food description
+/// containing most of them is likely to contain the word or a misspelling of
+/// it.  Only letters and digits are kept so the patterns are safe to quote.
+pub fn like_patterns(word: &str) -> Vec<String> {
+    let chars: Vec<char> = word.to_lowercase().chars().filter(|c| c.is_alphanumeric()).collect();
+    if chars.len() < 3 {
+        return vec![format!("%{}%", chars.iter().collect::<String>())];
+    }
+    let mut p: Vec<String> = chars
+        .windows(3)
+        .map(|g| format!("%{}%", g.iter().collect::<String>()))
+        .collect();
+    p.dedup();
+    p
+}
+/// Returns the LIKE patterns of the inner trigrams of each of a string's words
+pub fn text_patterns(s: &str) -> Vec<String> {
+    words(s).flat_map(|w| like_patterns(&w)).collect()
+}
+fn words(s: &str) -> impl Iterator<Item = String> + '_ {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+}
+/// Returned when nothing is close enough to the name looked up.  Holds the
+/// names which came closest.
+#[derive(Debug)]
+pub struct NoMatchError {
+    pub name: String,
+    pub suggestions: Vec<String>,
+}
+impl NoMatchError {
+    pub fn new(name: &str, suggestions: Vec<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            suggestions,
+        }
+    }
+}
+
+impl fmt::Display for NoMatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.suggestions.is_empty() {
+            true => write!(f, "{} not found", self.name),
+            false => write!(
+                f,
+                "{} not found.  Did you mean {}?",
+                self.name,
+                self.suggestions.join(" or ")
+            ),
+        }
+    }
+}
+
+impl Error for NoMatchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn trigram_similarity() {
+        assert_eq!(trigrams("Oat").len(), 4);
+        assert_eq!(similarity("General Mills, Inc.", "general mills inc"), 1.0);
+        assert!(similarity("General Mills, Inc.", "Genral Mills, Inc.") >= MATCH_THRESHOLD);
+        assert!(similarity("General Mills, Inc.", "Kellogg Company") < SUGGEST_THRESHOLD);
+        let owners = vec!["Kellogg Company", "General Mills, Inc.", "General Foods"];
+        let c = closest("Genral Mills", owners, |o| o, 5);
+        assert_eq!(c[0].0, "General Mills, Inc.");
+        assert!(c.iter().all(|(o, _)| *o != "Kellogg Company"));
+        assert_eq!(like_patterns("Oats"), vec!["%oat%", "%ats%"]);
+    }
+}
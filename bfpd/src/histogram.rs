@@ -0,0 +1,158 @@
+//! Histograms of a nutrient's values.  The bins are either a number of equal
+//! width bins spanning the values or explicit edges, and the database counts
+//! the values in each.
+use std::error::Error;
+use std::fmt;
+
+/// The bins when neither a count nor edges are asked for
+pub const DEFAULT_BINS: usize = 10;
+/// The most bins a histogram may have
+pub const MAX_BINS: usize = 100;
+
+/// How a histogram's values are binned
+#[derive(Debug, Clone, PartialEq)]
+pub enum Bins {
+    /// equal width bins from the least value to the greatest
+    Count(usize),
+    /// bins between consecutive edges, which ascend
+    Edges(Vec<f64>),
+}
+impl Bins {
+    /// Returns a number of bins from 1 to MAX_BINS
+    pub fn count(n: i64) -> Result<Bins, Box<dyn Error + Send + Sync>> {
+        match n >= 1 && n <= MAX_BINS as i64 {
+            true => Ok(Bins::Count(n as usize)),
+            false => Err(Box::new(HistogramError(format!(
+                "Invalid bin count {}.  Use 1 to {}",
+                n, MAX_BINS
+            )))),
+        }
+    }
+    /// Checks edges are finite, ascend and make 1 to MAX_BINS bins.  The edges
+    /// are written into the query's SQL so inf or NaN would break it.
+    pub fn edges(edges: Vec<f64>) -> Result<Bins, Box<dyn Error + Send + Sync>> {
+        let ascending = edges.windows(2).all(|e| e[0] < e[1]);
+        let finite = edges.iter().all(|e| e.is_finite());
+        match edges.len() >= 2 && edges.len() <= MAX_BINS + 1 && ascending && finite {
+            true => Ok(Bins::Edges(edges)),
+            false => Err(Box::new(HistogramError(format!(
+                "Invalid edges.  Use 2 to {} ascending finite values",
+                MAX_BINS + 1
+            )))),
+        }
+    }
+    /// Parses a comma separated list of edges, e.g. "0,1,5,10"
+    pub fn parse_edges(list: &str) -> Result<Bins, Box<dyn Error + Send + Sync>> {
+        let mut edges = Vec::new();
+        for e in list.split(',').map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            edges.push(e.parse::<f64>().map_err(|_e| {
+                HistogramError(format!("Invalid edge {}.  Edges must be numbers", e))
+            })?);
+        }
+        Bins::edges(edges)
+    }
+}
+/// Returns the edges of n bins from min to max.  Log bins are of equal width
+/// in log(1 + value) so values of 0, which many foods have, are binned too;
+/// any values below 0 then fall below the first bin.  Values all alike make a
+/// single bin.
+pub fn even_edges(min: f64, max: f64, n: usize, log: bool) -> Vec<f64> {
+    if max <= min {
+        return vec![min, max];
+    }
+    let (from, to) = match log {
+        true => (min.max(0.0).ln_1p(), max.ln_1p()),
+        false => (min, max),
+    };
+    let width = (to - from) / n as f64;
+    let mut edges: Vec<f64> = (0..=n)
+        .map(|i| match i {
+            i if i == n => to,
+            i => from + width * i as f64,
+        })
+        .map(|e| if log { e.exp_m1() } else { e })
+        .collect();
+    // the outer edges are exactly the values so rounding can't leave them out
+    edges[0] = if log { min.max(0.0) } else { min };
+    edges[n] = max;
+    edges
+}
+/// The number of values from lower up to upper, including upper in the last
+/// bin
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Bin {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: i64,
+}
+/// The values counted in each bin and, for explicit edges, outside them
+#[derive(Serialize, Debug, PartialEq, Default)]
+pub struct Histogram {
+    pub bins: Vec<Bin>,
+    pub below: i64,
+    pub above: i64,
+}
+impl Histogram {
+    /// Creates a histogram from its edges and the counts of the values in
+    /// each numbered bin, -1 being below the first and edges.len() - 1 above
+    /// the last.  Bins not counted are empty.
+    pub fn create(edges: &[f64], counts: Vec<(i32, i64)>) -> Self {
+        let mut h = Histogram {
+            bins: edges
+                .windows(2)
+                .map(|e| Bin {
+                    lower: e[0],
+                    upper: e[1],
+                    count: 0,
+                })
+                .collect(),
+            below: 0,
+            above: 0,
+        };
+        for (bin, count) in counts {
+            match bin {
+                b if b < 0 => h.below += count,
+                b if b as usize >= h.bins.len() => h.above += count,
+                b => h.bins[b as usize].count += count,
+            }
+        }
+        h
+    }
+}
+/// Returned for a bad bin count or edges
+#[derive(Debug)]
+pub struct HistogramError(pub String);
+
+impl fmt::Display for HistogramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for HistogramError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn bin_edges() {
+        assert_eq!(even_edges(0.0, 10.0, 4, false), vec![0.0, 2.5, 5.0, 7.5, 10.0]);
+        let log = even_edges(0.0, 999.0, 3, true);
+        assert_eq!((log[0], log[3]), (0.0, 999.0));
+        assert!((log[1] - 9.0).abs() < 1e-9 && (log[2] - 99.0).abs() < 1e-9);
+        assert_eq!(even_edges(3.0, 3.0, 10, false), vec![3.0, 3.0]);
+        assert_eq!(
+            Bins::parse_edges("0, 1,5").unwrap(),
+            Bins::Edges(vec![0.0, 1.0, 5.0])
+        );
+        assert!(Bins::parse_edges("5,1").is_err());
+        assert!(Bins::parse_edges("1").is_err());
+        assert!(Bins::parse_edges("0,inf").is_err());
+        assert!(Bins::parse_edges("-inf,0").is_err());
+        assert!(Bins::parse_edges("0,NaN").is_err());
+        assert!(Bins::count(0).is_err());
+        let h = Histogram::create(&[0.0, 1.0, 5.0], vec![(-1, 2), (0, 3), (2, 1)]);
+        let counts: Vec<i64> = h.bins.iter().map(|b| b.count).collect();
+        assert_eq!((counts, h.below, h.above), (vec![3, 0], 2, 1));
+    }
+}
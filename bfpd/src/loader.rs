@@ -0,0 +1,64 @@
+//! Batch loading of the data a page of food views needs.  Nutrient data,
+//! brand owners and food groups are read for every food on the page at once
+//! rather than food by food.
+use crate::models::{Brand, Derivation, Food, Foodgroup, Nutrient, Nutrientdata, NutrientdataForm};
+use std::collections::HashMap;
+
+/// The nutrient data, brands and food groups of a page of foods
+#[derive(Debug, Default)]
+pub struct FoodDetails {
+    nutrient_data: HashMap<i32, Vec<NutrientdataForm>>,
+    brands: HashMap<i32, Brand>,
+    groups: HashMap<i32, String>,
+}
+impl FoodDetails {
+    /// Builds the lookups from the rows read by the query module's
+    /// foods_nutrient_data, food_brands and food_food_groups
+    pub fn create(
+        data: &[(Nutrientdata, Nutrient, Derivation)],
+        brands: &[Brand],
+        groups: &[Foodgroup],
+    ) -> Self {
+        let mut nutrient_data: HashMap<i32, Vec<NutrientdataForm>> = HashMap::new();
+        for (nd, n, d) in data {
+            nutrient_data
+                .entry(nd.food_id)
+                .or_default()
+                .push(NutrientdataForm::create((nd, n, d)));
+        }
+        Self {
+            nutrient_data,
+            brands: brands.iter().map(|b| (b.id, b.clone())).collect(),
+            groups: groups
+                .iter()
+                .map(|fg| (fg.id, fg.description.to_string()))
+                .collect(),
+        }
+    }
+    pub fn nutrient_data(&self, food: &Food) -> &[NutrientdataForm] {
+        self.nutrient_data
+            .get(&food.id)
+            .map(|nd| nd.as_slice())
+            .unwrap_or(&[])
+    }
+    pub fn owner(&self, food: &Food) -> String {
+        self.brands
+            .get(&food.brand_id)
+            .map(|b| b.owner.to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+    /// Returns the food's brand name, None if it has none
+    pub fn brand(&self, food: &Food) -> Option<String> {
+        self.brands.get(&food.brand_id).and_then(|b| b.brand.clone())
+    }
+    /// Returns the food's sub-brand, None if it has none
+    pub fn subbrand(&self, food: &Food) -> Option<String> {
+        self.brands.get(&food.brand_id).and_then(|b| b.subbrand.clone())
+    }
+    pub fn food_group(&self, food: &Food) -> String {
+        self.groups
+            .get(&food.food_group_id)
+            .map(|g| g.to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+}
@@ -0,0 +1,328 @@
+//! Sorting and joining csv files too big to hold in memory.  A file is sorted
+//! in runs of at most RUN_SIZE records, each written to a temporary file, and
+//! the runs are merged as they're read.  Memory use depends on the run size,
+//! not the size of the file.
+use crate::reject::Rejects;
+use csv::{Reader, ReaderBuilder, StringRecord, WriterBuilder};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{self, AtomicUsize};
+
+/// The records sorted in memory at a time
+pub const RUN_SIZE: usize = 50_000;
+/// Numbers the run files so sorts running at once don't share them
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// The records of a csv file in order of a column.  The run files are removed
+/// when it's dropped.
+pub struct SortedCsv {
+    headers: StringRecord,
+    key: usize,
+    runs: Vec<Reader<File>>,
+    paths: Vec<PathBuf>,
+    /// the next record of each run
+    heads: Vec<Option<StringRecord>>,
+    /// the keys of the runs' next records, least first
+    order: BinaryHeap<Reverse<(String, usize)>>,
+}
+impl SortedCsv {
+    /// Sorts a csv file by the column named key, run_size records at a time.  Records
+    /// with the wrong number of fields are kept for the reader to reject;  those that
+    /// aren't UTF-8 are rejected here, with their line, and left out.
+    pub fn sort(
+        path: &Path,
+        key: &str,
+        run_size: usize,
+        rejects: &mut Rejects,
+    ) -> Result<SortedCsv, Box<dyn Error + Send + Sync>> {
+        let file = path.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+        let mut reader = ReaderBuilder::new().flexible(true).from_path(path)?;
+        let headers = reader.headers()?.clone();
+        let mut sorted = SortedCsv {
+            key: key_index(&headers, key)?,
+            headers,
+            runs: Vec::new(),
+            paths: Vec::new(),
+            heads: Vec::new(),
+            order: BinaryHeap::new(),
+        };
+        let mut run = Vec::with_capacity(run_size);
+        for r in reader.byte_records() {
+            let r = r?;
+            let line = r.position().map(|p| p.line());
+            match StringRecord::from_byte_record(r) {
+                Ok(r) => run.push(r),
+                Err(e) => {
+                    let reason = e.to_string();
+                    let r = StringRecord::from_byte_record_lossy(e.into_byte_record());
+                    rejects.reject(file, line, &r, &reason)?;
+                }
+            }
+            if run.len() >= run_size {
+                sorted.write_run(&mut run)?;
+            }
+        }
+        if !run.is_empty() {
+            sorted.write_run(&mut run)?;
+        }
+        for (i, path) in sorted.paths.iter().enumerate() {
+            let mut run = ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .from_path(path)?;
+            let mut head = StringRecord::new();
+            if run.read_record(&mut head)? {
+                sorted.order.push(Reverse((field(&head, sorted.key).to_string(), i)));
+                sorted.heads.push(Some(head));
+            } else {
+                sorted.heads.push(None);
+            }
+            sorted.runs.push(run);
+        }
+        Ok(sorted)
+    }
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+    /// Sorts a run of records and writes it to a temporary file
+    fn write_run(&mut self, run: &mut Vec<StringRecord>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let key = self.key;
+        run.sort_by(|a, b| field(a, key).cmp(field(b, key)));
+        let path = env::temp_dir().join(format!(
+            "bfpd-run-{}-{}.csv",
+            process::id(),
+            RUNS.fetch_add(1, atomic::Ordering::SeqCst)
+        ));
+        self.paths.push(path.clone());
+        let mut w = WriterBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(&path)?;
+        for r in run.drain(..) {
+            w.write_record(&r)?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+}
+impl Iterator for SortedCsv {
+    type Item = Result<StringRecord, Box<dyn Error + Send + Sync>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, i)) = self.order.pop()?;
+        let next = self.heads[i].take()?;
+        let mut head = StringRecord::new();
+        match self.runs[i].read_record(&mut head) {
+            Ok(true) => {
+                self.order.push(Reverse((field(&head, self.key).to_string(), i)));
+                self.heads[i] = Some(head);
+            }
+            Ok(false) => (),
+            Err(e) => return Some(Err(Box::new(e))),
+        }
+        Some(Ok(next))
+    }
+}
+impl Drop for SortedCsv {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+/// The inner join of two sorted csv files on their key columns.  Each record is
+/// the left's fields followed by the right's but for its copy of the key, so
+/// the joined records can be read by header name.
+pub struct Join {
+    headers: StringRecord,
+    left: Peekable<SortedCsv>,
+    right: Peekable<SortedCsv>,
+    left_key: usize,
+    right_key: usize,
+    /// joined records not yet returned
+    joined: VecDeque<StringRecord>,
+}
+impl Join {
+    pub fn new(left: SortedCsv, right: SortedCsv) -> Self {
+        let headers = left
+            .headers
+            .iter()
+            .chain(without(&right.headers, right.key))
+            .collect();
+        Join {
+            headers,
+            left_key: left.key,
+            right_key: right.key,
+            left: left.peekable(),
+            right: right.peekable(),
+            joined: VecDeque::new(),
+        }
+    }
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+    /// Joins the records either side with the next key they have in common
+    fn join_next(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        while self.joined.is_empty() {
+            let (l, r) = match (
+                peek_key(&mut self.left, self.left_key)?,
+                peek_key(&mut self.right, self.right_key)?,
+            ) {
+                (Some(l), Some(r)) => (l, r),
+                _ => return Ok(()),
+            };
+            match l.cmp(&r) {
+                Ordering::Less => {
+                    self.left.next();
+                }
+                Ordering::Greater => {
+                    self.right.next();
+                }
+                Ordering::Equal => {
+                    let mut matches = Vec::new();
+                    while peek_key(&mut self.right, self.right_key)?.as_ref() == Some(&r) {
+                        if let Some(m) = self.right.next() {
+                            matches.push(m?);
+                        }
+                    }
+                    while peek_key(&mut self.left, self.left_key)?.as_ref() == Some(&l) {
+                        let left = match self.left.next() {
+                            Some(left) => left?,
+                            None => break,
+                        };
+                        for m in &matches {
+                            self.joined.push_back(
+                                left.iter()
+                                    .chain(without(m, self.right_key))
+                                    .collect(),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+impl Iterator for Join {
+    type Item = Result<StringRecord, Box<dyn Error + Send + Sync>>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.join_next() {
+            return Some(Err(e));
+        }
+        self.joined.pop_front().map(Ok)
+    }
+}
+/// Returns the key of a sorted file's next record, None at the end
+fn peek_key(
+    records: &mut Peekable<SortedCsv>,
+    key: usize,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    match records.peek() {
+        Some(Ok(r)) => Ok(Some(field(r, key).to_string())),
+        Some(Err(_)) => Err(records.next().unwrap().unwrap_err()),
+        None => Ok(None),
+    }
+}
+/// Returns a record's field at index, empty if the record's too short to have it
+fn field(record: &StringRecord, index: usize) -> &str {
+    record.get(index).unwrap_or_default()
+}
+/// Returns the fields of a record but the one at index
+fn without(record: &StringRecord, index: usize) -> impl Iterator<Item = &str> {
+    record
+        .iter()
+        .enumerate()
+        .filter(move |(i, _)| *i != index)
+        .map(|(_, field)| field)
+}
+/// Finds the index of the column named key
+fn key_index(headers: &StringRecord, key: &str) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    match headers.iter().position(|column| column == key) {
+        Some(index) => Ok(index),
+        None => Err(Box::new(IndexError(format!(
+            "Column '{}' does not exist.",
+            key
+        )))),
+    }
+}
+/// Returned when the key column isn't in a file
+#[derive(Debug)]
+pub struct IndexError(String);
+
+impl fmt::Display for IndexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Index Error: {}", self.0)
+    }
+}
+
+impl Error for IndexError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    fn csv_file(name: &str, lines: &[&str]) -> PathBuf {
+        let path = env::temp_dir().join(format!("bfpd-merge-{}-{}.csv", process::id(), name));
+        let mut f = File::create(&path).unwrap();
+        for l in lines {
+            writeln!(f, "{}", l).unwrap();
+        }
+        path
+    }
+    #[test]
+    fn sort_and_join() {
+        let left = csv_file("left", &["id,name", "3,c", "1,a", "4,d", "2,b", "5,e"]);
+        let right = csv_file("right", &["qty,id", "30,3", "10,1", "31,3", "90,9"]);
+        let mut rejects = Rejects::new(&csv_file("rejects", &[]), None);
+        let sorted = SortedCsv::sort(&left, "id", 2, &mut rejects).unwrap();
+        assert_eq!(sorted.paths.len(), 3);
+        let ids: Vec<String> = sorted.map(|r| r.unwrap()[0].to_string()).collect();
+        assert_eq!(ids, vec!["1", "2", "3", "4", "5"]);
+        let join = Join::new(
+            SortedCsv::sort(&left, "id", 2, &mut rejects).unwrap(),
+            SortedCsv::sort(&right, "id", 2, &mut rejects).unwrap(),
+        );
+        assert_eq!(join.headers(), &StringRecord::from(vec!["id", "name", "qty"]));
+        let rows: Vec<Vec<String>> = join
+            .map(|r| r.unwrap().iter().map(|f| f.to_string()).collect())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                vec!["1", "a", "10"],
+                vec!["3", "c", "30"],
+                vec!["3", "c", "31"]
+            ]
+        );
+        assert!(SortedCsv::sort(&right, "fdc_id", 2, &mut rejects).is_err());
+        assert!(rejects.summary().files.is_empty());
+        let _ = fs::remove_file(left);
+        let _ = fs::remove_file(right);
+    }
+    #[test]
+    fn reject_unreadable_records() {
+        let path = csv_file("latin1", &[]);
+        fs::write(&path, b"id,name\n2,b\n1,caf\xe9\n3,c\n").unwrap();
+        let rejected = csv_file("unreadable", &[]);
+        let mut rejects = Rejects::new(&rejected, None);
+        let ids: Vec<String> = SortedCsv::sort(&path, "id", 2, &mut rejects)
+            .unwrap()
+            .map(|r| r.unwrap()[0].to_string())
+            .collect();
+        assert_eq!(ids, vec!["2", "3"]);
+        let counts = &rejects.summary().files[0];
+        let file = format!("bfpd-merge-{}-latin1.csv", process::id());
+        assert_eq!((&counts.file, counts.rejected), (&file, 1));
+        let written = fs::read_to_string(&rejected).unwrap();
+        assert!(written.lines().nth(1).unwrap().starts_with(&format!("{},3,", counts.file)));
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(rejected);
+    }
+}
@@ -0,0 +1,687 @@
+use crate::schema::{
+    brands, derivations, food_groups, foods, ingest_checkpoints, nutrient_data, nutrients,
+};
+use crate::fuzzy::{self, NoMatchError};
+use crate::Store;
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::HashMap;
+use std::error::Error;
+/// How many "did you mean" names a failed lookup returns
+const SUGGESTIONS: i64 = 5;
+#[derive(
+    Identifiable, Queryable, Associations, PartialEq, Clone, Serialize, Deserialize, Debug,
+)]
+#[belongs_to(Brand)]
+#[table_name = "foods"]
+pub struct Food {
+    pub id: i32,
+    pub publication_date: NaiveDateTime,
+    pub modified_date: NaiveDateTime,
+    pub available_date: NaiveDateTime,
+    pub upc: String,
+    pub fdc_id: String,
+    pub description: String,
+    pub food_group_id: i32,
+    pub brand_id: i32,
+    pub datasource: String,
+    pub serving_size: Option<f64>,
+    pub serving_unit: Option<String>,
+    pub serving_description: Option<String>,
+    pub country: Option<String>,
+    pub ingredients: Option<String>,
+    /// the date the product was withdrawn from the market
+    pub discontinued_date: Option<NaiveDateTime>,
+    /// the weight of the package as labelled, e.g. "12 oz/340 g"
+    pub package_weight: Option<String>,
+    /// the state the food is in as served, e.g. UNPREPARED
+    pub preparation_state_code: Option<String>,
+    /// the channels the product is sold through, e.g. GROCERY
+    pub trade_channel: Option<String>,
+    pub short_description: Option<String>,
+    /// the FoodData Central release the food was last loaded from
+    pub fdc_release: Option<String>,
+}
+/// NewFood is a Food without the database id, i.e. what gets inserted or, when
+/// a release is updated, what a food is updated with
+#[derive(Insertable, AsChangeset, Debug)]
+#[table_name = "foods"]
+#[changeset_options(treat_none_as_null = "true")]
+pub struct NewFood<'a> {
+    pub publication_date: NaiveDateTime,
+    pub modified_date: NaiveDateTime,
+    pub available_date: NaiveDateTime,
+    pub upc: &'a str,
+    pub fdc_id: &'a str,
+    pub description: &'a str,
+    pub food_group_id: i32,
+    pub brand_id: i32,
+    pub datasource: &'a str,
+    pub serving_size: Option<f64>,
+    pub serving_unit: Option<&'a str>,
+    pub serving_description: Option<&'a str>,
+    pub country: Option<&'a str>,
+    pub ingredients: Option<&'a str>,
+    pub discontinued_date: Option<NaiveDateTime>,
+    pub package_weight: Option<&'a str>,
+    pub preparation_state_code: Option<&'a str>,
+    pub trade_channel: Option<&'a str>,
+    pub short_description: Option<&'a str>,
+    pub fdc_release: Option<&'a str>,
+}
+
+/// The date a food's dates are until they're known
+fn epoch() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(1970, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+}
+impl Default for Food {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Food {
+    pub fn new() -> Self {
+        Self {
+            id: 0,
+            publication_date: epoch(),
+            modified_date: epoch(),
+            available_date: epoch(),
+            upc: String::from("unknown"),
+            fdc_id: String::from("unknown"),
+            description: String::from("unknown"),
+            food_group_id: 0,
+            brand_id: 0,
+            datasource: String::from("unknown"),
+            serving_size: None,
+            serving_unit: None,
+            serving_description: None,
+            country: None,
+            ingredients: None,
+            discontinued_date: None,
+            package_weight: None,
+            preparation_state_code: None,
+            trade_channel: None,
+            short_description: None,
+            fdc_release: None,
+        }
+    }
+    /// returns the columns to insert for a food
+    pub fn insertable(&self) -> NewFood<'_> {
+        NewFood {
+            publication_date: self.publication_date,
+            modified_date: self.modified_date,
+            available_date: self.available_date,
+            upc: &self.upc,
+            fdc_id: &self.fdc_id,
+            description: &self.description,
+            food_group_id: self.food_group_id,
+            brand_id: self.brand_id,
+            datasource: &self.datasource,
+            serving_size: self.serving_size,
+            serving_unit: self.serving_unit.as_deref(),
+            serving_description: self.serving_description.as_deref(),
+            country: self.country.as_deref(),
+            ingredients: self.ingredients.as_deref(),
+            discontinued_date: self.discontinued_date,
+            package_weight: self.package_weight.as_deref(),
+            preparation_state_code: self.preparation_state_code.as_deref(),
+            trade_channel: self.trade_channel.as_deref(),
+            short_description: self.short_description.as_deref(),
+            fdc_release: self.fdc_release.as_deref(),
+        }
+    }
+    pub fn get_food_group_name(&self, db: &dyn Store) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let mut fg = Foodgroup::new();
+        fg.id = self.food_group_id;
+        Ok(fg.find(db)?.description)
+    }
+    pub fn get_owner_name(&self, db: &dyn Store) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let mut b = Brand::new();
+        b.id = self.brand_id;
+        Ok(b.find(db)?.owner)
+    }
+    pub fn get_nutrient_data(
+        &self,
+        nids: &[String],
+        db: &dyn Store,
+    ) -> Result<Vec<NutrientdataForm>, Box<dyn Error + Send + Sync>> {
+        db.nutrient_data(self, nids)
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub fn get_report(
+        &self,
+        max: i64,
+        off: i64,
+        sort: String,
+        ord: String,
+        min: f64,
+        mx: f64,
+        nid: i32,
+        db: &dyn Store,
+    ) -> Result<Vec<ReportForm>, Box<dyn Error + Send + Sync>> {
+        db.report(max, off, sort, ord, min, mx, nid)
+    }
+}
+#[derive(Identifiable, Queryable, Insertable, PartialEq, Clone, Serialize, Deserialize, Debug)]
+#[table_name = "brands"]
+/// Brand is an owner's brand name and sub-brand.  An owner has a row for each
+/// of its brands;  a food links to the one it's sold under.
+pub struct Brand {
+    pub id: i32,
+    pub owner: String,
+    pub brand: Option<String>,
+    pub subbrand: Option<String>,
+}
+impl Default for Brand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Brand {
+    pub fn new() -> Self {
+        Self {
+            id: 0,
+            owner: String::from("Unknown"),
+            brand: None,
+            subbrand: None,
+        }
+    }
+    pub fn find_by_owner(&self, db: &dyn Store) -> Result<Brand, Box<dyn Error + Send + Sync>> {
+        let mut b = Brand::new();
+        b.owner = self.owner.to_string();
+        b.find(db)
+    }
+    /// Finds the brand with the same owner, brand name and sub-brand
+    pub fn find_by_brand(&self, db: &dyn Store) -> Result<Brand, Box<dyn Error + Send + Sync>> {
+        let mut b = Brand::new();
+        b.owner = self.owner.to_string();
+        match db
+            .get(&b)?
+            .into_iter()
+            .find(|b| b.brand == self.brand && b.subbrand == self.subbrand)
+        {
+            Some(b) => Ok(b),
+            None => Err(Box::new(NotFoundError(format!(
+                "brand {} {} {}",
+                self.owner,
+                self.brand.as_deref().unwrap_or_default(),
+                self.subbrand.as_deref().unwrap_or_default()
+            )))),
+        }
+    }
+    /// Finds the brand by owner or, failing that, the brand whose owner is most
+    /// like it, if it's at least fuzzy::MATCH_THRESHOLD similar.  Otherwise the
+    /// error is a NoMatchError listing the closest owners.
+    pub fn find_similar_owner(&self, db: &dyn Store) -> Result<Brand, Box<dyn Error + Send + Sync>> {
+        if let Ok(b) = self.find_by_owner(db) {
+            return Ok(b);
+        }
+        let mut similar = db.similar_owners(&self.owner, SUGGESTIONS)?;
+        match similar.first() {
+            Some((_, s)) if *s >= fuzzy::MATCH_THRESHOLD => Ok(similar.swap_remove(0).0),
+            _ => Err(Box::new(NoMatchError::new(
+                &format!("brand {}", self.owner),
+                similar.into_iter().map(|(b, _)| b.owner).collect(),
+            ))),
+        }
+    }
+    fn find(&self, db: &dyn Store) -> Result<Brand, Box<dyn Error + Send + Sync>> {
+        match db.get(self)?.into_iter().next() {
+            Some(b) => Ok(b),
+            None => Err(Box::new(NotFoundError(format!("brand {}", self.owner)))),
+        }
+    }
+}
+
+#[derive(Identifiable, Queryable, Insertable, PartialEq, Clone, Serialize, Deserialize, Debug)]
+#[table_name = "food_groups"]
+pub struct Foodgroup {
+    pub id: i32,
+    pub description: String,
+}
+impl Default for Foodgroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Foodgroup {
+    pub fn new() -> Self {
+        Self {
+            id: 0,
+            description: String::from("Unknown"),
+        }
+    }
+    pub fn find_by_description(
+        &self,
+        db: &dyn Store,
+    ) -> Result<Foodgroup, Box<dyn Error + Send + Sync>> {
+        let mut fg = Foodgroup::new();
+        fg.description = self.description.to_string();
+        fg.find(db)
+    }
+    /// Finds the food group by description or, failing that, the one most like
+    /// it as with Brand::find_similar_owner
+    pub fn find_similar_description(
+        &self,
+        db: &dyn Store,
+    ) -> Result<Foodgroup, Box<dyn Error + Send + Sync>> {
+        if let Ok(fg) = self.find_by_description(db) {
+            return Ok(fg);
+        }
+        let mut similar = db.similar_food_groups(&self.description, SUGGESTIONS)?;
+        match similar.first() {
+            Some((_, s)) if *s >= fuzzy::MATCH_THRESHOLD => Ok(similar.swap_remove(0).0),
+            _ => Err(Box::new(NoMatchError::new(
+                &format!("food group {}", self.description),
+                similar.into_iter().map(|(fg, _)| fg.description).collect(),
+            ))),
+        }
+    }
+    fn find(&self, db: &dyn Store) -> Result<Foodgroup, Box<dyn Error + Send + Sync>> {
+        match db.get(self)?.into_iter().next() {
+            Some(fg) => Ok(fg),
+            None => Err(Box::new(NotFoundError(format!(
+                "food group {}",
+                self.description
+            )))),
+        }
+    }
+}
+
+#[derive(
+    Identifiable, Queryable, PartialEq, Insertable, Serialize, Deserialize, Debug,
+)]
+#[table_name = "nutrients"]
+// Nutrient as in Calcium, Energy, etc, etc.
+pub struct Nutrient {
+    pub id: i32,
+    pub nutrientno: String,
+    pub description: String,
+    pub unit: String,
+}
+impl Default for Nutrient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Nutrient {
+    pub fn new() -> Self {
+        Self {
+            id: 0,
+            nutrientno: String::from("Unknown"),
+            description: String::from("Unknown"),
+            unit: String::from("Unknown"),
+        }
+    }
+    pub fn find_by_no(&self, db: &dyn Store) -> Result<Nutrient, Box<dyn Error + Send + Sync>> {
+        let mut n = Nutrient::new();
+        n.nutrientno = self.nutrientno.to_string();
+        match db.get(&n)?.into_iter().next() {
+            Some(n) => Ok(n),
+            None => Err(Box::new(NotFoundError(format!(
+                "nutrient {}",
+                self.nutrientno
+            )))),
+        }
+    }
+}
+#[derive(Identifiable, Queryable, Associations, PartialEq, Serialize, Deserialize, Debug)]
+#[belongs_to(Food)]
+#[belongs_to(Nutrient)]
+#[table_name = "nutrient_data"]
+/// Nutrientdata links nutrients and foods, i.e. it describes the list of nutrient values for a given food
+pub struct Nutrientdata {
+    pub id: i32,
+    pub value: f64,
+    pub portion_value: f64,
+    pub standard_error: Option<f64>,
+    pub minimum: Option<f64>,
+    pub maximum: Option<f64>,
+    pub median: Option<f64>,
+    /// the number of analyses the value is from
+    pub data_points: Option<i32>,
+    pub footnote: Option<String>,
+    /// the earliest year the value's data were collected
+    pub min_year: Option<i32>,
+    pub derivation_id: i32,
+    pub nutrient_id: i32,
+    pub food_id: i32,
+    /// the FoodData Central release the value was loaded from
+    pub fdc_release: Option<String>,
+}
+/// NewNutrientdata is a Nutrientdata without the database id
+#[derive(Insertable, Debug)]
+#[table_name = "nutrient_data"]
+pub struct NewNutrientdata {
+    pub value: f64,
+    pub portion_value: f64,
+    pub standard_error: Option<f64>,
+    pub minimum: Option<f64>,
+    pub maximum: Option<f64>,
+    pub median: Option<f64>,
+    pub data_points: Option<i32>,
+    pub footnote: Option<String>,
+    pub min_year: Option<i32>,
+    pub derivation_id: i32,
+    pub nutrient_id: i32,
+    pub food_id: i32,
+    pub fdc_release: Option<String>,
+}
+impl Default for Nutrientdata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Nutrientdata {
+    pub fn new() -> Self {
+        Self {
+            id: 0,
+            value: 0.0,
+            portion_value: 0.0,
+            standard_error: None,
+            minimum: None,
+            maximum: None,
+            median: None,
+            data_points: None,
+            footnote: None,
+            min_year: None,
+            derivation_id: 0,
+            nutrient_id: 0,
+            food_id: 0,
+            fdc_release: None,
+        }
+    }
+    /// returns the columns to insert for a nutrient value
+    pub fn insertable(&self) -> NewNutrientdata {
+        NewNutrientdata {
+            value: self.value,
+            portion_value: self.portion_value,
+            standard_error: self.standard_error,
+            minimum: self.minimum,
+            maximum: self.maximum,
+            median: self.median,
+            data_points: self.data_points,
+            footnote: self.footnote.clone(),
+            min_year: self.min_year,
+            derivation_id: self.derivation_id,
+            nutrient_id: self.nutrient_id,
+            food_id: self.food_id,
+            fdc_release: self.fdc_release.clone(),
+        }
+    }
+}
+// Derivations are descriptions of how a nutrient value was derived.
+#[derive(
+    Identifiable, Queryable, PartialEq, Insertable, Serialize, Deserialize, Debug,
+)]
+#[table_name = "derivations"]
+pub struct Derivation {
+    pub id: i32,
+    pub code: String,
+    pub description: String,
+}
+impl Default for Derivation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Derivation {
+    pub fn new() -> Self {
+        Self {
+            id: 0,
+            code: String::from("Unknown"),
+            description: String::from("Unknown"),
+        }
+    }
+}
+/// Checkpoint is how far the load of a csv file has got:  the rows read and
+/// the fdc_id of the last one.  It's saved in the transaction that commits
+/// the rows, so a load can be resumed from it.
+#[derive(Queryable, Insertable, AsChangeset, PartialEq, Clone, Debug)]
+#[table_name = "ingest_checkpoints"]
+pub struct Checkpoint {
+    pub phase: String,
+    pub file: String,
+    pub last_row: i64,
+    pub last_fdc_id: String,
+}
+impl Checkpoint {
+    pub fn new(phase: &str, file: &str) -> Self {
+        Self {
+            phase: phase.to_string(),
+            file: file.to_string(),
+            last_row: 0,
+            last_fdc_id: String::new(),
+        }
+    }
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NutrientdataForm {
+    pub value: f64,
+    pub minimum: Option<f64>,
+    pub maximum: Option<f64>,
+    pub median: Option<f64>,
+    pub data_points: Option<i32>,
+    pub footnote: Option<String>,
+    pub min_year: Option<i32>,
+    pub derivation: String,
+    pub derivation_code: String,
+    pub nutrient: String,
+    pub nutrient_no: String,
+    pub unit: String,
+}
+impl Default for NutrientdataForm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NutrientdataForm {
+    pub fn new() -> Self {
+        Self {
+            value: 0.0,
+            minimum: None,
+            maximum: None,
+            median: None,
+            data_points: None,
+            footnote: None,
+            min_year: None,
+            derivation: String::from("unknown"),
+            derivation_code: String::from("unknown"),
+            nutrient: String::from("unknown"),
+            nutrient_no: String::from("unknown"),
+            unit: String::from("unknown"),
+        }
+    }
+    pub fn create((nd, n, d): (&Nutrientdata, &Nutrient, &Derivation)) -> Self {
+        Self {
+            value: nd.value,
+            minimum: nd.minimum,
+            maximum: nd.maximum,
+            median: nd.median,
+            data_points: nd.data_points,
+            footnote: nd.footnote.clone(),
+            min_year: nd.min_year,
+            nutrient: (*(n.description)).to_string(),
+            nutrient_no: (*(n.nutrientno)).to_string(),
+            unit: (*(n.unit)).to_string(),
+            derivation: (*(d.description)).to_string(),
+            derivation_code: (*(d.code)).to_string(),
+        }
+    }
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReportForm {
+    pub fdc_id: String,
+    pub description: String,
+    pub upc: String,
+    pub serving_size: Option<f64>,
+    pub serving_unit: Option<String>,
+    pub serving_description: Option<String>,
+    pub unit_value: f64,
+    pub portion_value: f64,
+}
+/// The columns the report query selects for a line:  fdc_id, upc, description,
+/// serving size, description and unit, and the unit and portion values
+pub type ReportRow = (
+    String,
+    String,
+    String,
+    Option<f64>,
+    Option<String>,
+    Option<String>,
+    f64,
+    f64,
+);
+impl ReportForm {
+    /// creates a report line from the columns selected by the report query
+    pub fn create((f, u, d, ss, sd, su, v, pv): &ReportRow) -> Self {
+        Self {
+            unit_value: *v,
+            portion_value: *pv,
+            fdc_id: f.to_string(),
+            description: d.to_string(),
+            upc: u.to_string(),
+            serving_size: *ss,
+            serving_description: Some(
+                sd.as_ref()
+                    .map(|n| n.to_string())
+                    .unwrap_or("unknown".to_string()),
+            ),
+            serving_unit: Some(
+                su.as_ref()
+                    .map(|n| n.to_string())
+                    .unwrap_or("unknown".to_string()),
+            ),
+        }
+    }
+}
+/// A food and its values of the nutrients a report constrains
+#[derive(Serialize, Deserialize, Debug)]
+pub struct NutrientsReportForm {
+    pub fdc_id: String,
+    pub description: String,
+    pub upc: String,
+    pub serving_size: Option<f64>,
+    pub serving_unit: Option<String>,
+    pub serving_description: Option<String>,
+    pub nutrients: Vec<ReportValueForm>,
+}
+/// A nutrient's value per 100 grams and per serving
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReportValueForm {
+    pub nutrient_no: String,
+    pub nutrient: String,
+    pub unit: String,
+    pub unit_value: f64,
+    pub portion_value: f64,
+}
+impl NutrientsReportForm {
+    /// creates the report lines for foods from their nutrient data, which is
+    /// read by the query module's foods_nutrient_data
+    pub fn create_all(
+        foods: &[Food],
+        data: &[(Nutrientdata, Nutrient, Derivation)],
+    ) -> Vec<NutrientsReportForm> {
+        let mut values: HashMap<i32, Vec<ReportValueForm>> = HashMap::new();
+        for (nd, n, _d) in data {
+            values.entry(nd.food_id).or_default().push(ReportValueForm {
+                nutrient_no: n.nutrientno.to_string(),
+                nutrient: n.description.to_string(),
+                unit: n.unit.to_string(),
+                unit_value: nd.value,
+                portion_value: nd.portion_value,
+            });
+        }
+        foods
+            .iter()
+            .map(|f| NutrientsReportForm {
+                fdc_id: f.fdc_id.to_string(),
+                description: f.description.to_string(),
+                upc: f.upc.to_string(),
+                serving_size: f.serving_size,
+                serving_unit: f.serving_unit.clone(),
+                serving_description: f.serving_description.clone(),
+                nutrients: values.remove(&f.id).unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+/// Returned when a look-up by name or id doesn't find a row
+#[derive(Debug)]
+pub struct NotFoundError(pub String);
+
+impl std::fmt::Display for NotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Not found: {}", self.0)
+    }
+}
+
+impl Error for NotFoundError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn new_nutrientdata_form() {
+        let nf = NutrientdataForm::new();
+        assert_eq!(0.0, nf.value);
+        assert_eq!("unknown", nf.derivation);
+        assert_eq!("unknown", nf.derivation_code);
+        assert_eq!("unknown", nf.nutrient_no);
+        assert_eq!("unknown", nf.nutrient);
+        assert_eq!("unknown", nf.unit);
+    }
+    #[test]
+    fn create_nutrientdata_form() {
+        let n = Nutrient {
+            id: 0,
+            description: String::from("A nutrient"),
+            nutrientno: String::from("999"),
+            unit: String::from("g"),
+        };
+        let d = Derivation {
+            id: 0,
+            description: String::from("Some derivation"),
+            code: String::from("LXXX"),
+        };
+        let nd = Nutrientdata::new();
+        let nf = NutrientdataForm::create((&nd, &n, &d));
+        assert_eq!(0.0, nf.value);
+        assert_eq!("Some derivation", nf.derivation);
+        assert_eq!("LXXX", nf.derivation_code);
+        assert_eq!("999", nf.nutrient_no);
+        assert_eq!("A nutrient", nf.nutrient);
+        assert_eq!("g", nf.unit);
+    }
+    #[test]
+    fn new_food() {
+        let f = Food::new();
+        assert_eq!(0, f.id);
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0);
+        assert_eq!(epoch, Some(f.publication_date));
+        assert_eq!(epoch, Some(f.modified_date));
+        assert_eq!(epoch, Some(f.available_date));
+        assert_eq!("unknown", f.upc);
+        assert_eq!("unknown", f.fdc_id);
+        assert_eq!("unknown", f.description);
+        assert_eq!(0, f.food_group_id);
+        assert_eq!(0, f.brand_id);
+        assert_eq!("unknown", f.datasource);
+        assert_eq!(None, f.serving_size);
+        assert_eq!(None, f.serving_unit);
+        assert_eq!(None, f.serving_description);
+        assert_eq!(None, f.country);
+        assert_eq!(None, f.ingredients);
+    }
+}
@@ -0,0 +1,974 @@
+//! Query builders shared by the backends.  Each function returns a boxed
+//! diesel query for any backend;  the backend adds its own SQL (e.g. full-text
+//! search) where needed and runs it on its connection.
+use crate::cursor::{descending, Cursor, Key};
+use crate::filter::{Basis, FoodFilter, NutrientRange, NutrientSort};
+use crate::fuzzy;
+use crate::models::{Brand, Food, Foodgroup, Nutrient, Nutrientdata};
+use crate::schema::{
+    brands, derivations, food_groups, foods, nutrient_data, nutrients, search_hits,
+};
+use crate::search::{SearchQuery, Term};
+use crate::suggest::{self, ESCAPE};
+use chrono::NaiveDateTime;
+use diesel::backend::Backend;
+use diesel::dsl::{not, sql, InnerJoin, IntoBoxed, Select};
+use diesel::expression::{BoxableExpression, SqlLiteral};
+use diesel::query_builder::BoxedSelectStatement;
+use diesel::prelude::*;
+use diesel::serialize::ToSql;
+use diesel::sql_types::{BigInt, Bool, Double, Integer, Nullable, Text, Timestamp};
+
+/// A backend's full-text search predicate on the foods table
+pub type Search<'a, DB> = Box<dyn BoxableExpression<foods::table, DB, SqlType = Bool> + 'a>;
+/// A backend's score of how well a food matches the search terms
+pub type Rank<'a, DB> = Box<dyn BoxableExpression<foods::table, DB, SqlType = Double> + 'a>;
+/// A sort key which some foods have no value of, e.g. a nutrient they lack
+pub type SortKey<'a, DB> =
+    Box<dyn BoxableExpression<foods::table, DB, SqlType = Nullable<Double>> + 'a>;
+
+/// The diesel backends the query builders run on.  Backend brings the column
+/// types and diesel binds integers, doubles and text alike on any backend, but
+/// each binds timestamps its own way, so the queries on a timestamp are built
+/// here where the backend's ToSql is known.
+pub trait FoodQueryBackend: Backend + 'static {
+    /// Returns the predicate matching the foods published between two dates
+    fn published_between(from: NaiveDateTime, to: NaiveDateTime) -> Search<'static, Self>;
+}
+impl<DB> FoodQueryBackend for DB
+where
+    DB: Backend + 'static,
+    NaiveDateTime: ToSql<Timestamp, DB>,
+{
+    fn published_between(from: NaiveDateTime, to: NaiveDateTime) -> Search<'static, DB> {
+        Box::new(foods::publication_date.between(from, to))
+    }
+}
+/// The full-text search each backend supplies for foods
+pub trait FullText<DB> {
+    /// Returns the predicate matching a filter's search terms, if it has any
+    fn search(&self, filter: &FoodFilter) -> Option<Search<'static, DB>>;
+    /// Returns the relevance of a food to a filter's search terms, higher is
+    /// more relevant
+    fn rank(&self, filter: &FoodFilter) -> Option<Rank<'static, DB>>;
+}
+/// The most search index hits inserted in one statement
+pub const HITS_CHUNK: usize = 1_000;
+/// Returns the statement inserting search index hits, ids and scores, in a table.
+/// The values are written as literals, which no backend limits the number of as
+/// it does bind parameters.
+pub fn insert_hits(table: &str, hits: &[(i32, f64)]) -> String {
+    let rows: Vec<String> = hits
+        .iter()
+        .map(|(id, score)| format!("({}, {:e})", id, score))
+        .collect();
+    format!(
+        "INSERT INTO {} (id, score) VALUES {}",
+        table,
+        rows.join(", ")
+    )
+}
+/// Returns the score a search index gave a food, read from search_hits, 0 if
+/// it wasn't a hit
+fn hits_score<DB>() -> Rank<'static, DB>
+where
+    DB: FoodQueryBackend,
+{
+    Box::new(sql::<Double>(
+        "COALESCE((SELECT search_hits.score FROM search_hits WHERE search_hits.id = foods.id), 0)",
+    ))
+}
+/// Returns the relevance of foods to a filter:  the scores of its search index
+/// hits if it has them, otherwise the backend's rank of its search terms
+pub fn rank<DB>(filter: &FoodFilter, text: &dyn FullText<DB>) -> Option<Rank<'static, DB>>
+where
+    DB: FoodQueryBackend,
+{
+    match &filter.hits {
+        Some(_) => Some(hits_score()),
+        None => text.rank(filter),
+    }
+}
+
+sql_function!(fn lower(x: Text) -> Text);
+sql_function!(fn length(x: Text) -> Integer);
+
+type NutrientdataJoin =
+    InnerJoin<InnerJoin<nutrient_data::table, nutrients::table>, derivations::table>;
+
+type ReportColumns = (
+    foods::fdc_id,
+    foods::upc,
+    foods::description,
+    foods::serving_size,
+    foods::serving_description,
+    foods::serving_unit,
+    nutrient_data::value,
+    nutrient_data::portion_value,
+);
+const REPORT_COLUMNS: ReportColumns = (
+    foods::fdc_id,
+    foods::upc,
+    foods::description,
+    foods::serving_size,
+    foods::serving_description,
+    foods::serving_unit,
+    nutrient_data::value,
+    nutrient_data::portion_value,
+);
+type Report = Select<InnerJoin<foods::table, nutrient_data::table>, ReportColumns>;
+
+/// Builds a backend's predicate for a search, ANDing its groups and ORing the
+/// terms within a group.  term returns the predicate matching a single term;
+/// excluded terms are negated here.
+pub fn search_predicate<DB, F>(q: &SearchQuery, term: F) -> Option<Search<'static, DB>>
+where
+    DB: FoodQueryBackend,
+    F: Fn(&Term) -> Search<'static, DB>,
+{
+    let groups = q.groups.iter().filter_map(|g| {
+        g.iter()
+            .map(|t| match t.exclude {
+                true => Box::new(not(term(t))) as Search<'static, DB>,
+                false => term(t),
+            })
+            .fold(None, |a: Option<Search<'static, DB>>, b| match a {
+                Some(a) => Some(Box::new(a.or(b))),
+                None => Some(b),
+            })
+    });
+    groups.fold(None, |a, b| match a {
+        Some(a) => Some(Box::new(a.and(b))),
+        None => Some(b),
+    })
+}
+/// Matches foods whose description contains most of the trigrams of a term's
+/// words.  It's the typo tolerant fallback for backends without a trigram
+/// index, so only the candidates, the foods the backend's own index finds
+/// with something in common with the term, are compared.
+pub fn ngram_description<DB: FoodQueryBackend>(
+    t: &Term,
+    candidates: Search<'static, DB>,
+) -> Search<'static, DB> {
+    let text = t.words.join(" ");
+    Box::new(candidates.and(sql::<Bool>(&ngram_match(
+        "foods.description",
+        &text,
+        fuzzy::MATCH_THRESHOLD,
+    ))))
+}
+/// Returns SQL that's true for a column containing at least a share of the
+/// inner trigrams of a text's words
+fn ngram_match(column: &str, text: &str, share: f64) -> String {
+    let patterns = fuzzy::text_patterns(text);
+    if patterns.is_empty() {
+        return "1 = 0".to_string();
+    }
+    let hits = patterns
+        .iter()
+        .map(|p| format!("CASE WHEN {} LIKE '{}' THEN 1 ELSE 0 END", column, p))
+        .collect::<Vec<String>>()
+        .join(" + ");
+    let need = (patterns.len() as f64 * share).ceil() as usize;
+    format!("({}) >= {}", hits, need.max(1))
+}
+/// Orders a boxed query on a sort column and then id.  With a cursor, only the
+/// rows after its position are kept and a before cursor reads backwards, so
+/// the caller reverses the rows.  $key converts the cursor key to the column's
+/// type.
+macro_rules! keyset {
+    ($q:expr, $col:expr, $id:expr, $key:expr, $order:expr, $cursor:expr) => {{
+        let backwards = $cursor.map(|c| c.before).unwrap_or(false);
+        let q = if descending($order) != backwards {
+            $q.order(($col.desc(), $id.desc()))
+        } else {
+            $q.order(($col.asc(), $id.asc()))
+        };
+        match $cursor {
+            Some(c) => {
+                let k = c.key.as_ref().map($key).unwrap_or_default();
+                if c.ascending() {
+                    q.filter($col.gt(k.clone()).or($col.eq(k).and($id.gt(c.id))))
+                } else {
+                    q.filter($col.lt(k.clone()).or($col.eq(k).and($id.lt(c.id))))
+                }
+            }
+            None => q,
+        }
+    }};
+    ($q:expr, $id:expr, $order:expr, $cursor:expr) => {{
+        let backwards = $cursor.map(|c| c.before).unwrap_or(false);
+        let q = if descending($order) != backwards {
+            $q.order($id.desc())
+        } else {
+            $q.order($id.asc())
+        };
+        match $cursor {
+            Some(c) if c.ascending() => q.filter($id.gt(c.id)),
+            Some(c) => q.filter($id.lt(c.id)),
+            None => q,
+        }
+    }};
+}
+/// Returns the food identified by upc, id or fdc_id in that order
+pub fn food<'a, DB>(food: &Food) -> foods::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::foods::dsl::*;
+    let mut q = foods.into_boxed();
+    if food.upc != "unknown" {
+        q = q.filter(upc.eq(food.upc.to_string()));
+    } else if food.id > 0 {
+        q = q.filter(id.eq(food.id));
+    } else {
+        q = q.filter(fdc_id.eq(food.fdc_id.to_string()));
+    }
+    q
+}
+/// Applies a FoodFilter to the foods table.  Browse and Count both start from
+/// here so the two always agree.
+pub fn filter_foods<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+) -> foods::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::foods::dsl::*;
+    let mut q = foods.into_boxed();
+    if let Some(s) = search {
+        q = q.filter(s);
+    }
+    if filter.hits.is_some() {
+        q = q.filter(id.eq_any(search_hits::table.select(search_hits::id)));
+    }
+    if let Some(fg) = filter.food_group_id {
+        q = q.filter(food_group_id.eq(fg));
+    }
+    if let Some(b) = filter.brand_id {
+        q = q.filter(brand_id.eq(b));
+    }
+    if filter.owner.is_some() || filter.brand.is_some() || filter.subbrand.is_some() {
+        q = q.filter(brand_id.eq_any(filter_brands(filter)));
+    }
+    if let Some(r) = filter.publication_date {
+        q = q.filter(DB::published_between(r.from, r.to));
+    }
+    if let Some(c) = &filter.country {
+        q = q.filter(country.eq(c.to_string()));
+    }
+    if let Some(d) = &filter.datasource {
+        q = q.filter(datasource.eq(d.to_string()));
+    }
+    if let Some(u) = &filter.serving_unit {
+        q = q.filter(serving_unit.eq(u.to_string()));
+    }
+    for r in &filter.nutrients {
+        let (min, max) = (r.min.unwrap_or(f64::MIN), r.max.unwrap_or(f64::MAX));
+        let with = nutrient_data::table
+            .inner_join(nutrients::table)
+            .select(nutrient_data::food_id)
+            .filter(nutrients::nutrientno.eq(r.nutrientno.to_string()));
+        q = match r.basis {
+            Basis::Unit => q.filter(id.eq_any(with.filter(nutrient_data::value.between(min, max)))),
+            Basis::Portion => q.filter(id.eq_any(
+                with.filter(nutrient_data::portion_value.between(min, max)),
+            )),
+        };
+    }
+    q
+}
+/// Selects the ids of the brands of a filter's owner, brand name and sub-brand
+fn filter_brands<'a, DB>(
+    filter: &FoodFilter,
+) -> BoxedSelectStatement<'a, Integer, brands::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    let mut q = brands::table.select(brands::id).into_boxed();
+    if let Some(o) = &filter.owner {
+        q = q.filter(brands::owner.eq(o.to_string()));
+    }
+    if let Some(b) = &filter.brand {
+        q = q.filter(brands::brand.eq(b.to_string()));
+    }
+    if let Some(s) = &filter.subbrand {
+        q = q.filter(brands::subbrand.eq(s.to_string()));
+    }
+    q
+}
+/// Returns a food's brand owner.  Owners are counted and grouped by name as an
+/// owner has a brand row for each of its brand names and sub-brands.
+fn food_owner() -> SqlLiteral<Nullable<Text>> {
+    sql::<Nullable<Text>>("(SELECT brands.owner FROM brands WHERE brands.id = foods.brand_id)")
+}
+
+/// Returns a food's value of a nutrient on a basis.  It's NULL for a food
+/// without the nutrient so a query ordered by it should filter on the nutrient.
+pub fn nutrient_value<DB>(nutrientno: &str, basis: Basis) -> Rank<'static, DB>
+where
+    DB: FoodQueryBackend,
+{
+    Box::new(
+        sql::<Double>(&nutrient_select(basis))
+            .bind::<Text, _>(nutrientno.to_string())
+            .sql(")"),
+    )
+}
+/// Returns the key of a browse sorted by a nutrient:  a food's value of the
+/// nutrient, NULL if it has none
+pub fn nutrient_key<DB>(by: &NutrientSort) -> SortKey<'static, DB>
+where
+    DB: FoodQueryBackend,
+{
+    Box::new(
+        sql::<Nullable<Double>>(&nutrient_select(by.basis))
+            .bind::<Text, _>(by.nutrientno.to_string())
+            .sql(")"),
+    )
+}
+/// Orders foods on a nutrient key as keyset! does on a column, except the foods
+/// without the nutrient come last in either order
+fn nutrient_keyset<'a, DB, K>(
+    q: foods::BoxedQuery<'a, DB>,
+    key: K,
+    order: &str,
+    cursor: Option<&Cursor>,
+) -> foods::BoxedQuery<'a, DB>
+where
+    K: Fn() -> SortKey<'static, DB>,
+    DB: FoodQueryBackend,
+{
+    use crate::schema::foods::dsl::id;
+    let backwards = cursor.map(|c| c.before).unwrap_or(false);
+    let q = match backwards {
+        true => q.order(key().is_null().desc()),
+        false => q.order(key().is_null().asc()),
+    };
+    let q = match descending(order) != backwards {
+        true => q.then_order_by(key().desc()).then_order_by(id.desc()),
+        false => q.then_order_by(key().asc()).then_order_by(id.asc()),
+    };
+    let c = match cursor {
+        Some(c) => c,
+        None => return q,
+    };
+    let beyond: Search<'a, DB> = match (c.key.as_ref().map(Key::number), c.ascending()) {
+        (Some(k), true) => Box::new(key().gt(k).or(key().eq(k).and(id.gt(c.id)))),
+        (Some(k), false) => Box::new(key().lt(k).or(key().eq(k).and(id.lt(c.id)))),
+        (None, true) => Box::new(key().is_null().and(id.gt(c.id))),
+        (None, false) => Box::new(key().is_null().and(id.lt(c.id))),
+    };
+    match (c.before, c.key.is_some()) {
+        (false, true) => q.filter(beyond.or(key().is_null())),
+        (true, false) => q.filter(beyond.or(key().is_not_null())),
+        _ => q.filter(beyond),
+    }
+}
+/// The correlated subquery reading a food's value of a nutrient, up to the
+/// nutrient number to bind and the closing parenthesis
+fn nutrient_select(basis: Basis) -> String {
+    format!(
+        "(SELECT MAX(nutrient_data.{}) FROM nutrient_data INNER JOIN nutrients ON nutrients.id = nutrient_data.nutrient_id WHERE nutrient_data.food_id = foods.id AND nutrients.nutrientno = ",
+        basis.column()
+    )
+}
+/// Returns the foods filtered as in filter_foods ordered by their value of
+/// the nutrient in the filter's ranges numbered sort, or of the first if none is
+pub fn nutrients_report<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    sort: &str,
+    order: &str,
+    max: i64,
+    off: i64,
+) -> foods::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::foods::dsl::*;
+    let q = filter_foods(filter, search);
+    let by = filter
+        .nutrients
+        .iter()
+        .find(|r| r.nutrientno == sort)
+        .or_else(|| filter.nutrients.first());
+    let q = match by {
+        Some(r) if descending(order) => {
+            q.order((nutrient_value(&r.nutrientno, r.basis).desc(), id.desc()))
+        }
+        Some(r) => q.order((nutrient_value(&r.nutrientno, r.basis).asc(), id.asc())),
+        None => q.order(id.asc()),
+    };
+    q.limit(max).offset(off)
+}
+/// Returns a page of foods filtered as in filter_foods, read from offset or,
+/// if given, the cursor position.  The relevance sort orders on the backend's
+/// rank and falls back to id when there are no search terms.  A nutrient sort,
+/// see NutrientSort, orders on the foods' values of the nutrient.
+pub fn browse_foods<'a, DB>(
+    filter: &FoodFilter,
+    text: &dyn FullText<DB>,
+    max: i64,
+    off: i64,
+    sort: &str,
+    order: &str,
+    cursor: Option<&Cursor>,
+) -> foods::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::foods::dsl::*;
+    let q = filter_foods(filter, text.search(filter));
+    let q = match sort {
+        "relevance" if rank(filter, text).is_some() => {
+            let rank = || rank(filter, text).unwrap();
+            keyset!(q, rank(), id, Key::number, order, cursor)
+        }
+        s if NutrientSort::parse(s).is_some() => {
+            let by = NutrientSort::parse(s).unwrap();
+            nutrient_keyset(q, || nutrient_key(&by), order, cursor)
+        }
+        "description" => keyset!(q, description, id, Key::text, order, cursor),
+        "upc" => keyset!(q, upc, id, Key::text, order, cursor),
+        "fdcId" => keyset!(q, fdc_id, id, Key::text, order, cursor),
+        _ => keyset!(q, id, order, cursor),
+    };
+    q.limit(max).offset(off)
+}
+/// Returns a brand by id or, if no id is set, by owner
+pub fn brand<'a, DB>(b: &Brand) -> brands::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::brands::dsl::*;
+    let q = brands.into_boxed();
+    if b.id > 0 {
+        q.filter(id.eq(b.id))
+    } else {
+        q.filter(owner.eq(b.owner.to_string()))
+    }
+}
+/// Returns the brands of a page of foods
+pub fn food_brands<'a, DB>(fd: &[Food]) -> brands::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    brands_by_id(distinct_ids(fd, |f| f.brand_id))
+}
+/// Returns the brands with the given ids
+pub fn brands_by_id<'a, DB>(ids: Vec<i32>) -> brands::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::brands::dsl::*;
+    brands.into_boxed().filter(id.eq_any(ids))
+}
+/// Returns a brand of each owner with at least fuzzy::SUGGEST_THRESHOLD of an
+/// owner's trigrams, the candidates for the owners like it on a backend
+/// without a trigram index
+pub fn owner_candidates<'a, DB>(owner: &str) -> brands::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    brands::table.into_boxed().filter(sql::<Bool>(&format!(
+        "brands.id IN (SELECT MIN(id) FROM brands WHERE {} GROUP BY owner)",
+        ngram_match("owner", owner, fuzzy::SUGGEST_THRESHOLD)
+    )))
+}
+pub fn browse_brands<'a, DB>(
+    max: i64,
+    off: i64,
+    sort: &str,
+    order: &str,
+    cursor: Option<&Cursor>,
+) -> brands::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::brands::dsl::*;
+    let q = brands.into_boxed();
+    let q = match sort {
+        "owner" => keyset!(q, owner, id, Key::text, order, cursor),
+        _ => keyset!(q, id, order, cursor),
+    };
+    q.limit(max).offset(off)
+}
+/// Returns a food group by id or, if no id is set, by description
+pub fn food_group<'a, DB>(fg: &Foodgroup) -> food_groups::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::food_groups::dsl::*;
+    let q = food_groups.into_boxed();
+    if fg.id > 0 {
+        q.filter(id.eq(fg.id))
+    } else {
+        q.filter(description.eq(fg.description.to_string()))
+    }
+}
+/// Returns the food groups of a page of foods
+pub fn food_food_groups<'a, DB>(fd: &[Food]) -> food_groups::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    food_groups_by_id(distinct_ids(fd, |f| f.food_group_id))
+}
+/// Returns the food groups with the given ids
+pub fn food_groups_by_id<'a, DB>(ids: Vec<i32>) -> food_groups::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::food_groups::dsl::*;
+    food_groups.into_boxed().filter(id.eq_any(ids))
+}
+/// Returns the food groups with at least fuzzy::SUGGEST_THRESHOLD of a food
+/// group's trigrams as owner_candidates does owners
+pub fn food_group_candidates<'a, DB>(group: &str) -> food_groups::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    food_groups::table
+        .into_boxed()
+        .filter(sql::<Bool>(&ngram_match(
+            "food_groups.description",
+            group,
+            fuzzy::SUGGEST_THRESHOLD,
+        )))
+}
+pub fn browse_food_groups<'a, DB>(
+    max: i64,
+    off: i64,
+    sort: &str,
+    order: &str,
+    cursor: Option<&Cursor>,
+) -> food_groups::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::food_groups::dsl::*;
+    let q = food_groups.into_boxed();
+    let q = match sort {
+        "group" => keyset!(q, description, id, Key::text, order, cursor),
+        _ => keyset!(q, id, order, cursor),
+    };
+    q.limit(max).offset(off)
+}
+/// Returns a nutrient by id or, if no id is set, by nutrient number
+pub fn nutrient<'a, DB>(n: &Nutrient) -> nutrients::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::nutrients::dsl::*;
+    let q = nutrients.into_boxed();
+    if n.id > 0 {
+        q.filter(id.eq(n.id))
+    } else {
+        q.filter(nutrientno.eq(n.nutrientno.to_string()))
+    }
+}
+pub fn browse_nutrients<'a, DB>(
+    max: i64,
+    off: i64,
+    sort: &str,
+    order: &str,
+    cursor: Option<&Cursor>,
+) -> nutrients::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::nutrients::dsl::*;
+    let q = nutrients.into_boxed();
+    let q = match sort {
+        "name" => keyset!(q, description, id, Key::text, order, cursor),
+        "nbr" => keyset!(q, nutrientno, id, Key::text, order, cursor),
+        _ => keyset!(q, id, order, cursor),
+    };
+    q.limit(max).offset(off)
+}
+/// Returns nutrient values for a nutrient, optionally limited to a minimum/maximum
+/// range of the 100 gram or portion value
+pub fn browse_nutrient_data<'a, DB>(
+    nd: &Nutrientdata,
+    max: i64,
+    off: i64,
+    sort: &str,
+    order: &str,
+    cursor: Option<&Cursor>,
+) -> nutrient_data::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::nutrient_data::dsl::*;
+    let q = nutrient_data.into_boxed();
+    let mut q = match sort {
+        "portion" => keyset!(q, portion_value, id, Key::number, order, cursor),
+        _ => keyset!(q, value, id, Key::number, order, cursor),
+    };
+    if nd.nutrient_id > 0 {
+        q = q.filter(nutrient_id.eq(nd.nutrient_id));
+    }
+    let min = nd.minimum.unwrap_or(0.0);
+    let mut mx = nd.maximum.unwrap_or(0.0);
+    if min > 0.0 {
+        if mx == 0.0 || mx < min {
+            mx = min;
+        }
+        if sort == "portion" {
+            q = q.filter(portion_value.between(min, mx));
+        } else {
+            q = q.filter(value.between(min, mx));
+        }
+    }
+    q.limit(max).offset(off)
+}
+/// Returns the nutrient values of a food joined to their nutrient and derivation.
+/// The values are limited to the nutrient numbers in nids if any are given.
+pub fn food_nutrient_data<DB>(
+    food: &Food,
+    nids: &[String],
+) -> IntoBoxed<'static, NutrientdataJoin, DB>
+where
+    DB: FoodQueryBackend,
+{
+    foods_nutrient_data(std::slice::from_ref(food), nids)
+}
+/// Returns the nutrient values of a page of foods in one query, ordered by food
+pub fn foods_nutrient_data<DB>(
+    fd: &[Food],
+    nids: &[String],
+) -> IntoBoxed<'static, NutrientdataJoin, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::nutrient_data::dsl::*;
+    use crate::schema::nutrients::dsl::*;
+    let mut q = nutrient_data
+        .inner_join(nutrients)
+        .inner_join(derivations::table)
+        .into_boxed()
+        .filter(food_id.eq_any(distinct_ids(fd, |f| f.id)))
+        .order((food_id, crate::schema::nutrient_data::id));
+    if !nids.is_empty() {
+        q = q.filter(nutrientno.eq_any(nids.to_vec()));
+    }
+    q
+}
+fn distinct_ids(fd: &[Food], id: fn(&Food) -> i32) -> Vec<i32> {
+    let mut ids: Vec<i32> = fd.iter().map(id).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+/// Counts the foods a query matches by a column, largest count first.  The
+/// count is SQL as diesel won't select an aggregate alongside a column.
+macro_rules! facet {
+    ($q:expr, $col:expr, $max:expr) => {
+        $q.select(($col, sql::<BigInt>("COUNT(*)")))
+            .group_by($col)
+            .order((sql::<BigInt>("COUNT(*)").desc(), $col))
+            .limit($max)
+    };
+}
+/// Returns the number of foods filtered as in filter_foods in each food group
+pub fn facet_food_groups<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    max: i64,
+) -> BoxedSelectStatement<'a, (Integer, BigInt), foods::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    facet!(filter_foods(filter, search), foods::food_group_id, max)
+}
+/// Returns the number of foods filtered as in filter_foods of each brand owner
+pub fn facet_owners<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    max: i64,
+) -> BoxedSelectStatement<'a, (Nullable<Text>, BigInt), foods::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    facet!(filter_foods(filter, search), food_owner(), max)
+}
+/// Returns the number of foods filtered as in filter_foods from each country
+pub fn facet_countries<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    max: i64,
+) -> BoxedSelectStatement<'a, (Nullable<Text>, BigInt), foods::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    facet!(filter_foods(filter, search), foods::country, max)
+}
+/// Returns the number of foods filtered as in filter_foods from each datasource
+pub fn facet_datasources<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    max: i64,
+) -> BoxedSelectStatement<'a, (Text, BigInt), foods::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    facet!(filter_foods(filter, search), foods::datasource, max)
+}
+/// Returns foods with a nutrient's 100 gram value (or portion value if sort is
+/// "portion") between min and mx
+pub fn report<DB>(
+    max: i64,
+    off: i64,
+    sort: &str,
+    ord: &str,
+    min: f64,
+    mx: f64,
+    nid: i32,
+) -> IntoBoxed<'static, Report, DB>
+where
+    DB: FoodQueryBackend,
+{
+    use crate::schema::foods::dsl::*;
+    use crate::schema::nutrient_data::dsl::*;
+    let q = foods
+        .inner_join(nutrient_data)
+        .select(REPORT_COLUMNS)
+        .into_boxed()
+        .filter(nutrient_id.eq(nid));
+    let q = match (sort, descending(ord)) {
+        ("portion", false) => q
+            .filter(portion_value.between(min, mx))
+            .order(portion_value.asc()),
+        ("portion", true) => q
+            .filter(portion_value.between(min, mx))
+            .order(portion_value.desc()),
+        (_, false) => q.filter(value.between(min, mx)).order(value.asc()),
+        (_, true) => q.filter(value.between(min, mx)).order(value.desc()),
+    };
+    q.limit(max).offset(off)
+}
+/// Selects the distinct values of a column which start with a prefix, shortest
+/// first.  Backends whose collation ignores case compare the column as is so its
+/// index can be used, the others compare it lower cased.
+macro_rules! completions {
+    ($table:expr, $col:expr, $prefix:expr, $max:expr, $lower:expr) => {{
+        let pattern = suggest::prefix_pattern($prefix);
+        let q = $table.select((length($col), $col)).distinct().into_boxed();
+        let q = if $lower {
+            q.filter(lower($col).like(pattern).escape(ESCAPE))
+        } else {
+            q.filter($col.like(pattern).escape(ESCAPE))
+        };
+        q.order((length($col), $col)).limit($max)
+    }};
+}
+/// Returns the food descriptions starting with prefix along with their lengths
+pub fn suggest_foods<'a, DB>(
+    prefix: &str,
+    max: i64,
+    lower: bool,
+) -> BoxedSelectStatement<'a, (Integer, Text), foods::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    completions!(foods::table, foods::description, prefix, max, lower)
+}
+/// Returns the brand owners starting with prefix along with their lengths
+pub fn suggest_owners<'a, DB>(
+    prefix: &str,
+    max: i64,
+    lower: bool,
+) -> BoxedSelectStatement<'a, (Integer, Text), brands::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    completions!(brands::table, brands::owner, prefix, max, lower)
+}
+/// Returns the food groups starting with prefix along with their lengths
+pub fn suggest_food_groups<'a, DB>(
+    prefix: &str,
+    max: i64,
+    lower: bool,
+) -> BoxedSelectStatement<'a, (Integer, Text), food_groups::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    completions!(food_groups::table, food_groups::description, prefix, max, lower)
+}
+/// Selects a group column and each food's per 100 g value of a nutrient for
+/// the foods filtered as in filter_foods which have the nutrient
+macro_rules! nutrient_values {
+    ($filter:expr, $search:expr, $nutrientno:expr, $col:expr) => {{
+        let mut f = $filter.clone();
+        f.nutrients.push(NutrientRange::any($nutrientno, Basis::Unit));
+        filter_foods(&f, $search).select(($col, nutrient_value($nutrientno, Basis::Unit)))
+    }};
+}
+/// Returns the food group and value of a nutrient of the foods filtered as in
+/// filter_foods which have the nutrient
+pub fn nutrient_values_by_food_group<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    nutrientno: &str,
+) -> BoxedSelectStatement<'a, (Integer, Double), foods::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    nutrient_values!(filter, search, nutrientno, foods::food_group_id)
+}
+/// Returns the brand owner and value of a nutrient of the foods filtered as
+/// in filter_foods which have the nutrient
+pub fn nutrient_values_by_owner<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    nutrientno: &str,
+) -> BoxedSelectStatement<'a, (Nullable<Text>, Double), foods::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    nutrient_values!(filter, search, nutrientno, food_owner())
+}
+/// Returns the country and value of a nutrient of the foods filtered as in
+/// filter_foods which have the nutrient
+pub fn nutrient_values_by_country<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    nutrientno: &str,
+) -> BoxedSelectStatement<'a, (Nullable<Text>, Double), foods::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    nutrient_values!(filter, search, nutrientno, foods::country)
+}
+/// Returns the nutrient_data rows of a nutrient for the foods filtered as in
+/// filter_foods
+fn filtered_nutrient_data<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    nutrientno: &str,
+) -> nutrient_data::BoxedQuery<'a, DB>
+where
+    DB: FoodQueryBackend,
+{
+    let nutrient = nutrients::table
+        .select(nutrients::id)
+        .filter(nutrients::nutrientno.eq(nutrientno.to_string()));
+    nutrient_data::table
+        .filter(nutrient_data::nutrient_id.eq_any(nutrient))
+        .filter(nutrient_data::food_id.eq_any(filter_foods(filter, search).select(foods::id)))
+        .into_boxed()
+}
+/// Returns the least and greatest value of a nutrient on a basis in the foods
+/// filtered as in filter_foods, both NULL if none has the nutrient
+pub fn nutrient_value_range<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    nutrientno: &str,
+    basis: Basis,
+) -> BoxedSelectStatement<'a, (Nullable<Double>, Nullable<Double>), nutrient_data::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    let col = format!("nutrient_data.{}", basis.column());
+    filtered_nutrient_data(filter, search, nutrientno).select(sql::<(
+        Nullable<Double>,
+        Nullable<Double>,
+    )>(&format!("MIN({0}), MAX({0})", col)))
+}
+/// Returns the number of foods filtered as in filter_foods whose value of a
+/// nutrient falls in each bin between consecutive edges.  Bins are numbered
+/// from 0, include their lower edge and, for the last, their upper.  Values
+/// below the first edge are counted in bin -1 and above the last in bin
+/// edges.len() - 1.
+pub fn histogram_bins<'a, DB>(
+    filter: &FoodFilter,
+    search: Option<Search<'a, DB>>,
+    nutrientno: &str,
+    basis: Basis,
+    edges: &[f64],
+) -> BoxedSelectStatement<'a, (Integer, BigInt), nutrient_data::table, DB>
+where
+    DB: FoodQueryBackend,
+{
+    let col = format!("nutrient_data.{}", basis.column());
+    let mut case = format!("CASE WHEN {} < {:e} THEN -1", col, edges[0]);
+    let last = edges.len() - 1;
+    for (i, e) in edges.iter().enumerate().skip(1) {
+        let op = if i == last { "<=" } else { "<" };
+        case.push_str(&format!(" WHEN {} {} {:e} THEN {}", col, op, e, i - 1));
+    }
+    case.push_str(&format!(" ELSE {} END", last));
+    filtered_nutrient_data(filter, search, nutrientno)
+        .select((sql::<Integer>(&case), sql::<BigInt>("COUNT(*)")))
+        .group_by(sql::<Integer>(&case))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diesel::debug_query;
+    use diesel::sqlite::Sqlite;
+    struct NoText;
+    impl FullText<Sqlite> for NoText {
+        fn search(&self, filter: &FoodFilter) -> Option<Search<'static, Sqlite>> {
+            search_predicate(filter.query.as_ref()?, |t| {
+                Box::new(foods::description.eq(t.words.join(" ")))
+            })
+        }
+        fn rank(&self, _filter: &FoodFilter) -> Option<Rank<'static, Sqlite>> {
+            None
+        }
+    }
+    fn browse_sql(filter: &FoodFilter, sort: &str) -> String {
+        debug_query::<Sqlite, _>(&browse_foods(filter, &NoText, 10, 0, sort, "desc", None))
+            .to_string()
+    }
+    #[test]
+    fn search_groups() {
+        let mut f = FoodFilter::new();
+        f.search("jam OR butter -salt").unwrap();
+        let sql = browse_sql(&f, "id");
+        // terms ORed within a group, groups ANDed and exclusions negated
+        assert!(
+            sql.contains(
+                "WHERE (`foods`.`description` = ? OR `foods`.`description` = ?) \
+                 AND NOT (`foods`.`description` = ?)"
+            ),
+            "{}",
+            sql
+        );
+        assert!(sql.ends_with(r#"binds: ["jam", "butter", "salt", 10, 0]"#));
+    }
+    #[test]
+    fn read_hits() {
+        let mut f = FoodFilter::new();
+        f.hits = Some(vec![(3, 1.5)]);
+        let sql = browse_sql(&f, "relevance");
+        assert!(
+            sql.contains("`foods`.`id` IN (SELECT `search_hits`.`id` FROM `search_hits`)"),
+            "{}",
+            sql
+        );
+        assert!(
+            sql.contains("ORDER BY COALESCE((SELECT search_hits.score"),
+            "{}",
+            sql
+        );
+        // without hits or a rank the relevance sort falls back to id
+        assert!(!browse_sql(&FoodFilter::new(), "relevance").contains("search_hits"));
+        assert_eq!(
+            insert_hits("search_hits", &[(1, 2.5), (30, 0.125)]),
+            "INSERT INTO search_hits (id, score) VALUES (1, 2.5e0), (30, 1.25e-1)"
+        );
+    }
+    #[test]
+    fn ngram_sql() {
+        assert_eq!(ngram_match("foods.description", "", 0.5), "1 = 0");
+        // half the word's 4 inner trigrams
+        let sql = ngram_match("foods.description", "Peanut", 0.5);
+        assert!(sql.starts_with("(CASE WHEN foods.description LIKE '%pea%' THEN 1 ELSE 0 END + "));
+        assert_eq!(sql.matches(" LIKE ").count(), 4);
+        assert!(sql.ends_with(") >= 2"));
+    }
+}
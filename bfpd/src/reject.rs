@@ -0,0 +1,170 @@
+//! Rows that fail validation during ingest.  A rejected row is written to a
+//! reject csv, after its file, line and the reason it was rejected, and the
+//! load goes on without it unless more rows are rejected than allowed.  The
+//! rows loaded, skipped and rejected are counted by file for the run's summary.
+use csv::{StringRecord, Writer, WriterBuilder};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// The rows of a csv file loaded, skipped and rejected.  Rows are skipped when they're
+/// already loaded, e.g. when a load is resumed.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct FileCounts {
+    pub file: String,
+    pub loaded: usize,
+    pub skipped: usize,
+    pub rejected: usize,
+    /// the checkpoint's row a resumed load picked up after
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resumed_after: Option<i64>,
+    /// the release an update's rows are from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub release: Option<String>,
+    /// the rows loaded earlier which an update deleted to load its own in their place
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replaced: Option<usize>,
+}
+/// A run's counts by file and the reject csv, if any rows were rejected
+#[derive(Serialize, Debug)]
+pub struct Summary<'a> {
+    pub files: &'a [FileCounts],
+    pub rejects: Option<String>,
+}
+pub struct Rejects {
+    path: PathBuf,
+    /// opened with the first rejected row
+    writer: Option<Writer<File>>,
+    /// the most rows that may be rejected, no limit if None
+    max: Option<usize>,
+    rejected: usize,
+    files: Vec<FileCounts>,
+}
+impl Rejects {
+    /// Rejects rows to a csv at path, up to max of them if given
+    pub fn new(path: &Path, max: Option<usize>) -> Self {
+        Rejects {
+            path: path.to_path_buf(),
+            writer: None,
+            max,
+            rejected: 0,
+            files: Vec::new(),
+        }
+    }
+    pub fn loaded(&mut self, file: &str, count: usize) {
+        self.counts(file).loaded += count;
+    }
+    pub fn skipped(&mut self, file: &str, count: usize) {
+        self.counts(file).skipped += count;
+    }
+    pub fn resumed(&mut self, file: &str, row: i64) {
+        self.counts(file).resumed_after = Some(row);
+    }
+    pub fn replaced(&mut self, file: &str, release: &str, count: usize) {
+        let c = self.counts(file);
+        c.release = Some(release.to_string());
+        c.replaced = Some(c.replaced.unwrap_or_default() + count);
+    }
+    /// Writes a row to the reject csv with its line in the file, if known, and the reason.
+    /// It's an error once more rows are rejected than allowed.
+    pub fn reject(
+        &mut self,
+        file: &str,
+        line: Option<u64>,
+        record: &StringRecord,
+        reason: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.counts(file).rejected += 1;
+        self.rejected += 1;
+        if self.writer.is_none() {
+            let mut w = WriterBuilder::new().flexible(true).from_path(&self.path)?;
+            w.write_record(["file", "line", "reason"])?;
+            self.writer = Some(w);
+        }
+        if let Some(w) = self.writer.as_mut() {
+            let line = line.map(|l| l.to_string()).unwrap_or_default();
+            w.write_record([file, &line, reason].iter().copied().chain(record.iter()))?;
+            w.flush()?;
+        }
+        match self.max {
+            Some(max) if self.rejected > max => Err(Box::new(TooManyRejects(max))),
+            _ => Ok(()),
+        }
+    }
+    pub fn summary(&self) -> Summary<'_> {
+        Summary {
+            files: &self.files,
+            rejects: self
+                .writer
+                .as_ref()
+                .map(|_| self.path.display().to_string()),
+        }
+    }
+    fn counts(&mut self, file: &str) -> &mut FileCounts {
+        let i = match self.files.iter().position(|c| c.file == file) {
+            Some(i) => i,
+            None => {
+                self.files.push(FileCounts {
+                    file: file.to_string(),
+                    ..Default::default()
+                });
+                self.files.len() - 1
+            }
+        };
+        &mut self.files[i]
+    }
+}
+/// Returned when more rows are rejected than allowed
+#[derive(Debug)]
+pub struct TooManyRejects(usize);
+
+impl fmt::Display for TooManyRejects {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Stopped:  more than {} rows rejected", self.0)
+    }
+}
+
+impl Error for TooManyRejects {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+    #[test]
+    fn reject_rows() {
+        let path = env::temp_dir().join(format!("bfpd-rejects-{}.csv", process::id()));
+        let mut rejects = Rejects::new(&path, Some(1));
+        rejects.loaded("nutrient.csv", 2);
+        assert!(rejects.summary().rejects.is_none());
+        let r = StringRecord::from(vec!["1003", "Protein", "G", "", "600"]);
+        rejects.reject("nutrient.csv", Some(3), &r, "missing nutrient_nbr").unwrap();
+        rejects.skipped("food.csv", 1);
+        assert!(rejects.reject("food.csv", None, &r, "again").is_err());
+        let s = rejects.summary();
+        assert_eq!(s.rejects, Some(path.display().to_string()));
+        assert_eq!(
+            s.files[0],
+            FileCounts {
+                file: "nutrient.csv".to_string(),
+                loaded: 2,
+                skipped: 0,
+                rejected: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!((s.files[1].skipped, s.files[1].rejected), (1, 1));
+        let csv = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            vec![
+                "file,line,reason",
+                "nutrient.csv,3,missing nutrient_nbr,1003,Protein,G,,600",
+                "food.csv,,again,1003,Protein,G,,600"
+            ]
+        );
+        let _ = fs::remove_file(path);
+    }
+}
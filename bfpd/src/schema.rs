@@ -1,20 +1,30 @@
+// The tables shared by all backends.  Column types are limited to those
+// every backend understands:  date columns are declared as Timestamp even
+// though PostgreSQL stores a timestamptz (the pg pool sets the session time
+// zone to UTC) and MariaDB a datetime.  Backend specific columns, e.g. the
+// PostgreSQL kw_tsvector, are only referenced by the backend adapters.
 table! {
     derivations (id) {
         id -> Integer,
         code -> Varchar,
-        description -> Mediumtext,
+        description -> Text,
+    }
+}
+table! {
+    food_groups (id) {
+        id -> Integer,
+        description -> Varchar,
     }
 }
-
 table! {
     foods (id) {
         id -> Integer,
-        publication_date -> Datetime,
-        modified_date -> Datetime,
-        available_date -> Datetime,
+        publication_date -> Timestamp,
+        modified_date -> Timestamp,
+        available_date -> Timestamp,
         upc -> Varchar,
         fdc_id -> Varchar,
-        description -> Varchar,
+        description -> Text,
         food_group_id -> Integer,
         brand_id -> Integer,
         datasource -> Varchar,
@@ -22,17 +32,23 @@ table! {
         serving_unit -> Nullable<Varchar>,
         serving_description -> Nullable<Varchar>,
         country -> Nullable<Varchar>,
-        ingredients -> Nullable<Mediumtext>,
+        ingredients -> Nullable<Text>,
+        discontinued_date -> Nullable<Timestamp>,
+        package_weight -> Nullable<Varchar>,
+        preparation_state_code -> Nullable<Varchar>,
+        trade_channel -> Nullable<Varchar>,
+        short_description -> Nullable<Varchar>,
+        fdc_release -> Nullable<Varchar>,
     }
 }
-
 table! {
-    food_groups (id) {
-        id -> Integer,
-        description -> Varchar,
+    ingest_checkpoints (phase) {
+        phase -> Varchar,
+        file -> Varchar,
+        last_row -> BigInt,
+        last_fdc_id -> Varchar,
     }
 }
-
 table! {
     brands (id) {
         id -> Integer,
@@ -41,16 +57,6 @@ table! {
         subbrand -> Nullable<Varchar>,
     }
 }
-
-table! {
-    nutrients (id) {
-        id -> Integer,
-        nutrientno -> Varchar,
-        description -> Varchar,
-        unit -> Varchar,
-    }
-}
-
 table! {
     nutrient_data (id) {
         id -> Integer,
@@ -60,23 +66,48 @@ table! {
         minimum -> Nullable<Double>,
         maximum -> Nullable<Double>,
         median -> Nullable<Double>,
+        data_points -> Nullable<Integer>,
+        footnote -> Nullable<Text>,
+        min_year -> Nullable<Integer>,
         derivation_id -> Integer,
         nutrient_id -> Integer,
         food_id -> Integer,
+        fdc_release -> Nullable<Varchar>,
+    }
+}
+
+table! {
+    nutrients (id) {
+        id -> Integer,
+        nutrientno -> Varchar,
+        description -> Varchar,
+        unit -> Varchar,
+    }
+}
+
+// A search index's hits for the query a connection is running, which the
+// backend's Dialect fills.  It isn't in the migrations:  it's a temporary
+// table each connection creates, or on MariaDB a view of the connection's rows.
+table! {
+    search_hits (id) {
+        id -> Integer,
+        score -> Double,
     }
 }
 
-joinable!(foods -> food_groups (food_group_id));
 joinable!(foods -> brands (brand_id));
+joinable!(foods -> food_groups (food_group_id));
 joinable!(nutrient_data -> derivations (derivation_id));
 joinable!(nutrient_data -> foods (food_id));
 joinable!(nutrient_data -> nutrients (nutrient_id));
 
 allow_tables_to_appear_in_same_query!(
+    brands,
     derivations,
-    foods,
     food_groups,
-    brands,
-    nutrients,
+    foods,
+    ingest_checkpoints,
     nutrient_data,
+    nutrients,
+    search_hits,
 );
@@ -0,0 +1,237 @@
+//! The food search grammar.  A search is a list of terms which must all match,
+//! e.g. `"whole wheat" -sugar oat OR barley ingredients:palm`:
+//!
+//! * a term is a word or a "quoted phrase"
+//! * `-term` excludes foods matching the term
+//! * `term OR term` matches either term
+//! * `description:term` and `ingredients:term` match the term in just that field
+//!
+//! The backends compile the parsed terms to their own full-text syntax.
+use std::error::Error;
+use std::fmt;
+
+/// The food fields a term can be scoped to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Description,
+    Ingredients,
+}
+impl Field {
+    pub fn column(&self) -> &'static str {
+        match self {
+            Field::Description => "description",
+            Field::Ingredients => "ingredients",
+        }
+    }
+}
+/// A word or phrase to match.  Punctuation is dropped, so a term with more
+/// than one word, e.g. "whole wheat" or peanut-butter, is matched as a phrase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub words: Vec<String>,
+    pub field: Option<Field>,
+    pub exclude: bool,
+}
+impl Term {
+    /// True if the term is matched against the food description
+    pub fn in_description(&self) -> bool {
+        self.field != Some(Field::Ingredients)
+    }
+}
+/// A parsed search.  A food must match every group and a group matches if any
+/// of its terms do.  An excluded term is always a group of its own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    pub groups: Vec<Vec<Term>>,
+}
+enum Token {
+    Term(Term),
+    Or,
+}
+impl SearchQuery {
+    pub fn parse(q: &str) -> Result<SearchQuery, Box<dyn Error + Send + Sync>> {
+        let mut groups: Vec<Vec<Term>> = Vec::new();
+        let mut or = false;
+        for token in tokens(q)? {
+            match token {
+                Token::Or => {
+                    if or || groups.last().is_none_or(|g| g[0].exclude) {
+                        return Err(Box::new(SearchError::new(q, "OR must join two terms")));
+                    }
+                    or = true;
+                }
+                Token::Term(t) if or => {
+                    if t.exclude {
+                        return Err(Box::new(SearchError::new(
+                            q,
+                            "an excluded term can't be joined with OR",
+                        )));
+                    }
+                    groups.last_mut().unwrap().push(t);
+                    or = false;
+                }
+                Token::Term(t) => groups.push(vec![t]),
+            }
+        }
+        if or {
+            return Err(Box::new(SearchError::new(q, "OR must join two terms")));
+        }
+        if groups.iter().all(|g| g[0].exclude) {
+            return Err(Box::new(SearchError::new(
+                q,
+                "there must be at least one term which isn't excluded",
+            )));
+        }
+        Ok(SearchQuery { groups })
+    }
+    /// Returns the words of the terms which aren't excluded, e.g. for ranking
+    pub fn words(&self) -> Vec<&str> {
+        self.groups
+            .iter()
+            .flatten()
+            .filter(|t| !t.exclude)
+            .flat_map(|t| t.words.iter().map(|w| w.as_str()))
+            .collect()
+    }
+}
+/// Splits a search into terms and ORs
+fn tokens(q: &str) -> Result<Vec<Token>, Box<dyn Error + Send + Sync>> {
+    let mut tokens = Vec::new();
+    let mut chars = q.chars().peekable();
+    loop {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        if chars.peek().is_none() {
+            break;
+        }
+        let exclude = chars.peek() == Some(&'-');
+        if exclude {
+            chars.next();
+        }
+        // read up to whitespace, or the closing quote of a phrase
+        let mut text = String::new();
+        let mut field = None;
+        let mut phrase = false;
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            if c == '"' {
+                phrase = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => text.push(c),
+                        None => {
+                            return Err(Box::new(SearchError::new(q, "a phrase has no closing quote")))
+                        }
+                    }
+                }
+            } else if c == ':' && field.is_none() && !phrase {
+                field = match text.to_lowercase().as_str() {
+                    "description" => Some(Field::Description),
+                    "ingredients" => Some(Field::Ingredients),
+                    f => {
+                        return Err(Box::new(SearchError::new(
+                            q,
+                            &format!("unknown field {}, use description or ingredients", f),
+                        )))
+                    }
+                };
+                text.clear();
+            } else {
+                text.push(c);
+            }
+        }
+        if text == "OR" && !exclude && !phrase && field.is_none() {
+            tokens.push(Token::Or);
+            continue;
+        }
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(|w| w.to_string())
+            .collect();
+        if words.is_empty() {
+            if phrase || field.is_some() {
+                return Err(Box::new(SearchError::new(q, "a phrase or field has no words")));
+            }
+            continue;
+        }
+        tokens.push(Token::Term(Term {
+            words,
+            field,
+            exclude,
+        }));
+    }
+    Ok(tokens)
+}
+/// Returned when a search can't be parsed
+#[derive(Debug)]
+pub struct SearchError(pub String);
+
+impl SearchError {
+    fn new(q: &str, reason: &str) -> Self {
+        SearchError(format!("Invalid search {}:  {}", q, reason))
+    }
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for SearchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn term(words: &[&str], field: Option<Field>, exclude: bool) -> Term {
+        Term {
+            words: words.iter().map(|w| w.to_string()).collect(),
+            field,
+            exclude,
+        }
+    }
+    #[test]
+    fn parse_search() {
+        let q = SearchQuery::parse(r#""whole wheat" -sugar oat OR barley ingredients:palm"#).unwrap();
+        assert_eq!(
+            q.groups,
+            vec![
+                vec![term(&["whole", "wheat"], None, false)],
+                vec![term(&["sugar"], None, true)],
+                vec![term(&["oat"], None, false), term(&["barley"], None, false)],
+                vec![term(&["palm"], Some(Field::Ingredients), false)],
+            ]
+        );
+        assert_eq!(q.words(), vec!["whole", "wheat", "oat", "barley", "palm"]);
+        let q = SearchQuery::parse("description:\"peanut butter\" -ingredients:salt").unwrap();
+        assert_eq!(
+            q.groups,
+            vec![
+                vec![term(&["peanut", "butter"], Some(Field::Description), false)],
+                vec![term(&["salt"], Some(Field::Ingredients), true)],
+            ]
+        );
+        assert!(SearchQuery::parse("\"whole wheat").is_err());
+        assert!(SearchQuery::parse("oat OR").is_err());
+        assert!(SearchQuery::parse("OR oat").is_err());
+        assert!(SearchQuery::parse("oat OR -barley").is_err());
+        assert!(SearchQuery::parse("-sugar").is_err());
+        assert!(SearchQuery::parse("brand:acme").is_err());
+    }
+    #[test]
+    fn term_words() {
+        let q = SearchQuery::parse("peanut-butter -salt ingredients:oil").unwrap();
+        // excluded terms aren't ranked
+        assert_eq!(q.words(), vec!["peanut", "butter", "oil"]);
+        assert!(q.groups[0][0].in_description());
+        assert!(!q.groups[2][0].in_description());
+        let q = SearchQuery::parse("description:jam").unwrap();
+        assert!(q.groups[0][0].in_description());
+    }
+}
@@ -0,0 +1,188 @@
+//! Distribution statistics of a nutrient's per 100 g values across the foods
+//! in each food group, brand owner or country, e.g. for benchmarking a
+//! category.  The values are read from the database and summarized here as
+//! not every backend has percentile aggregates.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+/// The percentiles returned when none are asked for
+pub const DEFAULT_PERCENTILES: [f64; 4] = [10.0, 25.0, 75.0, 90.0];
+/// The groups returned when no maximum is asked for
+pub const DEFAULT_GROUPS: i64 = 10;
+
+/// The food column values are grouped by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupBy {
+    FoodGroup,
+    Owner,
+    Country,
+}
+impl GroupBy {
+    pub fn parse(by: &str) -> Result<GroupBy, Box<dyn Error + Send + Sync>> {
+        match by.trim().to_lowercase().as_str() {
+            "fg" | "group" | "foodgroup" => Ok(GroupBy::FoodGroup),
+            "owner" | "brand" => Ok(GroupBy::Owner),
+            "country" => Ok(GroupBy::Country),
+            _ => Err(Box::new(StatsError(format!(
+                "Unknown group {}.  Use fg, owner or country",
+                by
+            )))),
+        }
+    }
+}
+/// Parses a comma separated list of percentiles, each from 0 to 100
+pub fn parse_percentiles(list: &str) -> Result<Vec<f64>, Box<dyn Error + Send + Sync>> {
+    let mut percentiles = Vec::new();
+    for p in list.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let v = p.parse::<f64>().map_err(|_e| percentile_error(p))?;
+        percentiles.push(check_percentile(v)?);
+    }
+    Ok(percentiles)
+}
+/// Returns a percentile if it's from 0 to 100
+pub fn check_percentile(p: f64) -> Result<f64, Box<dyn Error + Send + Sync>> {
+    match (0.0..=100.0).contains(&p) {
+        true => Ok(p),
+        false => Err(Box::new(percentile_error(&p.to_string()))),
+    }
+}
+fn percentile_error(p: &str) -> StatsError {
+    StatsError(format!("Invalid percentile {}.  Use numbers from 0 to 100", p))
+}
+/// A group's value at a percentile
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Percentile {
+    pub percentile: f64,
+    pub value: f64,
+}
+/// The distribution of a nutrient's values in a group of foods
+#[derive(Serialize, Debug, PartialEq)]
+pub struct NutrientStats {
+    /// the food group, brand owner or country, "unknown" if it isn't known
+    pub group: String,
+    pub count: i64,
+    pub mean: f64,
+    pub median: f64,
+    /// the sample standard deviation, 0 for a single value
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub percentiles: Vec<Percentile>,
+}
+impl NutrientStats {
+    /// Summarizes a group's values, of which there must be at least one
+    pub fn create(group: String, mut values: Vec<f64>, percentiles: &[f64]) -> Self {
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let stddev = match values.len() {
+            1 => 0.0,
+            _ => (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt(),
+        };
+        Self {
+            group,
+            count: values.len() as i64,
+            mean,
+            median: percentile(&values, 50.0),
+            stddev,
+            min: values[0],
+            max: values[values.len() - 1],
+            percentiles: percentiles
+                .iter()
+                .map(|p| Percentile {
+                    percentile: *p,
+                    value: percentile(&values, *p),
+                })
+                .collect(),
+        }
+    }
+    /// Summarizes the values of groups of foods identified by a brand or food
+    /// group id, "unknown" if an id isn't in names
+    pub fn named(
+        groups: Vec<(i32, Vec<f64>)>,
+        names: &HashMap<i32, String>,
+        percentiles: &[f64],
+    ) -> Vec<NutrientStats> {
+        groups
+            .into_iter()
+            .map(|(id, values)| {
+                let group = names
+                    .get(&id)
+                    .map(|n| n.to_string())
+                    .unwrap_or_else(|| "unknown".to_string());
+                NutrientStats::create(group, values, percentiles)
+            })
+            .collect()
+    }
+    /// Summarizes the values of groups of foods by a column which may be null,
+    /// "unknown" if it is
+    pub fn nullable(
+        groups: Vec<(Option<String>, Vec<f64>)>,
+        percentiles: &[f64],
+    ) -> Vec<NutrientStats> {
+        groups
+            .into_iter()
+            .map(|(g, values)| {
+                let group = g.unwrap_or_else(|| "unknown".to_string());
+                NutrientStats::create(group, values, percentiles)
+            })
+            .collect()
+    }
+}
+/// Collects (group, value) rows into the max groups with the most values,
+/// largest first
+pub fn largest_groups<K: Eq + Hash + Ord>(rows: Vec<(K, f64)>, max: i64) -> Vec<(K, Vec<f64>)> {
+    let mut groups: HashMap<K, Vec<f64>> = HashMap::new();
+    for (k, v) in rows {
+        groups.entry(k).or_default().push(v);
+    }
+    let mut groups: Vec<(K, Vec<f64>)> = groups.into_iter().collect();
+    groups.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+    groups.truncate(max.max(0) as usize);
+    groups
+}
+/// Returns the value at a percentile of sorted values, interpolating between
+/// the two nearest as PostgreSQL's percentile_cont does
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let at = (sorted.len() - 1) as f64 * p / 100.0;
+    let (lo, hi) = (at.floor() as usize, at.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (at - lo as f64)
+}
+/// Returned for an unknown group or a bad percentile
+#[derive(Debug)]
+pub struct StatsError(pub String);
+
+impl fmt::Display for StatsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for StatsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn summarize_values() {
+        let s = NutrientStats::create("Snacks".to_string(), vec![4.0, 1.0, 3.0, 2.0], &[25.0]);
+        assert_eq!((s.count, s.mean, s.median), (4, 2.5, 2.5));
+        assert_eq!((s.min, s.max), (1.0, 4.0));
+        assert!((s.stddev - 1.2910).abs() < 1e-4);
+        assert_eq!(s.percentiles[0].value, 1.75);
+        assert_eq!(NutrientStats::create("one".to_string(), vec![7.0], &[]).stddev, 0.0);
+        let rows = vec![(3, 2.0), (2, 5.0), (3, 4.0), (1, 1.0)];
+        let names: HashMap<i32, String> = vec![(3, "Snacks".to_string()), (1, "Cereal".to_string())]
+            .into_iter()
+            .collect();
+        let s = NutrientStats::named(largest_groups(rows, 2), &names, &[]);
+        let groups: Vec<(&str, i64)> = s.iter().map(|s| (s.group.as_str(), s.count)).collect();
+        assert_eq!(groups, vec![("Snacks", 2), ("Cereal", 1)]);
+        assert_eq!(parse_percentiles("90, 10").unwrap(), vec![90.0, 10.0]);
+        assert!(parse_percentiles("101").is_err());
+        assert_eq!(GroupBy::parse("Brand").unwrap(), GroupBy::Owner);
+        assert!(GroupBy::parse("datasource").is_err());
+    }
+}
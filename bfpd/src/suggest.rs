@@ -0,0 +1,63 @@
+//! Type-ahead completion of food descriptions, brand owners and food groups
+use std::error::Error;
+use std::fmt;
+
+/// What a prefix completes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// foods.description
+    Food,
+    /// brands.owner
+    Owner,
+    /// food_groups.description
+    Group,
+}
+impl Kind {
+    pub fn parse(kind: &str) -> Result<Kind, Box<dyn Error + Send + Sync>> {
+        match kind.to_lowercase().as_str() {
+            "food" | "description" => Ok(Kind::Food),
+            "owner" | "brand" => Ok(Kind::Owner),
+            "group" | "fg" => Ok(Kind::Group),
+            _ => Err(Box::new(KindError(kind.to_string()))),
+        }
+    }
+}
+/// The escape character in prefix patterns
+pub const ESCAPE: char = '\\';
+
+/// Returns the LIKE pattern for names starting with prefix, lower cased and with
+/// any LIKE wildcards in it escaped
+pub fn prefix_pattern(prefix: &str) -> String {
+    let mut p = String::new();
+    for c in prefix.to_lowercase().chars() {
+        if c == '%' || c == '_' || c == ESCAPE {
+            p.push(ESCAPE);
+        }
+        p.push(c);
+    }
+    p.push('%');
+    p
+}
+/// Returned for an unknown kind of completion
+#[derive(Debug)]
+pub struct KindError(pub String);
+
+impl fmt::Display for KindError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unknown kind {}.  Use food, owner or group", self.0)
+    }
+}
+
+impl Error for KindError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn prefix_patterns() {
+        assert_eq!(prefix_pattern("Peanut B"), "peanut b%");
+        assert_eq!(prefix_pattern("100%_"), "100\\%\\_%");
+        assert_eq!(Kind::parse("Owner").unwrap(), Kind::Owner);
+        assert!(Kind::parse("nutrient").is_err());
+    }
+}
@@ -1,4 +1,8 @@
 -- This file should undo anything in `up.sql`
+drop table ingest_checkpoints;
+drop table ingest_indexes;
+drop view search_hits;
+drop table search_hit_rows;
 drop table nutrient_data;
 drop table derivations;
 drop table nutrients;
@@ -99,19 +99,77 @@ CREATE TABLE `foods` (
   `serving_description` varchar(256) DEFAULT NULL,
   `country` varchar(24) DEFAULT NULL,
   `ingredients` mediumtext DEFAULT NULL,
+  `discontinued_date` datetime DEFAULT NULL,
+  `package_weight` varchar(64) DEFAULT NULL,
+  `preparation_state_code` varchar(64) DEFAULT NULL,
+  `trade_channel` varchar(256) DEFAULT NULL,
+  `short_description` varchar(256) DEFAULT NULL,
+  `fdc_release` varchar(64) DEFAULT NULL,
   PRIMARY KEY (`id`),
-  KEY `foods_fdc_id_IDX` (`fdc_id`) USING BTREE,
+  UNIQUE KEY `foods_fdc_id_IDX` (`fdc_id`) USING BTREE,
   KEY `foods_upc_IDX` (`upc`) USING BTREE,
   KEY `foods_FK` (`brand_id`),
   KEY `foods_description_IDX` (`description`(768)) USING BTREE,
   KEY `foods_brand_id_IDX` (`brand_id`) USING BTREE,
   KEY `foods_food_group_id_IDX` (`food_group_id`) USING BTREE,
   KEY `foods_country_IDX` (`country`) USING BTREE,
+  FULLTEXT KEY `foods_keywords_FT` (`description`,`ingredients`),
+  FULLTEXT KEY `foods_description_FT` (`description`),
+  FULLTEXT KEY `foods_ingredients_FT` (`ingredients`),
   CONSTRAINT `foods_FK` FOREIGN KEY (`brand_id`) REFERENCES `brands` (`id`),
   CONSTRAINT `foods_FK_1` FOREIGN KEY (`food_group_id`) REFERENCES `food_groups` (`id`)
 ) ENGINE=InnoDB AUTO_INCREMENT=5107548 DEFAULT CHARSET=utf8mb4;
 /*!40101 SET character_set_client = @saved_cs_client */;
 
+--
+-- Table structure for table `ingest_checkpoints`
+--
+
+DROP TABLE IF EXISTS `ingest_checkpoints`;
+/*!40101 SET @saved_cs_client     = @@character_set_client */;
+/*!40101 SET character_set_client = utf8 */;
+CREATE TABLE `ingest_checkpoints` (
+  `phase` varchar(32) NOT NULL,
+  `file` varchar(512) NOT NULL,
+  `last_row` bigint(20) NOT NULL,
+  `last_fdc_id` varchar(24) NOT NULL,
+  PRIMARY KEY (`phase`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+/*!40101 SET character_set_client = @saved_cs_client */;
+
+--
+-- Table structure for table `ingest_indexes`
+--
+
+DROP TABLE IF EXISTS `ingest_indexes`;
+/*!40101 SET @saved_cs_client     = @@character_set_client */;
+/*!40101 SET character_set_client = utf8 */;
+CREATE TABLE `ingest_indexes` (
+  `tablename` varchar(64) NOT NULL,
+  `indexname` varchar(64) NOT NULL,
+  `definition` text NOT NULL,
+  PRIMARY KEY (`tablename`,`indexname`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+/*!40101 SET character_set_client = @saved_cs_client */;
+
+--
+-- Table structure for table `search_hit_rows`, a search index's hits by
+-- connection, and the view `search_hits` of the connection's own
+--
+
+DROP TABLE IF EXISTS `search_hit_rows`;
+/*!40101 SET @saved_cs_client     = @@character_set_client */;
+/*!40101 SET character_set_client = utf8 */;
+CREATE TABLE `search_hit_rows` (
+  `connection_id` bigint(20) unsigned NOT NULL DEFAULT (CONNECTION_ID()),
+  `id` int(10) NOT NULL,
+  `score` double NOT NULL,
+  PRIMARY KEY (`connection_id`,`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+/*!40101 SET character_set_client = @saved_cs_client */;
+CREATE OR REPLACE VIEW `search_hits` AS
+  SELECT `id`, `score` FROM `search_hit_rows` WHERE `connection_id` = CONNECTION_ID();
+
 --
 -- Table structure for table `nutrient_data`
 --
@@ -127,9 +185,13 @@ CREATE TABLE `nutrient_data` (
   `minimum` double DEFAULT NULL,
   `maximum` double DEFAULT NULL,
   `median` double DEFAULT NULL,
+  `data_points` int(10) DEFAULT NULL,
+  `footnote` text DEFAULT NULL,
+  `min_year` int(10) DEFAULT NULL,
   `derivation_id` int(10) NOT NULL DEFAULT 0,
   `nutrient_id` int(10) NOT NULL DEFAULT 0,
   `food_id` int(10) DEFAULT NULL,
+  `fdc_release` varchar(64) DEFAULT NULL,
   PRIMARY KEY (`id`),
   KEY `nutrient_data_food_id_IDX` (`food_id`) USING BTREE,
   KEY `nutrient_data_FK` (`nutrient_id`),
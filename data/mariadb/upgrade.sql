@@ -0,0 +1,52 @@
+-- Brings a database created from an earlier up.sql up to date with it.  Every
+-- statement can be run again, so it's safe to apply to a database which is
+-- part or all the way there.  Making fdc_id unique fails if a food was loaded
+-- twice;  delete the duplicates first.
+
+ALTER TABLE `foods`
+  ADD COLUMN IF NOT EXISTS `discontinued_date` datetime DEFAULT NULL,
+  ADD COLUMN IF NOT EXISTS `package_weight` varchar(64) DEFAULT NULL,
+  ADD COLUMN IF NOT EXISTS `preparation_state_code` varchar(64) DEFAULT NULL,
+  ADD COLUMN IF NOT EXISTS `trade_channel` varchar(256) DEFAULT NULL,
+  ADD COLUMN IF NOT EXISTS `short_description` varchar(256) DEFAULT NULL,
+  ADD COLUMN IF NOT EXISTS `fdc_release` varchar(64) DEFAULT NULL;
+
+ALTER TABLE `foods`
+  DROP INDEX IF EXISTS `foods_fdc_id_IDX`,
+  ADD UNIQUE INDEX `foods_fdc_id_IDX` (`fdc_id`) USING BTREE;
+
+-- InnoDB adds one FULLTEXT index at a time
+ALTER TABLE `foods` ADD FULLTEXT INDEX IF NOT EXISTS `foods_keywords_FT` (`description`,`ingredients`);
+ALTER TABLE `foods` ADD FULLTEXT INDEX IF NOT EXISTS `foods_description_FT` (`description`);
+ALTER TABLE `foods` ADD FULLTEXT INDEX IF NOT EXISTS `foods_ingredients_FT` (`ingredients`);
+
+ALTER TABLE `nutrient_data`
+  ADD COLUMN IF NOT EXISTS `data_points` int(10) DEFAULT NULL AFTER `median`,
+  ADD COLUMN IF NOT EXISTS `footnote` text DEFAULT NULL AFTER `data_points`,
+  ADD COLUMN IF NOT EXISTS `min_year` int(10) DEFAULT NULL AFTER `footnote`,
+  ADD COLUMN IF NOT EXISTS `fdc_release` varchar(64) DEFAULT NULL;
+
+CREATE TABLE IF NOT EXISTS `ingest_checkpoints` (
+  `phase` varchar(32) NOT NULL,
+  `file` varchar(512) NOT NULL,
+  `last_row` bigint(20) NOT NULL,
+  `last_fdc_id` varchar(24) NOT NULL,
+  PRIMARY KEY (`phase`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+CREATE TABLE IF NOT EXISTS `ingest_indexes` (
+  `tablename` varchar(64) NOT NULL,
+  `indexname` varchar(64) NOT NULL,
+  `definition` text NOT NULL,
+  PRIMARY KEY (`tablename`,`indexname`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+CREATE TABLE IF NOT EXISTS `search_hit_rows` (
+  `connection_id` bigint(20) unsigned NOT NULL DEFAULT (CONNECTION_ID()),
+  `id` int(10) NOT NULL,
+  `score` double NOT NULL,
+  PRIMARY KEY (`connection_id`,`id`)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+CREATE OR REPLACE VIEW `search_hits` AS
+  SELECT `id`, `score` FROM `search_hit_rows` WHERE `connection_id` = CONNECTION_ID();
@@ -1,4 +1,6 @@
 -- This file should undo anything in `up.sql`
+drop table ingest_checkpoints;
+drop table ingest_indexes;
 drop table nutrient_data;
 drop table derivations;
 drop table nutrients;
@@ -16,6 +16,12 @@ SET xmloption = content;
 SET client_min_messages = warning;
 SET row_security = off;
 
+--
+-- Name: pg_trgm; Type: EXTENSION; Schema: -; Owner: -
+--
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm WITH SCHEMA public;
+
 --
 -- Name: diesel_manage_updated_at(regclass); Type: FUNCTION; Schema: public; Owner: gmoore
 --
@@ -194,6 +200,12 @@ CREATE TABLE public.foods (
     serving_description character varying(256) DEFAULT NULL::character varying,
     country character varying(24) DEFAULT NULL::character varying,
     ingredients text,
+    discontinued_date timestamp with time zone,
+    package_weight character varying(64) DEFAULT NULL::character varying,
+    preparation_state_code character varying(64) DEFAULT NULL::character varying,
+    trade_channel character varying(256) DEFAULT NULL::character varying,
+    short_description character varying(256) DEFAULT NULL::character varying,
+    fdc_release character varying(64) DEFAULT NULL::character varying,
     kw_tsvector tsvector GENERATED ALWAYS AS (to_tsvector('english'::regconfig, ((COALESCE(description, (''::character varying)::text) || ' '::text) || COALESCE(ingredients, ''::text)))) STORED
 );
 
@@ -222,6 +234,33 @@ ALTER TABLE public.foods_id_seq OWNER TO gmoore;
 ALTER SEQUENCE public.foods_id_seq OWNED BY public.foods.id;
 
 
+--
+-- Name: ingest_checkpoints; Type: TABLE; Schema: public; Owner: gmoore
+--
+
+CREATE TABLE public.ingest_checkpoints (
+    phase character varying(32) NOT NULL,
+    file character varying(512) NOT NULL,
+    last_row bigint NOT NULL,
+    last_fdc_id character varying(24) NOT NULL
+);
+
+
+ALTER TABLE public.ingest_checkpoints OWNER TO gmoore;
+
+--
+-- Name: ingest_indexes; Type: TABLE; Schema: public; Owner: gmoore
+--
+
+CREATE TABLE public.ingest_indexes (
+    tablename character varying(64) NOT NULL,
+    indexname character varying(64) NOT NULL,
+    definition text NOT NULL
+);
+
+
+ALTER TABLE public.ingest_indexes OWNER TO gmoore;
+
 --
 -- Name: nutrient_data; Type: TABLE; Schema: public; Owner: gmoore
 --
@@ -234,9 +273,13 @@ CREATE TABLE public.nutrient_data (
     minimum double precision,
     maximum double precision,
     median double precision,
+    data_points integer,
+    footnote text,
+    min_year integer,
     derivation_id integer DEFAULT 0 NOT NULL,
     nutrient_id integer DEFAULT 0 NOT NULL,
-    food_id integer DEFAULT 0 NOT NULL
+    food_id integer DEFAULT 0 NOT NULL,
+    fdc_release character varying(64) DEFAULT NULL::character varying
 );
 
 
@@ -353,6 +396,30 @@ ALTER TABLE ONLY public.foods
     ADD CONSTRAINT foods_pkey PRIMARY KEY (id);
 
 
+--
+-- Name: foods foods_fdc_id_key; Type: CONSTRAINT; Schema: public; Owner: gmoore
+--
+
+ALTER TABLE ONLY public.foods
+    ADD CONSTRAINT foods_fdc_id_key UNIQUE (fdc_id);
+
+
+--
+-- Name: ingest_checkpoints ingest_checkpoints_pkey; Type: CONSTRAINT; Schema: public; Owner: gmoore
+--
+
+ALTER TABLE ONLY public.ingest_checkpoints
+    ADD CONSTRAINT ingest_checkpoints_pkey PRIMARY KEY (phase);
+
+
+--
+-- Name: ingest_indexes ingest_indexes_pkey; Type: CONSTRAINT; Schema: public; Owner: gmoore
+--
+
+ALTER TABLE ONLY public.ingest_indexes
+    ADD CONSTRAINT ingest_indexes_pkey PRIMARY KEY (tablename, indexname);
+
+
 --
 -- Name: nutrient_data nutrient_data_pkey; Type: CONSTRAINT; Schema: public; Owner: gmoore
 --
@@ -390,13 +457,6 @@ CREATE INDEX idx_16458_foods_brand_id_idx ON public.foods USING btree (brand_id)
 CREATE INDEX idx_16458_foods_description_idx ON public.foods USING btree (description);
 
 
---
--- Name: idx_16458_foods_fdc_id_idx; Type: INDEX; Schema: public; Owner: gmoore
---
-
-CREATE INDEX idx_16458_foods_fdc_id_idx ON public.foods USING btree (fdc_id);
-
-
 --
 -- Name: idx_16458_foods_fk; Type: INDEX; Schema: public; Owner: gmoore
 --
@@ -467,6 +527,48 @@ CREATE INDEX idx_16489_nutrient_data_food_id_idx ON public.nutrient_data USING b
 CREATE INDEX kw_tsvector_idx ON public.foods USING gin (kw_tsvector);
 
 
+--
+-- Name: foods_description_prefix_idx; Type: INDEX; Schema: public; Owner: gmoore
+--
+
+CREATE INDEX foods_description_prefix_idx ON public.foods USING btree (lower((description)::text) text_pattern_ops);
+
+
+--
+-- Name: brands_owner_prefix_idx; Type: INDEX; Schema: public; Owner: gmoore
+--
+
+CREATE INDEX brands_owner_prefix_idx ON public.brands USING btree (lower((owner)::text) text_pattern_ops);
+
+
+--
+-- Name: food_groups_description_prefix_idx; Type: INDEX; Schema: public; Owner: gmoore
+--
+
+CREATE INDEX food_groups_description_prefix_idx ON public.food_groups USING btree (lower((description)::text) text_pattern_ops);
+
+
+--
+-- Name: foods_description_trgm_idx; Type: INDEX; Schema: public; Owner: gmoore
+--
+
+CREATE INDEX foods_description_trgm_idx ON public.foods USING gin (description public.gin_trgm_ops);
+
+
+--
+-- Name: brands_owner_trgm_idx; Type: INDEX; Schema: public; Owner: gmoore
+--
+
+CREATE INDEX brands_owner_trgm_idx ON public.brands USING gin (owner public.gin_trgm_ops);
+
+
+--
+-- Name: food_groups_description_trgm_idx; Type: INDEX; Schema: public; Owner: gmoore
+--
+
+CREATE INDEX food_groups_description_trgm_idx ON public.food_groups USING gin (description public.gin_trgm_ops);
+
+
 --
 -- Name: foods foods_fk; Type: FK CONSTRAINT; Schema: public; Owner: gmoore
 --
@@ -0,0 +1,52 @@
+-- Brings a database created from an earlier up.sql up to date with it.  Every
+-- statement can be run again, so it's safe to apply to a database which is
+-- part or all the way there.  Making fdc_id unique fails if a food was loaded
+-- twice;  delete the duplicates first.
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm WITH SCHEMA public;
+
+ALTER TABLE public.foods
+    ADD COLUMN IF NOT EXISTS discontinued_date timestamp with time zone,
+    ADD COLUMN IF NOT EXISTS package_weight character varying(64) DEFAULT NULL::character varying,
+    ADD COLUMN IF NOT EXISTS preparation_state_code character varying(64) DEFAULT NULL::character varying,
+    ADD COLUMN IF NOT EXISTS trade_channel character varying(256) DEFAULT NULL::character varying,
+    ADD COLUMN IF NOT EXISTS short_description character varying(256) DEFAULT NULL::character varying,
+    ADD COLUMN IF NOT EXISTS fdc_release character varying(64) DEFAULT NULL::character varying;
+
+ALTER TABLE public.nutrient_data
+    ADD COLUMN IF NOT EXISTS data_points integer,
+    ADD COLUMN IF NOT EXISTS footnote text,
+    ADD COLUMN IF NOT EXISTS min_year integer,
+    ADD COLUMN IF NOT EXISTS fdc_release character varying(64) DEFAULT NULL::character varying;
+
+CREATE TABLE IF NOT EXISTS public.ingest_checkpoints (
+    phase character varying(32) NOT NULL,
+    file character varying(512) NOT NULL,
+    last_row bigint NOT NULL,
+    last_fdc_id character varying(24) NOT NULL,
+    CONSTRAINT ingest_checkpoints_pkey PRIMARY KEY (phase)
+);
+
+CREATE TABLE IF NOT EXISTS public.ingest_indexes (
+    tablename character varying(64) NOT NULL,
+    indexname character varying(64) NOT NULL,
+    definition text NOT NULL,
+    CONSTRAINT ingest_indexes_pkey PRIMARY KEY (tablename, indexname)
+);
+
+-- the unique constraint's index replaces the plain one on fdc_id
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_constraint WHERE conname = 'foods_fdc_id_key') THEN
+        ALTER TABLE ONLY public.foods ADD CONSTRAINT foods_fdc_id_key UNIQUE (fdc_id);
+    END IF;
+END
+$$;
+DROP INDEX IF EXISTS public.idx_16458_foods_fdc_id_idx;
+
+CREATE INDEX IF NOT EXISTS foods_description_prefix_idx ON public.foods USING btree (lower((description)::text) text_pattern_ops);
+CREATE INDEX IF NOT EXISTS brands_owner_prefix_idx ON public.brands USING btree (lower((owner)::text) text_pattern_ops);
+CREATE INDEX IF NOT EXISTS food_groups_description_prefix_idx ON public.food_groups USING btree (lower((description)::text) text_pattern_ops);
+CREATE INDEX IF NOT EXISTS foods_description_trgm_idx ON public.foods USING gin (description public.gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS brands_owner_trgm_idx ON public.brands USING gin (owner public.gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS food_groups_description_trgm_idx ON public.food_groups USING gin (description public.gin_trgm_ops);
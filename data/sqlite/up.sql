@@ -0,0 +1,131 @@
+-- SQLite schema for the bfpd database.  Every statement is idempotent so the
+-- sqlite library can apply it when it opens a database file.
+CREATE TABLE IF NOT EXISTS brands (
+  id INTEGER PRIMARY KEY,
+  owner VARCHAR(255) NOT NULL DEFAULT '',
+  brand VARCHAR(255) DEFAULT NULL,
+  subbrand VARCHAR(255) DEFAULT NULL
+);
+CREATE INDEX IF NOT EXISTS brand_owner_idx ON brands (owner);
+CREATE INDEX IF NOT EXISTS brand_owner_nocase_idx ON brands (owner COLLATE NOCASE);
+
+CREATE TABLE IF NOT EXISTS derivations (
+  id INTEGER PRIMARY KEY,
+  code VARCHAR(255) NOT NULL,
+  description TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS food_groups (
+  id INTEGER PRIMARY KEY,
+  description VARCHAR(255) NOT NULL DEFAULT ''
+);
+CREATE INDEX IF NOT EXISTS food_groups_description_idx ON food_groups (description);
+CREATE INDEX IF NOT EXISTS food_groups_description_nocase_idx ON food_groups (description COLLATE NOCASE);
+
+CREATE TABLE IF NOT EXISTS foods (
+  id INTEGER PRIMARY KEY,
+  publication_date TIMESTAMP NOT NULL,
+  modified_date TIMESTAMP NOT NULL,
+  available_date TIMESTAMP NOT NULL,
+  upc VARCHAR(24) NOT NULL,
+  fdc_id VARCHAR(24) NOT NULL,
+  description TEXT NOT NULL,
+  food_group_id INTEGER NOT NULL DEFAULT 0 REFERENCES food_groups (id),
+  brand_id INTEGER NOT NULL DEFAULT 0 REFERENCES brands (id),
+  datasource VARCHAR(8) NOT NULL,
+  serving_size DOUBLE DEFAULT NULL,
+  serving_unit VARCHAR(24) DEFAULT NULL,
+  serving_description VARCHAR(256) DEFAULT NULL,
+  country VARCHAR(24) DEFAULT NULL,
+  ingredients TEXT DEFAULT NULL,
+  discontinued_date TIMESTAMP DEFAULT NULL,
+  package_weight VARCHAR(64) DEFAULT NULL,
+  preparation_state_code VARCHAR(64) DEFAULT NULL,
+  trade_channel VARCHAR(256) DEFAULT NULL,
+  short_description VARCHAR(256) DEFAULT NULL,
+  fdc_release VARCHAR(64) DEFAULT NULL
+);
+CREATE UNIQUE INDEX IF NOT EXISTS foods_fdc_id_idx ON foods (fdc_id);
+CREATE INDEX IF NOT EXISTS foods_upc_idx ON foods (upc);
+CREATE INDEX IF NOT EXISTS foods_brand_id_idx ON foods (brand_id);
+CREATE INDEX IF NOT EXISTS foods_food_group_id_idx ON foods (food_group_id);
+CREATE INDEX IF NOT EXISTS foods_country_idx ON foods (country);
+CREATE INDEX IF NOT EXISTS foods_description_nocase_idx ON foods (description COLLATE NOCASE);
+
+-- full-text index on the food description and ingredients, kept in step with
+-- the foods table by the triggers below
+CREATE VIRTUAL TABLE IF NOT EXISTS foods_fts USING fts5 (
+  description,
+  ingredients,
+  content = 'foods',
+  content_rowid = 'id',
+  tokenize = 'porter unicode61'
+);
+CREATE TRIGGER IF NOT EXISTS foods_fts_insert AFTER INSERT ON foods BEGIN
+  INSERT INTO foods_fts (rowid, description, ingredients)
+  VALUES (new.id, new.description, new.ingredients);
+END;
+CREATE TRIGGER IF NOT EXISTS foods_fts_delete AFTER DELETE ON foods BEGIN
+  INSERT INTO foods_fts (foods_fts, rowid, description, ingredients)
+  VALUES ('delete', old.id, old.description, old.ingredients);
+END;
+CREATE TRIGGER IF NOT EXISTS foods_fts_update AFTER UPDATE ON foods BEGIN
+  INSERT INTO foods_fts (foods_fts, rowid, description, ingredients)
+  VALUES ('delete', old.id, old.description, old.ingredients);
+  INSERT INTO foods_fts (rowid, description, ingredients)
+  VALUES (new.id, new.description, new.ingredients);
+END;
+
+-- trigram index on the food description, narrowing a fuzzy search to the
+-- foods sharing a trigram with its term
+CREATE VIRTUAL TABLE IF NOT EXISTS foods_trigrams USING fts5 (
+  description,
+  content = 'foods',
+  content_rowid = 'id',
+  tokenize = 'trigram'
+);
+CREATE TRIGGER IF NOT EXISTS foods_trigrams_insert AFTER INSERT ON foods BEGIN
+  INSERT INTO foods_trigrams (rowid, description) VALUES (new.id, new.description);
+END;
+CREATE TRIGGER IF NOT EXISTS foods_trigrams_delete AFTER DELETE ON foods BEGIN
+  INSERT INTO foods_trigrams (foods_trigrams, rowid, description)
+  VALUES ('delete', old.id, old.description);
+END;
+CREATE TRIGGER IF NOT EXISTS foods_trigrams_update AFTER UPDATE OF description ON foods BEGIN
+  INSERT INTO foods_trigrams (foods_trigrams, rowid, description)
+  VALUES ('delete', old.id, old.description);
+  INSERT INTO foods_trigrams (rowid, description) VALUES (new.id, new.description);
+END;
+
+CREATE TABLE IF NOT EXISTS nutrients (
+  id INTEGER PRIMARY KEY,
+  nutrientno VARCHAR(12) NOT NULL UNIQUE,
+  description VARCHAR(255) NOT NULL,
+  unit VARCHAR(24) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS ingest_checkpoints (
+  phase VARCHAR(32) PRIMARY KEY,
+  file VARCHAR(512) NOT NULL,
+  last_row BIGINT NOT NULL,
+  last_fdc_id VARCHAR(24) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS nutrient_data (
+  id INTEGER PRIMARY KEY,
+  value DOUBLE NOT NULL DEFAULT 0,
+  portion_value DOUBLE NOT NULL DEFAULT 0,
+  standard_error DOUBLE DEFAULT NULL,
+  minimum DOUBLE DEFAULT NULL,
+  maximum DOUBLE DEFAULT NULL,
+  median DOUBLE DEFAULT NULL,
+  data_points INTEGER DEFAULT NULL,
+  footnote TEXT DEFAULT NULL,
+  min_year INTEGER DEFAULT NULL,
+  derivation_id INTEGER NOT NULL DEFAULT 0 REFERENCES derivations (id),
+  nutrient_id INTEGER NOT NULL DEFAULT 0 REFERENCES nutrients (id),
+  food_id INTEGER DEFAULT NULL REFERENCES foods (id),
+  fdc_release VARCHAR(64) DEFAULT NULL
+);
+CREATE INDEX IF NOT EXISTS nutrient_data_food_id_idx ON nutrient_data (food_id);
+CREATE INDEX IF NOT EXISTS nutrient_data_nutrient_id_idx ON nutrient_data (nutrient_id);
@@ -3,39 +3,47 @@ extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
 use crate::views::*;
-use juniper::{graphql_value, FieldError, FieldResult, IntoFieldError, RootNode};
-#[cfg(feature = "maria")]
-use mariadb::db::MysqlPool;
-#[cfg(feature = "maria")]
-use mariadb::models::*;
-#[cfg(feature = "maria")]
-use mariadb::{Browse, Count, Get};
-#[cfg(feature = "postgres")]
-use pg::db::PgPool;
-#[cfg(feature = "postgres")]
-use pg::models::*;
-#[cfg(feature = "postgres")]
-use pg::{Browse, Count, Get};
+use juniper::{graphql_value, FieldError, FieldResult, IntoFieldError, Object, RootNode, Value};
+use bfpd::cursor::{self, Cursor, Page};
+use bfpd::facet::{FacetField, DEFAULT_VALUES};
+use bfpd::filter::{Basis, DateRange, FoodFilter, NutrientRange, NutrientSort};
+use bfpd::fuzzy::NoMatchError;
+use bfpd::histogram::{Bins, DEFAULT_BINS};
+use bfpd::models::*;
+use bfpd::stats::{self, GroupBy, DEFAULT_GROUPS, DEFAULT_PERCENTILES};
+use bfpd::suggest::Kind;
+use bfpd::Store;
+use std::error::Error;
+use std::sync::Arc;
 
 const MAX_RECS: i32 = 150;
 const DEFAULT_RECS: i32 = 50;
+const MAX_SUGGESTIONS: i32 = 50;
+const DEFAULT_SUGGESTIONS: i32 = 10;
 
 #[derive(Clone)]
 pub struct Context {
-    #[cfg(feature = "maria")]
-    pub db: MysqlPool,
-    #[cfg(feature = "postgres")]
-    pub db: PgPool,
+    pub db: Arc<dyn Store>,
 }
 
 impl juniper::Context for Context {}
 
+#[allow(clippy::enum_variant_names)]
 enum CustomError {
     MaxValidationError,
     OffsetError,
+    OrderError,
     FoodSortError,
-    FoodGroupNotFoundError,
-    ManuNotFoundError,
+    FoodGroupNotFoundError(Vec<String>),
+    ManuNotFoundError(Vec<String>),
+    DateRangeError,
+    CursorError,
+    SearchError(String),
+    KindError,
+    NutrientRangeError(String),
+    NutrientNotFoundError(String),
+    StatsError(String),
+    HistogramError(String),
 }
 
 impl juniper::IntoFieldError for CustomError {
@@ -56,179 +64,231 @@ impl juniper::IntoFieldError for CustomError {
                     "type": "OFFSET_ERROR"
                 }),
             ),
+            CustomError::OrderError => FieldError::new(
+                "order parameter not recognized.  try 'asc' or 'desc'",
+                graphql_value!({
+                    "type": "ORDER_ERROR"
+                }),
+            ),
             CustomError::FoodSortError => FieldError::new(
-                "sort parameter not recognized.  try 'description','fdcid', 'upc' or 'id'",
+                "sort parameter not recognized.  try 'description','fdcid', 'upc', 'id', 'relevance' or 'nutrient:nbr', adding ':portion' for per serving values",
                 graphql_value!({
                     "type": "SORT_ERROR"
                 }),
             ),
-            CustomError::FoodGroupNotFoundError => FieldError::new(
-                "Food group not found.",
+            CustomError::FoodGroupNotFoundError(suggestions) => FieldError::new(
+                did_you_mean("Food group not found.", &suggestions),
+                not_found(suggestions),
+            ),
+            CustomError::ManuNotFoundError(suggestions) => FieldError::new(
+                did_you_mean("Brand not found.", &suggestions),
+                not_found(suggestions),
+            ),
+            CustomError::CursorError => FieldError::new(
+                "cursor parameter not recognized.  use a cursor returned with the same sort and order and no offset",
                 graphql_value!({
-                    "type": "NOT_FOUND_ERROR"
+                    "type": "CURSOR_ERROR"
+                }),
+            ),
+            CustomError::SearchError(reason) => FieldError::new(
+                format!("query parameter not recognized.  {}", reason),
+                graphql_value!({
+                    "type": "SEARCH_ERROR"
                 }),
             ),
-            CustomError::ManuNotFoundError => FieldError::new(
-                "Brand not found.",
+            CustomError::KindError => FieldError::new(
+                "kind parameter not recognized.  try 'food', 'owner' or 'group'",
+                graphql_value!({
+                    "type": "KIND_ERROR"
+                }),
+            ),
+            CustomError::NutrientRangeError(reason) => FieldError::new(
+                format!("nutrients parameter not recognized.  {}", reason),
+                graphql_value!({
+                    "type": "NUTRIENT_RANGE_ERROR"
+                }),
+            ),
+            CustomError::NutrientNotFoundError(nbr) => FieldError::new(
+                format!("Nutrient {} not found.", nbr),
                 graphql_value!({
                     "type": "NOT_FOUND_ERROR"
                 }),
             ),
+            CustomError::StatsError(reason) => FieldError::new(
+                reason,
+                graphql_value!({
+                    "type": "STATS_ERROR"
+                }),
+            ),
+            CustomError::HistogramError(reason) => FieldError::new(
+                reason,
+                graphql_value!({
+                    "type": "HISTOGRAM_ERROR"
+                }),
+            ),
+            CustomError::DateRangeError => FieldError::new(
+                "pubdate parameter not recognized.  try YYYY-MM-DD or YYYY-MM-DD:YYYY-MM-DD",
+                graphql_value!({
+                    "type": "DATE_ERROR"
+                }),
+            ),
         }
     }
 }
+fn did_you_mean(msg: &str, suggestions: &[String]) -> String {
+    match suggestions.is_empty() {
+        true => msg.to_string(),
+        false => format!("{}  Did you mean {}?", msg, suggestions.join(" or ")),
+    }
+}
+/// the extensions of a not found error, listing the closest names
+fn not_found(suggestions: Vec<String>) -> Value {
+    let mut ext = Object::with_capacity(2);
+    ext.add_field("type", Value::scalar("NOT_FOUND_ERROR".to_string()));
+    ext.add_field(
+        "suggestions",
+        Value::list(suggestions.into_iter().map(Value::scalar).collect()),
+    );
+    Value::object(ext)
+}
+/// true if there's a nutrient numbered nbr
+fn nutrient_exists(nbr: &str, context: &Context) -> bool {
+    let mut n = Nutrient::new();
+    n.nutrientno = nbr.to_string();
+    n.find_by_no(&*context.db).is_ok()
+}
+/// reads an order argument, the default if there's none
+fn read_order(order: Option<&str>, default: &'static str) -> FieldResult<String> {
+    match cursor::parse_order(order, default) {
+        Some(o) => Ok(o.to_string()),
+        None => Err(CustomError::OrderError.into_field_error()),
+    }
+}
+/// the names a failed fuzzy lookup came closest to, if any
+fn suggestions(e: &(dyn Error + Send + Sync + 'static)) -> Vec<String> {
+    e.downcast_ref::<NoMatchError>()
+        .map(|n| n.suggestions.clone())
+        .unwrap_or_default()
+}
 pub struct QueryRoot;
 #[juniper::object(Context = Context)]
 impl QueryRoot {
     // count foods in a query
-    fn foods_count(context: &Context, mut filters: Browsefilters) -> FieldResult<Querycount> {
+    fn foods_count(context: &Context, filters: Browsefilters) -> FieldResult<Querycount> {
         use std::convert::TryFrom;
-        let mut food = Food::new();
-        let conn = context.db.get().unwrap();
-        let mut fm = Brand::new();
-        fm.owner = match filters.owners {
-            None => "".to_string(),
-            Some(m) => m,
-        };
-        if !fm.owner.is_empty() {
-            let i = match fm.find_by_owner(&conn) {
-                Ok(data) => data.id,
-                Err(_e) => -1,
-            };
-            if i == -1 {
-                return Err(CustomError::ManuNotFoundError.into_field_error());
-            }
-            food.brand_id = i;
-        }
-        let mut fgg = Foodgroup::new();
-        fgg.description = match filters.food_group {
-            None => "".to_string(),
-            Some(m) => m,
-        };
-        if fgg.description.len() > 0 {
-            let i = match fgg.find_by_description(&conn) {
-                Ok(data) => data.id,
-                Err(_e) => -1,
-            };
-            if i == -1 {
-                return Err(CustomError::FoodGroupNotFoundError.into_field_error());
-            }
-            food.food_group_id = i;
-        }
-        food.ingredients = match filters.publication_date {
-            None => None,
-            Some(m) => Some(m),
-        };
-        food.description = match filters.query {
-            None => "".to_string(),
-            Some(m) => m,
-        };
-        let c64 = food.query_count(&conn)?;
+        let filter = filters.food_filter(context)?;
+        let c64 = context.db.query_count(&filter)?;
         let c32 = i32::try_from(c64)?;
         Ok(Querycount { count: c32 })
     }
+    // count the foods in a query by food group, owner, country and datasource
+    fn food_facets(
+        context: &Context,
+        filters: Browsefilters,
+        max: Option<i32>,
+    ) -> FieldResult<Facetsview> {
+        let max = max.unwrap_or(DEFAULT_VALUES as i32);
+        if !(1..=MAX_RECS).contains(&max) {
+            return Err(CustomError::MaxValidationError.into_field_error());
+        }
+        let filter = filters.food_filter(context)?;
+        let facets = context
+            .db
+            .facets(&filter, &FacetField::ALL, max as i64)?;
+        Ok(Facetsview::create(facets))
+    }
     async fn foods(
         context: &Context,
         mut browse: Browsequery,
         nids: Vec<String>,
-    ) -> FieldResult<Vec<Foodview>> {
-        let conn = context.db.get().unwrap();
-
+    ) -> FieldResult<Foodpage> {
         let mut max = match browse.max {
             None => DEFAULT_RECS,
             Some(m) => m,
         };
-        if max > MAX_RECS || max < 1 {
+        if !(1..=MAX_RECS).contains(&max) {
             return Err(CustomError::MaxValidationError.into_field_error());
         };
-        let mut offset = match browse.offset {
-            None => 0,
-            Some(m) => m,
-        };
+        let mut offset = browse.offset.unwrap_or_default();
         if offset < 0 {
             return Err(CustomError::OffsetError.into_field_error());
         }
-        let mut order = match browse.order {
-            None => "".to_string(),
-            Some(m) => m,
+        let filter = match browse.filters {
+            None => FoodFilter::new(),
+            Some(m) => m.food_filter(context)?,
         };
+        // searches are ranked most relevant first unless asked otherwise
         let mut sort = match browse.sort {
-            None => "".to_string(),
-            Some(m) => m,
+            Some(m) if !m.is_empty() => m.to_lowercase(),
+            _ if filter.query.is_some() => "relevance".to_string(),
+            _ => "id".to_string(),
         };
-        if sort.is_empty() {
-            sort = "id".to_string();
-        }
-        sort = sort.to_lowercase();
         sort = match &*sort {
             "description" => "description".to_string(),
             "id" => "id".to_string(),
             "fdcid" => "fdcId".to_string(),
             "upc" => "upc".to_string(),
-            _ => "".to_string(),
+            "relevance" => "relevance".to_string(),
+            s => match NutrientSort::parse(s) {
+                Some(by) if nutrient_exists(&by.nutrientno, context) => by.to_string(),
+                Some(by) => {
+                    return Err(CustomError::NutrientNotFoundError(by.nutrientno).into_field_error())
+                }
+                None => "".to_string(),
+            },
         };
         if sort.is_empty() {
             return Err(CustomError::FoodSortError.into_field_error());
         }
-        let mut food = Food::new();
-        // stash filters into the Food struct, this is ugly but helps keep things simple
-        // for users and the model
-        let filters = match browse.filters {
-            None => Browsefilters::new(),
-            Some(m) => m,
-        };
-
-        let mut fm = Brand::new();
-        fm.owner = match filters.owners {
-            None => "".to_string(),
-            Some(m) => m,
-        };
-        if !fm.owner.is_empty() {
-            let i = match fm.find_by_owner(&conn) {
-                Ok(data) => data.id,
-                Err(_e) => -1,
-            };
-            if i == -1 {
-                return Err(CustomError::ManuNotFoundError.into_field_error());
+        let default = if sort == "relevance" { "desc" } else { "asc" };
+        let order = read_order(browse.order.as_deref(), default)?;
+        // page from a cursor if we have one, otherwise from the offset
+        let page = match browse.cursor {
+            Some(c) => {
+                if offset > 0 {
+                    return Err(CustomError::CursorError.into_field_error());
+                }
+                let cursor = match Cursor::decode(&c, &sort, &order) {
+                    Ok(c) => c,
+                    Err(_e) => return Err(CustomError::CursorError.into_field_error()),
+                };
+                context
+                    .db
+                    .browse_page(&filter, max as i64, Some(&cursor), sort, order)?
             }
-            food.brand_id = i;
-        }
-        // add food group filter if we have one
-        let mut fgg = Foodgroup::new();
-        fgg.description = match filters.food_group {
-            None => "".to_string(),
-            Some(m) => m,
-        };
-        if fgg.description.len() > 0 {
-            let i = match fgg.find_by_description(&conn) {
-                Ok(data) => data.id,
-                Err(_e) => -1,
-            };
-            if i < 1 {
-                return Err(CustomError::FoodGroupNotFoundError.into_field_error());
+            None if offset == 0 => context.db.browse_page(&filter, max as i64, None, sort, order)?,
+            None => {
+                let data = context.db.browse(
+                    &filter,
+                    max as i64 + 1,
+                    offset as i64,
+                    sort.to_string(),
+                    order.to_string(),
+                )?;
+                // the rank and nutrient values aren't read with an offset
+                // browse so there are no relevance or nutrient cursors
+                match &*sort {
+                    s if s == "relevance" || NutrientSort::parse(s).is_some() => Page {
+                        items: data.into_iter().take(max as usize).collect(),
+                        next: None,
+                        prev: None,
+                    },
+                    _ => Page::create(data, max as i64, None, &sort, &order),
+                }
             }
-            food.food_group_id = i;
-        }
-        food.country = match filters.country {
-            None => None,
-            Some(m) => Some(m),
-        };
-        // stash publication date filter into food ingredients
-        // ugly but expedient
-        food.ingredients = match filters.publication_date {
-            None => None,
-            Some(m) => Some(m),
         };
-        // put any search terms into the food description field
-        food.description = match filters.query {
-            None => "".to_string(),
-            Some(m) => m,
-        };
-        let data = food.browse(max as i64, offset as i64, sort, order, &conn)?;
-        Ok(Foodview::build_view(data, &nids, context))
+        let snippets = context.db.highlights(&filter, &page.items)?;
+        let mut foods = Foodview::build_view(page.items, &nids, context)?;
+        for (f, s) in foods.iter_mut().zip(snippets) {
+            f.snippet = s;
+        }
+        Ok(Foodpage {
+            foods,
+            next_cursor: page.next,
+            prev_cursor: page.prev,
+        })
     }
     async fn food(context: &Context, fid: String, nids: Vec<String>) -> FieldResult<Vec<Foodview>> {
-        let conn = context.db.get().unwrap();
         let mut food = Food::new();
 
         if fid.len() >= 10 {
@@ -237,14 +297,13 @@ impl QueryRoot {
             food.fdc_id = fid;
         }
 
-        let data = food.get(&conn)?;
-        Ok(Foodview::build_view(data, &nids, context))
+        let data = context.db.get(&food)?;
+        Ok(Foodview::build_view(data, &nids, context)?)
     }
     fn nutrient(context: &Context, nno: String) -> FieldResult<Vec<Nutrientview>> {
-        let conn = context.db.get().unwrap();
         let mut n = Nutrient::new();
         n.nutrientno = nno;
-        let nut = n.get(&conn)?;
+        let nut = context.db.get(&n)?;
         let mut nv: Vec<Nutrientview> = Vec::new();
         for i in &nut {
             let nv1 = &i;
@@ -260,17 +319,17 @@ impl QueryRoot {
         mut order: String,
         nids: Vec<String>,
     ) -> FieldResult<Vec<Nutrientview>> {
-        let conn = context.db.get()?;
         let mut b = false;
-        if max > MAX_RECS || max < 1 {
+        if !(1..=MAX_RECS).contains(&max) {
             return Err(CustomError::MaxValidationError.into_field_error());
         }
         if offset < 0 {
             return Err(CustomError::OffsetError.into_field_error());
         }
+        let order = read_order(Some(&order), "asc")?;
         let n = Nutrient::new();
 
-        let data = n.browse(max as i64, offset as i64, sort, order, &conn)?;
+        let data = context.db.browse(&n, max as i64, offset as i64, sort, order)?;
         let mut nv: Vec<Nutrientview> = Vec::new();
         for i in &data {
             let nv1 = &i;
@@ -286,18 +345,18 @@ impl QueryRoot {
         mut sort: String,
         order: String,
     ) -> FieldResult<Vec<BrandView>> {
-        let conn = context.db.get().unwrap();
-        if max > MAX_RECS || max < 1 {
+        if !(1..=MAX_RECS).contains(&max) {
             return Err(CustomError::MaxValidationError.into_field_error());
         }
         if offset < 0 {
             return Err(CustomError::OffsetError.into_field_error());
         }
+        let order = read_order(Some(&order), "asc")?;
         let m = Brand::new();
-        let data = m.browse(max as i64, offset as i64, sort, order, &conn)?;
+        let data = context.db.browse(&m, max as i64, offset as i64, sort, order)?;
         let mut mv: Vec<BrandView> = Vec::new();
         for i in &data {
-            mv.push(BrandView::create(&i));
+            mv.push(BrandView::create(i));
         }
         Ok(mv)
     }
@@ -308,15 +367,15 @@ impl QueryRoot {
         mut sort: String,
         order: String,
     ) -> FieldResult<Vec<FoodgroupView>> {
-        let conn = context.db.get().unwrap();
-        if max > MAX_RECS || max < 1 {
+        if !(1..=MAX_RECS).contains(&max) {
             return Err(CustomError::MaxValidationError.into_field_error());
         }
         if offset < 0 {
             return Err(CustomError::OffsetError.into_field_error());
         }
+        let order = read_order(Some(&order), "asc")?;
         let fg = Foodgroup::new();
-        let data = fg.browse(max as i64, offset as i64, sort, order, &conn)?;
+        let data = context.db.browse(&fg, max as i64, offset as i64, sort, order)?;
         let mut fgv: Vec<FoodgroupView> = Vec::new();
         for i in &data {
             let fgv1 = &i;
@@ -324,6 +383,145 @@ impl QueryRoot {
         }
         Ok(fgv)
     }
+    // foods within a range of each of one or more nutrients, sorted by the
+    // value of the nutrient numbered sort or else of the first range's
+    fn nutrients_report(
+        context: &Context,
+        nutrients: Vec<Nutrientrange>,
+        filters: Option<Browsefilters>,
+        sort: Option<String>,
+        order: Option<String>,
+        max: Option<i32>,
+        offset: Option<i32>,
+    ) -> FieldResult<Vec<NutrientsReportview>> {
+        let max = max.unwrap_or(DEFAULT_RECS);
+        if !(1..=MAX_RECS).contains(&max) {
+            return Err(CustomError::MaxValidationError.into_field_error());
+        }
+        let offset = offset.unwrap_or(0);
+        if offset < 0 {
+            return Err(CustomError::OffsetError.into_field_error());
+        }
+        let mut filter = match filters {
+            Some(f) => f.food_filter(context)?,
+            None => FoodFilter::new(),
+        };
+        for r in nutrients {
+            filter.nutrients.push(r.nutrient_range(context)?);
+        }
+        if filter.nutrients.is_empty() {
+            return Err(CustomError::NutrientRangeError(
+                "At least one range is required".to_string(),
+            )
+            .into_field_error());
+        }
+        let sort = sort.unwrap_or_default();
+        if !sort.is_empty() && !filter.nutrients.iter().any(|r| r.nutrientno == sort) {
+            return Err(CustomError::NutrientRangeError(format!(
+                "Sort {} must be one of the ranges' nutrients",
+                sort
+            ))
+            .into_field_error());
+        }
+        let order = read_order(order.as_deref(), "asc")?;
+        let data =
+            context
+                .db
+                .nutrients_report(&filter, &sort, &order, max as i64, offset as i64)?;
+        Ok(data.into_iter().map(NutrientsReportview::create).collect())
+    }
+    // the distribution of a nutrient's per 100g values in each food group,
+    // owner or country, the max groups with the most foods
+    fn nutrient_stats(
+        context: &Context,
+        nutrient: String,
+        by: Option<String>,
+        percentiles: Option<Vec<f64>>,
+        filters: Option<Browsefilters>,
+        max: Option<i32>,
+    ) -> FieldResult<Vec<NutrientStatsview>> {
+        let max = max.unwrap_or(DEFAULT_GROUPS as i32);
+        if !(1..=MAX_RECS).contains(&max) {
+            return Err(CustomError::MaxValidationError.into_field_error());
+        }
+        if !nutrient_exists(&nutrient, context) {
+            return Err(CustomError::NutrientNotFoundError(nutrient).into_field_error());
+        }
+        let by = match GroupBy::parse(by.as_deref().unwrap_or("fg")) {
+            Ok(by) => by,
+            Err(e) => return Err(CustomError::StatsError(e.to_string()).into_field_error()),
+        };
+        let percentiles = percentiles.unwrap_or_else(|| DEFAULT_PERCENTILES.to_vec());
+        for p in &percentiles {
+            if let Err(e) = stats::check_percentile(*p) {
+                return Err(CustomError::StatsError(e.to_string()).into_field_error());
+            }
+        }
+        let filter = match filters {
+            Some(f) => f.food_filter(context)?,
+            None => FoodFilter::new(),
+        };
+        let stats = context
+            .db
+            .nutrient_stats(&filter, &nutrient, by, &percentiles, max as i64)?;
+        Ok(stats.into_iter().map(NutrientStatsview::create).collect())
+    }
+    // a histogram of a nutrient's values, in a number of bins spanning them or
+    // between edges
+    fn histogram(
+        context: &Context,
+        nutrient: String,
+        bins: Option<i32>,
+        edges: Option<Vec<f64>>,
+        log: Option<bool>,
+        portion: Option<bool>,
+        filters: Option<Browsefilters>,
+    ) -> FieldResult<Histogramview> {
+        if !nutrient_exists(&nutrient, context) {
+            return Err(CustomError::NutrientNotFoundError(nutrient).into_field_error());
+        }
+        let bins = match (edges, bins) {
+            (Some(_), Some(_)) => Err("Use either bins or edges, not both".into()),
+            (Some(edges), None) => Bins::edges(edges),
+            (None, n) => Bins::count(n.map(|n| n as i64).unwrap_or(DEFAULT_BINS as i64)),
+        };
+        let bins = match bins {
+            Ok(b) => b,
+            Err(e) => return Err(CustomError::HistogramError(e.to_string()).into_field_error()),
+        };
+        let basis = match portion.unwrap_or(false) {
+            true => Basis::Portion,
+            false => Basis::Unit,
+        };
+        let filter = match filters {
+            Some(f) => f.food_filter(context)?,
+            None => FoodFilter::new(),
+        };
+        let h = context
+            .db
+            .histogram(&filter, &nutrient, basis, &bins, log.unwrap_or(false))?;
+        Ok(Histogramview::create(h))
+    }
+    // type-ahead completions of food descriptions, brand owners or food groups
+    fn suggest(
+        context: &Context,
+        prefix: String,
+        kind: String,
+        max: Option<i32>,
+    ) -> FieldResult<Vec<String>> {
+        let max = max.unwrap_or(DEFAULT_SUGGESTIONS);
+        if !(1..=MAX_SUGGESTIONS).contains(&max) {
+            return Err(CustomError::MaxValidationError.into_field_error());
+        }
+        let kind = match Kind::parse(&kind) {
+            Ok(k) => k,
+            Err(_e) => return Err(CustomError::KindError.into_field_error()),
+        };
+        if prefix.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(context.db.suggest(prefix.trim(), kind, max as i64)?)
+    }
 }
 pub struct MutationRoot;
 
@@ -349,12 +547,20 @@ pub struct Browsequery {
     pub max: Option<i32>,
     #[graphql(description = "Return records starting at an offset into the result set.  Optional.  Defaults to 0")]
     pub offset: Option<i32>,
-    #[graphql(description = "Optional Sort by, one of: database id (default),description, upc or fdcId")]
+    #[graphql(
+        description = "Optional Sort by, one of: database id (default),description, upc, fdcId, relevance (the default when there is a query) or nutrient:nbr for a nutrient's value per 100g, nutrient:nbr:portion for its value per portion.  Foods without the nutrient come last"
+    )]
     pub sort: Option<String>,
-    #[graphql(description = "Optional Sort order, one of: asc (default) or desc")]
+    #[graphql(
+        description = "Optional Sort order, one of: asc (default) or desc.  Relevance defaults to desc, most relevant first"
+    )]
     pub order: Option<String>,
     #[graphql(description = "Optional filters to apply to the data")]
     pub filters: Option<Browsefilters>,
+    #[graphql(
+        description = "Optional nextCursor or prevCursor returned with an earlier page of the same browse.  Can't be combined with offset"
+    )]
+    pub cursor: Option<String>,
 }
 #[derive(juniper::GraphQLInputObject, Debug)]
 pub struct Browsefilters {
@@ -370,26 +576,123 @@ pub struct Browsefilters {
         description = "Return records from specified brand owner"
     )]
     pub owners: Option<String>,
+    #[graphql(
+        name = "brand",
+        description = "Return records sold under the specified brand name"
+    )]
+    pub brand: Option<String>,
+    #[graphql(
+        name = "subbrand",
+        description = "Return records sold under the specified sub-brand"
+    )]
+    pub subbrand: Option<String>,
     #[graphql(
         name = "query",
-        description = "Filter on terms which appear in the food description and/or ingredients"
+        description = "Filter on terms which appear in the food description and/or ingredients.  Use \"quotes\" for phrases, -term to exclude, OR for either term and description: or ingredients: to search just one field"
     )]
     pub query: Option<String>,
     #[graphql(
-        name="country",
-        description= = "Filter on country"
+        name = "fuzzy",
+        description = "Also match food descriptions with misspellings of the query terms"
     )]
+    pub fuzzy: Option<bool>,
+    #[graphql(name = "country", description = "Filter on country")]
     pub country: Option<String>,
-    
+    #[graphql(
+        name = "datasource",
+        description = "Return records from specified data provider, GDSN or LI"
+    )]
+    pub datasource: Option<String>,
+    #[graphql(
+        name = "servingUnit",
+        description = "Return records with specified serving unit, e.g. g or ml"
+    )]
+    pub serving_unit: Option<String>,
+    #[graphql(
+        name = "nutrients",
+        description = "Return records whose nutrient values fall in all of the ranges"
+    )]
+    pub nutrients: Option<Vec<Nutrientrange>>,
 }
 impl Browsefilters {
-    fn new() -> Self {
-        Self {
-            publication_date: None,
-            food_group: None,
-            query: None,
-            owners: None,
-            country: None,
+    /// maps the filters onto a FoodFilter, looking up the brand owner and
+    /// food group ids
+    fn food_filter(self, context: &Context) -> FieldResult<FoodFilter> {
+        let mut filter = FoodFilter::new();
+        if let Some(owner) = self.owners.filter(|o| !o.is_empty()) {
+            let mut fm = Brand::new();
+            fm.owner = owner;
+            match fm.find_similar_owner(&*context.db) {
+                Ok(data) => filter.owner = Some(data.owner),
+                Err(e) => {
+                    return Err(CustomError::ManuNotFoundError(suggestions(&*e)).into_field_error())
+                }
+            };
+        }
+        if let Some(group) = self.food_group.filter(|g| !g.is_empty()) {
+            let mut fgg = Foodgroup::new();
+            fgg.description = group;
+            match fgg.find_similar_description(&*context.db) {
+                Ok(data) => filter.food_group_id = Some(data.id),
+                Err(e) => {
+                    return Err(
+                        CustomError::FoodGroupNotFoundError(suggestions(&*e)).into_field_error(),
+                    )
+                }
+            };
+        }
+        if let Some(pubdate) = self.publication_date.filter(|p| !p.is_empty()) {
+            filter.publication_date = match DateRange::parse(&pubdate) {
+                Ok(r) => Some(r),
+                Err(_e) => return Err(CustomError::DateRangeError.into_field_error()),
+            };
+        }
+        if let Some(q) = self.query {
+            if let Err(e) = filter.search(&q) {
+                return Err(CustomError::SearchError(e.to_string()).into_field_error());
+            }
+        }
+        for r in self.nutrients.unwrap_or_default() {
+            filter.nutrients.push(r.nutrient_range(context)?);
+        }
+        filter.fuzzy = self.fuzzy.unwrap_or(false);
+        filter.country = self.country.filter(|c| !c.is_empty());
+        filter.datasource = self.datasource.filter(|d| !d.is_empty());
+        filter.serving_unit = self.serving_unit.filter(|u| !u.is_empty());
+        filter.brand = self.brand.filter(|b| !b.is_empty());
+        filter.subbrand = self.subbrand.filter(|b| !b.is_empty());
+        Ok(filter)
+    }
+}
+#[derive(juniper::GraphQLInputObject, Debug)]
+#[graphql(
+    name = "NutrientRange",
+    description = "Constrains a nutrient's value to a range.  At least one of min and max is required"
+)]
+pub struct Nutrientrange {
+    #[graphql(description = "A unique code identifying a nutrient, e.g. 203 for protein")]
+    pub nbr: String,
+    #[graphql(description = "Optional least value, inclusive")]
+    pub min: Option<f64>,
+    #[graphql(description = "Optional greatest value, inclusive")]
+    pub max: Option<f64>,
+    #[graphql(description = "Compare the value per portion rather than per 100g.  Optional.  Defaults to false")]
+    pub portion: Option<bool>,
+}
+impl Nutrientrange {
+    /// checks the range and that its nutrient exists
+    fn nutrient_range(self, context: &Context) -> FieldResult<NutrientRange> {
+        let basis = match self.portion.unwrap_or(false) {
+            true => Basis::Portion,
+            false => Basis::Unit,
+        };
+        let r = match NutrientRange::new(&self.nbr, basis, self.min, self.max) {
+            Ok(r) => r,
+            Err(e) => return Err(CustomError::NutrientRangeError(e.to_string()).into_field_error()),
+        };
+        match nutrient_exists(&r.nutrientno, context) {
+            true => Ok(r),
+            false => Err(CustomError::NutrientNotFoundError(r.nutrientno).into_field_error()),
         }
     }
 }
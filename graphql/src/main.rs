@@ -4,15 +4,28 @@ use actix_web::{get, post, web, App, Error, HttpResponse, HttpServer};
 use dotenv::dotenv;
 use juniper::http::graphiql::graphiql_source;
 use juniper::http::GraphQLRequest;
+use search_index::store::IndexedStore;
 use std::env;
+use std::path::Path;
 use std::sync::Arc;
 mod graphql_schema;
 mod views;
 use crate::graphql_schema::{create_schema, Context, Schema};
-#[cfg(feature = "maria")]
-use mariadb::db::connect;
-#[cfg(feature = "postgres")]
-use pg::db::connect;
+use bfpd::{Backend, Store};
+/// connects to the backend named by the DATABASE_URL scheme.  Searches use
+/// the index in the SEARCH_INDEX directory if one is set.
+fn connect() -> Arc<dyn Store> {
+    let url = env::var("DATABASE_URL").expect("Bad url");
+    let db: Arc<dyn Store> = match Backend::from_url(&url).expect("Error") {
+        Backend::Postgres => Arc::new(pg::db::connect(&url)),
+        Backend::Mysql => Arc::new(mariadb::db::connect(&url)),
+        Backend::Sqlite => Arc::new(sqlite::db::connect(&url)),
+    };
+    match env::var("SEARCH_INDEX") {
+        Ok(path) => Arc::new(IndexedStore::open(db, Path::new(&path)).expect("Error opening index")),
+        Err(_e) => db,
+    }
+}
 #[get("/graphiql")]
 fn graphiql() -> HttpResponse {
     let url = match env::var("GRAPHIQL_URL") {
@@ -33,7 +46,7 @@ async fn graphql(
 ) -> Result<HttpResponse, Error> {
     let res = web::block(move || {
         let res = data.execute(&st, &ctx);
-        Ok::<_, serde_json::error::Error>(serde_json::to_string(&res)?)
+        serde_json::to_string(&res)
     })
     .await
     .map_err(Error::from)?;
@@ -44,8 +57,8 @@ async fn graphql(
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    let pool = connect();
-    let schema_context = Context { db: pool.clone() };
+    let db = connect();
+    let schema_context = Context { db };
     let schema = std::sync::Arc::new(create_schema());
     HttpServer::new(move || {
         App::new()
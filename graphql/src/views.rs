@@ -1,8 +1,10 @@
 use crate::graphql_schema::Context;
-#[cfg(feature = "maria")]
-use mariadb::models::*;
-#[cfg(feature = "postgres")]
-use pg::models::*;
+use bfpd::facet::{FacetCount, Facets};
+use bfpd::histogram::Histogram;
+use bfpd::loader::FoodDetails;
+use bfpd::models::*;
+use bfpd::stats::NutrientStats;
+use std::error::Error;
 #[derive(juniper::GraphQLObject, Debug)]
 #[graphql(description = "Defines a branded food product")]
 pub struct Foodview {
@@ -28,6 +30,10 @@ pub struct Foodview {
     pub food_group: String,
     #[graphql(description = "Brand owner for the food")]
     pub owner: String,
+    #[graphql(description = "Brand name the food is sold under")]
+    pub brand: Option<String>,
+    #[graphql(description = "Sub-brand the food is sold under")]
+    pub subbrand: Option<String>,
     #[graphql(description = "Provider of food data -- GDSN or LI")]
     pub datasource: String,
     #[graphql(
@@ -42,26 +48,53 @@ pub struct Foodview {
     pub country: Option<String>,
     #[graphql(description = "The list of ingredients (as it appears on the product label)")]
     pub ingredients: Option<String>,
+    #[graphql(description = "The date the product was discontinued, if it has been")]
+    pub discontinued_date: Option<String>,
+    #[graphql(description = "The weight of the package as labelled")]
+    pub package_weight: Option<String>,
+    #[graphql(description = "The state the food is in as served, e.g. UNPREPARED or PREPARED")]
+    pub preparation_state_code: Option<String>,
+    #[graphql(description = "The channels the product is sold through, e.g. GROCERY or FOOD_SERVICE")]
+    pub trade_channel: Option<String>,
+    #[graphql(description = "A short description of the food")]
+    pub short_description: Option<String>,
+    #[graphql(description = "The FoodData Central release the food was last loaded from")]
+    pub fdc_release: Option<String>,
     #[graphql(description = "nutrient data for a food")]
     pub nutrient_data: Vec<Nutrientdataview>,
+    #[graphql(
+        description = "The description or ingredients with the search terms in bold.  Only set when searching a search index"
+    )]
+    pub snippet: Option<String>,
+}
+#[derive(juniper::GraphQLObject, Debug)]
+#[graphql(description = "A page of foods with cursors for the pages either side of it")]
+pub struct Foodpage {
+    pub foods: Vec<Foodview>,
+    #[graphql(description = "Pass as the cursor to get the next page.  Null on the last page")]
+    pub next_cursor: Option<String>,
+    #[graphql(
+        description = "Pass as the cursor to get the previous page.  Null on the first page"
+    )]
+    pub prev_cursor: Option<String>,
 }
 impl Foodview {
-    pub fn build_view(fd: Vec<Food>, nids: &Vec<String>, context: &Context) -> Vec<Foodview> {
-        let conn = context.db.get().unwrap();
+    /// Builds the views for a page of foods.  The page's nutrient data, brand
+    /// owners and food groups are loaded together rather than food by food.
+    pub fn build_view(
+        fd: Vec<Food>,
+        nids: &[String],
+        context: &Context,
+    ) -> Result<Vec<Foodview>, Box<dyn Error + Send + Sync>> {
+        let details = context.db.food_details(&fd, nids)?;
         let mut fv: Vec<Foodview> = Vec::new();
-        for i in &fd {
-            let f = &i;
-            let mut fdv = Foodview::create(&f, &context);
-            let nutform: Vec<NutrientdataForm> = f
-                .get_nutrient_data(nids, &conn)
-                .expect("error loading nutrient data");
-
+        for f in &fd {
+            let mut fdv = Foodview::create(f, &details);
             let mut ndv: Vec<Nutrientdataview> = Vec::new();
-            for j in &nutform {
-                let nf = &j;
-                let mut nv = Nutrientdataview::create(&nf);
+            for nf in details.nutrient_data(f) {
+                let mut nv = Nutrientdataview::create(nf);
                 nv.portion_value = match fdv.serving_size {
-                    Some(x) => (x as f64 / 100.0) * nv.value,
+                    Some(x) => (x / 100.0) * nv.value,
                     None => 0.0,
                 };
                 ndv.push(nv);
@@ -69,11 +102,10 @@ impl Foodview {
             fdv.nutrient_data = ndv;
             fv.push(fdv);
         }
-        fv
+        Ok(fv)
     }
     /// creates a new food view from a food
-    pub fn create(f: &Food, context: &Context) -> Self {
-        let conn = context.db.get().unwrap();
+    pub fn create(f: &Food, details: &FoodDetails) -> Self {
         Self {
             publication_date: f.publication_date.format("%Y-%m-%d").to_string(),
             modified_date: f.modified_date.format("%Y-%m-%d").to_string(),
@@ -81,8 +113,10 @@ impl Foodview {
             upc: f.upc.to_string(),
             fdc_id: f.fdc_id.to_string(),
             description: f.description.to_string(),
-            food_group: f.get_food_group_name(&conn).unwrap(),
-            owner: f.get_owner_name(&conn).unwrap(),
+            food_group: details.food_group(f),
+            owner: details.owner(f),
+            brand: details.brand(f),
+            subbrand: details.subbrand(f),
             datasource: f.datasource.to_string(),
             serving_description: Some(
                 f.serving_description
@@ -109,7 +143,16 @@ impl Foodview {
                     .map(|n| n.to_string())
                     .unwrap_or("unknown".to_string()),
             ),
+            discontinued_date: f
+                .discontinued_date
+                .map(|d| d.format("%Y-%m-%d").to_string()),
+            package_weight: f.package_weight.clone(),
+            preparation_state_code: f.preparation_state_code.clone(),
+            trade_channel: f.trade_channel.clone(),
+            short_description: f.short_description.clone(),
+            fdc_release: f.fdc_release.clone(),
             nutrient_data: Vec::new(),
+            snippet: None,
         }
     }
 }
@@ -125,6 +168,18 @@ pub struct Nutrientdataview {
         description = "Amount of the nutrient per portion size of food. Specified in unit defined in the nutrient table."
     )]
     pub portion_value: f64,
+    #[graphql(description = "Least of the values the amount was derived from")]
+    pub minimum: Option<f64>,
+    #[graphql(description = "Greatest of the values the amount was derived from")]
+    pub maximum: Option<f64>,
+    #[graphql(description = "Median of the values the amount was derived from")]
+    pub median: Option<f64>,
+    #[graphql(description = "Number of observations the amount is based on")]
+    pub data_points: Option<i32>,
+    #[graphql(description = "Comments on the amount")]
+    pub footnote: Option<String>,
+    #[graphql(description = "Earliest year the amount's data were collected")]
+    pub min_year: Option<i32>,
     #[graphql(description = "Description of the derivation")]
     pub derivation: String,
     #[graphql(description = "Code used for the derivation (e.g. A means analytical)")]
@@ -142,6 +197,12 @@ impl Nutrientdataview {
         Self {
             value: n.value,
             portion_value: 0.0,
+            minimum: n.minimum,
+            maximum: n.maximum,
+            median: n.median,
+            data_points: n.data_points,
+            footnote: n.footnote.clone(),
+            min_year: n.min_year,
             nutrient_no: n.nutrient_no.to_string(),
             nutrient: n.nutrient.to_string(),
             unit: n.unit.to_string(),
@@ -151,12 +212,6 @@ impl Nutrientdataview {
     }
 }
 
-#[derive(juniper::GraphQLObject, Debug)]
-#[graphql(description = "How a nutrient value is dervied for a food")]
-pub struct Derivationview {
-    code: String,
-    description: String,
-}
 #[derive(juniper::GraphQLObject, Debug)]
 #[graphql(description = "The category assigned to a food")]
 pub struct FoodgroupView {
@@ -231,3 +286,168 @@ impl Nutrientview {
 pub struct Querycount {
     pub count: i32,
 }
+#[derive(juniper::GraphQLObject, Debug)]
+#[graphql(description = "The number of foods with a value")]
+pub struct Facetview {
+    pub value: String,
+    pub count: i32,
+}
+#[derive(juniper::GraphQLObject, Debug)]
+#[graphql(description = "Counts of the foods returned by a query in each food group, owner, country and datasource, largest first")]
+pub struct Facetsview {
+    pub food_groups: Vec<Facetview>,
+    pub owners: Vec<Facetview>,
+    pub countries: Vec<Facetview>,
+    pub datasources: Vec<Facetview>,
+}
+impl Facetsview {
+    pub fn create(f: Facets) -> Self {
+        let view = |counts: Option<Vec<FacetCount>>| -> Vec<Facetview> {
+            counts
+                .unwrap_or_default()
+                .into_iter()
+                .map(|c| Facetview {
+                    value: c.value,
+                    count: c.count as i32,
+                })
+                .collect()
+        };
+        Self {
+            food_groups: view(f.food_groups),
+            owners: view(f.owners),
+            countries: view(f.countries),
+            datasources: view(f.datasources),
+        }
+    }
+}
+#[derive(juniper::GraphQLObject, Debug)]
+#[graphql(description = "A food and its values of the nutrients a report constrains")]
+pub struct NutrientsReportview {
+    #[graphql(description = "FDC id of the food")]
+    pub fdc_id: String,
+    #[graphql(description = "Food name")]
+    pub description: String,
+    #[graphql(description = "Food UPC")]
+    pub upc: String,
+    #[graphql(description = "Serving size in the serving unit")]
+    pub serving_size: Option<f64>,
+    #[graphql(description = "Serving unit, e.g. g or ml")]
+    pub serving_unit: Option<String>,
+    #[graphql(description = "Household description of a serving")]
+    pub serving_description: Option<String>,
+    #[graphql(description = "Values of each nutrient in the report")]
+    pub nutrients: Vec<ReportValueview>,
+}
+#[derive(juniper::GraphQLObject, Debug)]
+#[graphql(description = "A food's value of a nutrient per 100g and per portion")]
+pub struct ReportValueview {
+    #[graphql(description = "A unique code identifying a nutrient or food constituent")]
+    pub nutrient_no: String,
+    #[graphql(description = "Name of the nutrient")]
+    pub nutrient: String,
+    #[graphql(description = "The standard unit of measure for the nutrient")]
+    pub unit: String,
+    #[graphql(description = "Amount of the nutrient per 100g of food")]
+    pub value: f64,
+    #[graphql(description = "Amount of the nutrient per portion of food")]
+    pub portion_value: f64,
+}
+impl NutrientsReportview {
+    pub fn create(r: NutrientsReportForm) -> Self {
+        Self {
+            fdc_id: r.fdc_id,
+            description: r.description,
+            upc: r.upc,
+            serving_size: r.serving_size,
+            serving_unit: r.serving_unit,
+            serving_description: r.serving_description,
+            nutrients: r
+                .nutrients
+                .into_iter()
+                .map(|v| ReportValueview {
+                    nutrient_no: v.nutrient_no,
+                    nutrient: v.nutrient,
+                    unit: v.unit,
+                    value: v.unit_value,
+                    portion_value: v.portion_value,
+                })
+                .collect(),
+        }
+    }
+}
+#[derive(juniper::GraphQLObject, Debug)]
+#[graphql(description = "The distribution of a nutrient's per 100g values in a food group, brand owner or country")]
+pub struct NutrientStatsview {
+    #[graphql(description = "Food group, brand owner or country, unknown if not known")]
+    pub group: String,
+    #[graphql(description = "Number of foods with the nutrient")]
+    pub count: i32,
+    pub mean: f64,
+    pub median: f64,
+    #[graphql(description = "Sample standard deviation, 0 for a single food")]
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    #[graphql(description = "Values at each percentile asked for")]
+    pub percentiles: Vec<Percentileview>,
+}
+#[derive(juniper::GraphQLObject, Debug)]
+#[graphql(description = "The value at a percentile")]
+pub struct Percentileview {
+    pub percentile: f64,
+    pub value: f64,
+}
+impl NutrientStatsview {
+    pub fn create(s: NutrientStats) -> Self {
+        Self {
+            group: s.group,
+            count: s.count as i32,
+            mean: s.mean,
+            median: s.median,
+            stddev: s.stddev,
+            min: s.min,
+            max: s.max,
+            percentiles: s
+                .percentiles
+                .into_iter()
+                .map(|p| Percentileview {
+                    percentile: p.percentile,
+                    value: p.value,
+                })
+                .collect(),
+        }
+    }
+}
+#[derive(juniper::GraphQLObject, Debug)]
+#[graphql(description = "A histogram of a nutrient's values")]
+pub struct Histogramview {
+    pub bins: Vec<Binview>,
+    #[graphql(description = "Values below the first edge given")]
+    pub below: i32,
+    #[graphql(description = "Values above the last edge given")]
+    pub above: i32,
+}
+#[derive(juniper::GraphQLObject, Debug)]
+#[graphql(description = "The number of values from lower up to upper, the last bin including upper")]
+pub struct Binview {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: i32,
+}
+impl Histogramview {
+    pub fn create(h: Histogram) -> Self {
+        Self {
+            bins: h
+                .bins
+                .into_iter()
+                .map(|b| Binview {
+                    lower: b.lower,
+                    upper: b.upper,
+                    count: b.count as i32,
+                })
+                .collect(),
+            below: h.below as i32,
+            above: h.above as i32,
+        }
+    }
+}
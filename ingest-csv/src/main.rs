@@ -2,19 +2,21 @@ extern crate serde;
 #[macro_use]
 extern crate clap;
 use clap::App;
-#[cfg(feature = "maria")]
-use mariadb::csv::{process_derivations, process_foods, process_nutdata, process_nutrients};
-#[cfg(feature = "maria")]
-use mariadb::db::connect;
-#[cfg(feature = "postgres")]
-use pg::csv::{process_derivations, process_foods, process_nutdata, process_nutrients};
-#[cfg(feature = "postgres")]
-use pg::db::connect;
+use bfpd::csv::{
+    process_derivations, process_foods, process_nutdata, process_nutrients, update_foods,
+    update_nutdata, Ingest,
+};
+use bfpd::reject::Rejects;
+use bfpd::Backend;
+use search_index::index::FoodIndex;
 
 extern crate dotenv;
 use dotenv::dotenv;
+use std::env;
 use std::error::Error;
 use std::fmt;
+use std::fs;
+use std::path::Path;
 use std::process;
 #[derive(Debug)]
 struct ArgError {
@@ -38,6 +40,55 @@ impl Error for ArgError {
         &self.msg
     }
 }
+/// connects to the backend named by the DATABASE_URL scheme
+fn connect() -> Result<Box<dyn Ingest>, Box<dyn Error>> {
+    let url = env::var("DATABASE_URL")?;
+    let db: Box<dyn Ingest> = match Backend::from_url(&url).map_err(|e| e.to_string())? {
+        Backend::Postgres => Box::new(pg::db::connect(&url)),
+        Backend::Mysql => Box::new(mariadb::db::connect(&url)),
+        Backend::Sqlite => Box::new(sqlite::db::connect(&url)),
+    };
+    Ok(db)
+}
+/// names a release after the directory holding its csv files
+fn release_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+/// updates the foods and nutrient data loaded from an earlier release
+fn update_release(
+    path: &str,
+    release: &str,
+    db: &dyn Ingest,
+    rejects: &mut Rejects,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    println!("Updating foods from release {}", release);
+    let report = update_foods(path.to_string(), release, db, rejects)?;
+    println!("Finished.  {}", report);
+    println!("Now replacing nutrient data.");
+    let count = update_nutdata(path.to_string(), release, db, rejects)?;
+    println!("Finished nutrient data.");
+    Ok(report.added + report.changed + count)
+}
+/// writes the run's summary as JSON to a file if one's given, otherwise to stdout
+fn write_summary(rejects: &Rejects, summary: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_string_pretty(&rejects.summary())?;
+    match summary {
+        Some(file) => fs::write(file, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+/// reports an error and the summary of the rows loaded before it, then exits
+fn abort(e: &dyn fmt::Display, rejects: &Rejects, summary: Option<&str>) -> ! {
+    eprintln!("{}", e);
+    if let Err(e) = write_summary(rejects, summary) {
+        eprintln!("{}", e);
+    }
+    process::exit(1);
+}
 /// imports USDA csv files into the database
 fn run() -> Result<usize, Box<dyn Error>> {
     dotenv().ok();
@@ -46,104 +97,125 @@ fn run() -> Result<usize, Box<dyn Error>> {
     let matches = App::from_yaml(cli).get_matches();
     let mut csvtype = matches.value_of("type").unwrap_or_default();
     let path = matches.value_of("path").unwrap();
-    if csvtype.len() == 0 {
+    if csvtype.is_empty() {
         csvtype = "ALL"
     }
-    let pool = connect();
-    let conn = pool.get().unwrap();
+    let update = matches.is_present("update");
+    let resume = matches.is_present("resume");
+    let release = match matches.value_of("release") {
+        Some(r) => r.to_string(),
+        None => release_name(path),
+    };
+    let max_rejects = match matches.value_of("max-rejects") {
+        Some(n) => Some(
+            n.parse::<usize>()
+                .map_err(|_| ArgError::new("max-rejects must be a number"))?,
+        ),
+        None => None,
+    };
+    let mut rejects = Rejects::new(
+        Path::new(matches.value_of("rejects").unwrap_or("rejects.csv")),
+        max_rejects,
+    );
+    let summary = matches.value_of("summary");
+    let db = connect()?;
     let mut err = false;
     let mut count: usize = 0;
     match csvtype {
+        "FOOD" if update => {
+            count = match update_release(path, &release, &*db, &mut rejects) {
+                Ok(c) => c,
+                Err(e) => abort(&e, &rejects, summary),
+            };
+        }
         "FOOD" => {
-           /* println!("Loading foods");
-            count = match process_foods(path.to_string(), &conn) {
+            println!("Loading foods");
+            count = match process_foods(path.to_string(), &release, resume, &*db, &mut rejects) {
                 Ok(c) => c,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    process::exit(1);
-                }
+                Err(e) => abort(&e, &rejects, summary),
             };
-            println!("Finished foods.");*/
+            println!("Finished foods.");
             println!("Now loading nutrient data.");
-            count += match process_nutdata(path.to_string(), &conn) {
+            count += match process_nutdata(path.to_string(), &release, resume, &*db, &mut rejects) {
                 Ok(c) => c,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    process::exit(1);
-                }
+                Err(e) => abort(&e, &rejects, summary),
             };
             println!("Finished nutrient data.")
         }
         "NUT" => {
-            count = match process_nutrients(path.to_string(), &conn) {
+            count = match process_nutrients(path.to_string(), &*db, &mut rejects) {
                 Ok(c) => c,
-                Err(e) => {
-                    eprintln!("{} {}", path.to_string(), e);
-                    process::exit(1);
-                }
+                Err(e) => abort(&format!("{} {}", path, e), &rejects, summary),
             };
             println!("Finished nutrients");
         }
         "DERV" => {
-            count = match process_derivations(path.to_string(), &conn) {
+            count = match process_derivations(path.to_string(), &*db, &mut rejects) {
                 Ok(c) => c,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    process::exit(1);
-                }
+                Err(e) => abort(&e, &rejects, summary),
             };
             println!("Finished derivations");
         }
         "ALL" => {
             println!("Starting csv load");
-            count = match process_nutrients(path.to_string(), &conn) {
+            count = match process_nutrients(path.to_string(), &*db, &mut rejects) {
                 Ok(c) => c,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    process::exit(1);
-                }
+                Err(e) => abort(&e, &rejects, summary),
             };
             println!("Finished.  {} nutrients loaded", count);
-            count += match process_derivations(path.to_string(), &conn) {
+            count += match process_derivations(path.to_string(), &*db, &mut rejects) {
                 Ok(c) => c,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    process::exit(1);
-                }
+                Err(e) => abort(&e, &rejects, summary),
             };
             println!("Finished derivations");
-            println!("Loading foods");
-            count += match process_foods(path.to_string(), &conn) {
-                Ok(c) => c,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    process::exit(1);
-                }
-            };
-            println!("Finished foods.");
-            println!("Now loading nutrient data.");
+            if update {
+                count += match update_release(path, &release, &*db, &mut rejects) {
+                    Ok(c) => c,
+                    Err(e) => abort(&e, &rejects, summary),
+                };
+            } else {
+                println!("Loading foods");
+                count += match process_foods(
+                    path.to_string(),
+                    &release,
+                    resume,
+                    &*db,
+                    &mut rejects,
+                ) {
+                    Ok(c) => c,
+                    Err(e) => abort(&e, &rejects, summary),
+                };
+                println!("Finished foods.");
+                println!("Now loading nutrient data.");
 
-            count += match process_nutdata(path.to_string(), &conn) {
-                Ok(c) => c,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    process::exit(1);
-                }
-            };
-            println!("Finished nutrient data.")
+                count += match process_nutdata(path.to_string(), &release, resume, &*db, &mut rejects) {
+                    Ok(c) => c,
+                    Err(e) => abort(&e, &rejects, summary),
+                };
+                println!("Finished nutrient data.")
+            }
         }
         _ => {
             err = true;
         }
     }
     if err {
-        Err(Box::new(ArgError::new("invalid input type")))
-    } else {
-        Ok(count)
+        return Err(Box::new(ArgError::new("invalid input type")));
+    }
+    write_summary(&rejects, summary)?;
+    // keep the search index in step with the foods
+    if csvtype == "FOOD" || csvtype == "ALL" {
+        if let Ok(index) = env::var("SEARCH_INDEX") {
+            println!("Rebuilding the search index");
+            let indexed = FoodIndex::open(Path::new(&index))
+                .and_then(|i| i.rebuild(&*db))
+                .map_err(|e| e.to_string())?;
+            println!("Finished.  {} foods indexed", indexed);
+        }
     }
+    Ok(count)
 }
 //#[derive(Debug, Serialize, Deserialize)]
-///
 fn main() {
     match run() {
         Ok(count) => {
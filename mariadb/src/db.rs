@@ -1,17 +1,26 @@
 extern crate diesel;
-use std::env;
 
+use bfpd::adapter::Deferred;
 use diesel::mysql::MysqlConnection;
 use diesel::r2d2::{ConnectionManager, Pool, PoolError};
 
 pub type MysqlPool = Pool<ConnectionManager<MysqlConnection>>;
 
+/// MysqlStore runs the bfpd queries against a MariaDB connection pool
+#[derive(Clone)]
+pub struct MysqlStore {
+    pub pool: MysqlPool,
+    pub deferred: Deferred,
+}
+
 fn init(database_url: &str) -> Result<MysqlPool, PoolError> {
     let manager = ConnectionManager::<MysqlConnection>::new(database_url);
     Pool::builder().build(manager)
 }
 
-pub fn connect() -> MysqlPool {
-    let database_url = env::var("DATABASE_URL").expect("Bad url");
-    init(&database_url).expect("Error")
+pub fn connect(database_url: &str) -> MysqlStore {
+    MysqlStore {
+        pool: init(database_url).expect("Error"),
+        deferred: Deferred::default(),
+    }
 }
@@ -0,0 +1,221 @@
+//! Bulk loading with LOAD DATA LOCAL INFILE.  The rows are written to a
+//! temporary csv file and loaded BATCH_SIZE at a time, each chunk in one
+//! transaction with the load's checkpoint so an interrupted load can be resumed.
+//! The brands and food groups new to a chunk of foods are inserted in its
+//! transaction too.  The table's secondary indexes but its unique ones are
+//! dropped before the first chunk, and the foreign key checks turned off, and the
+//! indexes are added back once the rows are in.  Their definitions are kept in
+//! ingest_indexes meanwhile, so a resumed load adds back those an interrupted one
+//! dropped.  The unique indexes stay so a chunk with a duplicate key fails rather
+//! than the index when it's added back.  local_infile must be enabled on the
+//! server and in the client library.
+use bfpd::adapter::Deferred;
+use bfpd::bulk;
+use bfpd::csv::{insert_batches, Progress, Rows};
+use bfpd::models::Checkpoint;
+use bfpd::schema::{brands, food_groups, ingest_checkpoints};
+use diesel::connection::SimpleConnection;
+use diesel::dsl::sql;
+use diesel::mysql::MysqlConnection;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Nullable, Text};
+use std::error::Error;
+use std::io::BufWriter;
+
+/// A secondary index as it's dropped and added back
+struct Index {
+    name: String,
+    kind: String,
+    unique: bool,
+    /// the columns with their prefix lengths, if any
+    columns: Vec<(String, Option<i64>)>,
+}
+impl Index {
+    fn definition(&self) -> String {
+        let kind = match (self.kind.as_str(), self.unique) {
+            ("FULLTEXT", _) => "FULLTEXT INDEX",
+            (_, true) => "UNIQUE INDEX",
+            _ => "INDEX",
+        };
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .map(|(c, part)| match part {
+                Some(n) => format!("`{}`({})", c, n),
+                None => format!("`{}`", c),
+            })
+            .collect();
+        format!("ADD {} `{}` ({})", kind, self.name, columns.join(", "))
+    }
+}
+/// Turns a connection's foreign key checks off until it's dropped, so they're
+/// back on even if a load fails or panics and its pooled connection is reused
+struct ForeignKeyChecksOff<'a>(&'a MysqlConnection);
+impl<'a> ForeignKeyChecksOff<'a> {
+    fn new(conn: &'a MysqlConnection) -> QueryResult<Self> {
+        conn.batch_execute("SET foreign_key_checks = 0")?;
+        Ok(ForeignKeyChecksOff(conn))
+    }
+}
+impl Drop for ForeignKeyChecksOff<'_> {
+    fn drop(&mut self) {
+        // it only fails if the connection is, and then it isn't reused
+        let _ = self.0.batch_execute("SET foreign_key_checks = 1");
+    }
+}
+/// Loads rows into the columns of a table, each chunk with the brands and food
+/// groups deferred while it's read, if any, and returns the number loaded.  The
+/// indexes are added back whether or not the load finishes.
+pub fn load_data<T>(
+    conn: &MysqlConnection,
+    table: &str,
+    columns: &[&str],
+    rows: Rows<T>,
+    record: fn(&T) -> Vec<Option<String>>,
+    at: Progress,
+    deferred: Option<&Deferred>,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let checks = ForeignKeyChecksOff::new(conn)?;
+    let mut dropped = false;
+    let count = insert_batches(rows, |chunk| {
+        if !dropped {
+            drop_indexes(conn, table)?;
+            dropped = true;
+        }
+        // a new file with a random name each chunk, so a file planted at the
+        // path can't be written through
+        let file = tempfile::Builder::new()
+            .prefix(&format!("bfpd-{}-", table))
+            .suffix(".csv")
+            .tempfile()?;
+        bulk::write_csv(
+            BufWriter::new(file.as_file()),
+            chunk,
+            record,
+            bulk::Format::LoadData,
+        )?;
+        // the path is quoted in the statement
+        let infile = file
+            .path()
+            .display()
+            .to_string()
+            .replace('\\', "\\\\")
+            .replace('\'', "\\'");
+        let (brands, food_groups) = deferred.map(Deferred::take).unwrap_or_default();
+        conn.transaction::<_, diesel::result::Error, _>(|| {
+            diesel::insert_into(brands::table)
+                .values(&brands)
+                .execute(conn)?;
+            diesel::insert_into(food_groups::table)
+                .values(&food_groups)
+                .execute(conn)?;
+            // LOAD DATA can't be a prepared statement so it's sent as text
+            conn.batch_execute(&format!(
+                "LOAD DATA LOCAL INFILE '{}' INTO TABLE {} CHARACTER SET utf8mb4 \
+                 FIELDS TERMINATED BY ',' OPTIONALLY ENCLOSED BY '\"' LINES TERMINATED BY '\\n' ({})",
+                infile,
+                table,
+                columns.join(", ")
+            ))?;
+            save_checkpoint(conn, &at())?;
+            Ok(())
+        })?;
+        Ok(chunk.len())
+    });
+    drop(checks);
+    let added = add_indexes(conn, table);
+    let count = count?;
+    added?;
+    Ok(count)
+}
+/// Returns a table's secondary indexes
+fn indexes(
+    conn: &MysqlConnection,
+    table: &str,
+) -> Result<Vec<Index>, Box<dyn Error + Send + Sync>> {
+    let columns: Vec<(String, String, Option<i64>, i64, String)> = sql::<(
+        Text,
+        Text,
+        Nullable<BigInt>,
+        BigInt,
+        Text,
+    )>(
+        "SELECT INDEX_NAME, COLUMN_NAME, CAST(SUB_PART AS SIGNED), CAST(NON_UNIQUE AS SIGNED), \
+         INDEX_TYPE FROM information_schema.STATISTICS WHERE TABLE_SCHEMA = DATABASE() \
+         AND TABLE_NAME = ",
+    )
+    .bind::<Text, _>(table)
+    .sql(" AND INDEX_NAME <> 'PRIMARY' ORDER BY INDEX_NAME, SEQ_IN_INDEX")
+    .load(conn)?;
+    let mut indexes: Vec<Index> = Vec::new();
+    for (name, column, part, non_unique, kind) in columns {
+        match indexes.last_mut() {
+            Some(i) if i.name == name => i.columns.push((column, part)),
+            _ => indexes.push(Index {
+                name,
+                kind,
+                unique: non_unique == 0,
+                columns: vec![(column, part)],
+            }),
+        }
+    }
+    Ok(indexes)
+}
+/// Drops a table's secondary indexes and saves their definitions in
+/// ingest_indexes.  Unique ones and those a foreign key needs are kept.
+fn drop_indexes(conn: &MysqlConnection, table: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let foreign_keys: Vec<String> = sql::<Text>(
+        "SELECT COLUMN_NAME FROM information_schema.KEY_COLUMN_USAGE \
+         WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ",
+    )
+    .bind::<Text, _>(table)
+    .sql(" AND REFERENCED_TABLE_NAME IS NOT NULL")
+    .load(conn)?;
+    let mut indexes = indexes(conn, table)?;
+    indexes.retain(|i| !i.unique && !foreign_keys.contains(&i.columns[0].0));
+    if indexes.is_empty() {
+        return Ok(());
+    }
+    // ALTER TABLE commits by itself so the definitions are saved first
+    for i in &indexes {
+        diesel::sql_query(
+            "REPLACE INTO ingest_indexes (tablename, indexname, definition) VALUES (?, ?, ?)",
+        )
+        .bind::<Text, _>(table)
+        .bind::<Text, _>(&i.name)
+        .bind::<Text, _>(i.definition())
+        .execute(conn)?;
+    }
+    let drops: Vec<String> = indexes
+        .iter()
+        .map(|i| format!("DROP INDEX `{}`", i.name))
+        .collect();
+    conn.batch_execute(&format!("ALTER TABLE {} {}", table, drops.join(", ")))?;
+    Ok(())
+}
+/// Adds back the indexes saved in ingest_indexes for a table but those it has
+fn add_indexes(conn: &MysqlConnection, table: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let saved: Vec<(String, String)> =
+        sql::<(Text, Text)>("SELECT indexname, definition FROM ingest_indexes WHERE tablename = ")
+            .bind::<Text, _>(table)
+            .load(conn)?;
+    let existing: Vec<String> = indexes(conn, table)?.into_iter().map(|i| i.name).collect();
+    let definitions: Vec<&str> = saved
+        .iter()
+        .filter(|(name, _)| !existing.contains(name))
+        .map(|(_, definition)| definition.as_str())
+        .collect();
+    if !definitions.is_empty() {
+        conn.batch_execute(&format!("ALTER TABLE {} {}", table, definitions.join(", ")))?;
+    }
+    diesel::sql_query("DELETE FROM ingest_indexes WHERE tablename = ?")
+        .bind::<Text, _>(table)
+        .execute(conn)?;
+    Ok(())
+}
+/// Saves a load's checkpoint, replacing the phase's last one
+fn save_checkpoint(conn: &MysqlConnection, at: &Checkpoint) -> QueryResult<usize> {
+    diesel::replace_into(ingest_checkpoints::table)
+        .values(at)
+        .execute(conn)
+}
@@ -0,0 +1,181 @@
+//! Runs the bfpd queries on MariaDB
+use crate::db::MysqlStore;
+use crate::load;
+use bfpd::adapter::{Deferred, Dialect};
+use bfpd::bulk;
+use bfpd::csv::{Progress, Rows};
+use bfpd::filter::FoodFilter;
+use bfpd::fuzzy;
+use bfpd::models::{Brand, Food, Foodgroup, Nutrientdata};
+use bfpd::query::{self, FullText, Rank, Search};
+use bfpd::search::{Field, Term};
+use diesel::dsl::sql;
+use diesel::mysql::{Mysql, MysqlConnection};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Double, Text};
+use std::error::Error;
+
+/// full-text search on the foods description/ingredients FULLTEXT indexes in
+/// boolean mode, ranked by the natural language MATCH score.  A fuzzy search
+/// also matches descriptions by their trigrams, of the foods with a word
+/// starting as one of the term's does.
+struct Fts;
+impl FullText<Mysql> for Fts {
+    fn search(&self, filter: &FoodFilter) -> Option<Search<'static, Mysql>> {
+        query::search_predicate(filter.query.as_ref()?, |t| {
+            let term = match t.words.len() {
+                1 => t.words[0].to_string(),
+                _ => format!("\"{}\"", t.words.join(" ")),
+            };
+            let fts: Search<'static, Mysql> = Box::new(
+                sql::<Bool>(&format!("MATCH({}) AGAINST(", columns(t.field)))
+                    .bind::<Text, _>(term)
+                    .sql(" IN BOOLEAN MODE)"),
+            );
+            match filter.fuzzy && !t.exclude && t.in_description() {
+                true => Box::new(fts.or(query::ngram_description(t, prefixes(t)))),
+                false => fts,
+            }
+        })
+    }
+    fn rank(&self, filter: &FoodFilter) -> Option<Rank<'static, Mysql>> {
+        let q = filter.query.as_ref()?;
+        Some(Box::new(
+            sql::<Double>("MATCH(foods.description,foods.ingredients) AGAINST(")
+                .bind::<Text, _>(q.words().join(" "))
+                .sql(")"),
+        ))
+    }
+}
+/// Matches the descriptions with a word starting with the first three letters of
+/// one of a term's words, the candidates for a misspelling of it
+fn prefixes(t: &Term) -> Search<'static, Mysql> {
+    let words: Vec<String> = t
+        .words
+        .iter()
+        .map(|w| {
+            w.chars()
+                .filter(|c| c.is_alphanumeric())
+                .take(3)
+                .collect::<String>()
+        })
+        .filter(|w| !w.is_empty())
+        .map(|w| format!("{}*", w))
+        .collect();
+    Box::new(
+        sql::<Bool>("MATCH(foods.description) AGAINST(")
+            .bind::<Text, _>(words.join(" "))
+            .sql(" IN BOOLEAN MODE)"),
+    )
+}
+/// Returns the columns of the FULLTEXT index to match a term against
+fn columns(field: Option<Field>) -> String {
+    match field {
+        Some(f) => format!("foods.{}", f.column()),
+        None => "foods.description,foods.ingredients".to_string(),
+    }
+}
+
+bfpd::diesel_store!(MysqlStore, Mysql, Fts);
+
+/// suggest matches prefixes as they are:  the columns' case insensitive collation and
+/// btree indexes serve them
+impl Dialect for MysqlStore {
+    type Connection = MysqlConnection;
+    /// A query can't read a temporary table twice, as a ranked browse does, so
+    /// search_hits is a view of the connection's rows of search_hit_rows
+    const CLEAR_HITS: &'static str =
+        "DELETE FROM search_hit_rows WHERE connection_id = CONNECTION_ID()";
+    const HITS_TABLE: &'static str = "search_hit_rows";
+    fn owners_like(
+        &self,
+        owner: &str,
+        max: i64,
+    ) -> Result<Vec<(Brand, f64)>, Box<dyn Error + Send + Sync>> {
+        let conn = self.pool.get()?;
+        let rows = query::owner_candidates::<Mysql>(owner).load::<Brand>(&conn)?;
+        Ok(fuzzy::closest(owner, rows, |b| &b.owner, max as usize))
+    }
+    fn food_groups_like(
+        &self,
+        group: &str,
+        max: i64,
+    ) -> Result<Vec<(Foodgroup, f64)>, Box<dyn Error + Send + Sync>> {
+        let conn = self.pool.get()?;
+        let rows = query::food_group_candidates::<Mysql>(group).load::<Foodgroup>(&conn)?;
+        Ok(fuzzy::closest(
+            group,
+            rows,
+            |fg| &fg.description,
+            max as usize,
+        ))
+    }
+    fn deferred(&self) -> Option<&Deferred> {
+        Some(&self.deferred)
+    }
+    fn bulk_load_foods(
+        &self,
+        foods: Rows<Food>,
+        at: Progress,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let conn = self.pool.get()?;
+        self.deferred.during(|| {
+            load::load_data(
+                &conn,
+                "foods",
+                &bulk::FOOD_COLUMNS,
+                foods,
+                bulk::food_record,
+                at,
+                Some(&self.deferred),
+            )
+        })
+    }
+    fn bulk_load_nutrient_data(
+        &self,
+        nd: Rows<Nutrientdata>,
+        at: Progress,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let conn = self.pool.get()?;
+        load::load_data(
+            &conn,
+            "nutrient_data",
+            &bulk::NUTRIENT_DATA_COLUMNS,
+            nd,
+            bulk::nutrientdata_record,
+            at,
+            None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::connect;
+    use diesel::connection::SimpleConnection;
+    use std::env;
+
+    /// Loads the fixtures' csv files with LOAD DATA into the database
+    /// TEST_DATABASE_URL names, one created from data/mariadb/up.sql with
+    /// local_infile enabled, whose rows are deleted first
+    #[test]
+    #[ignore]
+    fn load_data() {
+        let db = connect(&env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL"));
+        let conn = db.pool.get().unwrap();
+        for table in &[
+            "nutrient_data",
+            "foods",
+            "brands",
+            "food_groups",
+            "nutrients",
+            "derivations",
+            "ingest_checkpoints",
+        ] {
+            conn.batch_execute(&format!("DELETE FROM {}", table))
+                .unwrap();
+        }
+        drop(conn);
+        bfpd::fixtures::bulk_load(&db, "load");
+    }
+}
@@ -0,0 +1,272 @@
+//! Bulk loading with COPY.  The rows are streamed to the server as csv on a
+//! connection of its own, a COPY ... FROM STDIN for each BATCH_SIZE of them.
+//! Each chunk is copied in one transaction with the load's checkpoint, so an
+//! interrupted load can be resumed.  The table's indexes are dropped before the
+//! first chunk and rebuilt once the rows are in, which is much quicker than
+//! keeping them up to date row by row.  Their definitions are kept in
+//! ingest_indexes meanwhile, so a resumed load rebuilds those an interrupted one
+//! dropped.  The brands and food groups new to a chunk of foods are inserted in
+//! its transaction.  The connection uses TLS as the url's sslmode asks.
+use bfpd::adapter::Deferred;
+use bfpd::bulk;
+use bfpd::csv::{insert_batches, Progress, Rows};
+use bfpd::models::Checkpoint;
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use postgres::{Client, Transaction};
+use postgres_openssl::MakeTlsConnector;
+use std::error::Error;
+
+/// Copies rows into the columns of a table, each chunk with the brands and food
+/// groups deferred while it's read, if any, and returns the number copied
+pub fn copy_in<T>(
+    url: &str,
+    table: &str,
+    columns: &[&str],
+    rows: Rows<T>,
+    record: fn(&T) -> Vec<Option<String>>,
+    at: Progress,
+    deferred: Option<&Deferred>,
+) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    let mut client = connect(url)?;
+    // the timestamps are written as UTC as they're stored
+    client.batch_execute("SET TIME ZONE 'UTC'")?;
+    let mut dropped = false;
+    let count = insert_batches(rows, |chunk| {
+        if !dropped {
+            drop_indexes(&mut client, table)?;
+            dropped = true;
+        }
+        let mut tx = client.transaction()?;
+        if let Some(d) = deferred {
+            insert_deferred(&mut tx, d)?;
+        }
+        copy(&mut tx, table, columns, chunk, record)?;
+        save_checkpoint(&mut tx, &at())?;
+        tx.commit()?;
+        Ok(chunk.len())
+    });
+    // the indexes are rebuilt whether or not the load finishes;  those that can't
+    // be, e.g. the connection dropped, are left for the resumed load
+    let rebuilt = create_indexes(&mut client, table);
+    let count = count?;
+    rebuilt?;
+    if count > 0 {
+        client.batch_execute(&format!("ANALYZE {}", table))?;
+    }
+    Ok(count)
+}
+/// How the server's certificate is checked
+#[derive(Debug, PartialEq)]
+enum Verify {
+    /// not at all, the connection is only encrypted
+    None,
+    /// against the root certificates
+    Ca,
+    /// against the root certificates and the url's host name
+    Full,
+}
+/// A url's TLS settings:  the url for the postgres crate, which knows only
+/// whether TLS is off, tried or required, and how the certificate is checked
+/// against sslrootcert if it's given or else the system's roots
+#[derive(Debug, PartialEq)]
+struct Tls<'a> {
+    url: String,
+    verify: Verify,
+    root_cert: Option<&'a str>,
+}
+/// Reads a url's sslmode the way libpq does for the diesel connections:
+/// disable, allow and prefer don't check the certificate, nor does require
+/// unless there's an sslrootcert, verify-ca checks it's signed by a root and
+/// verify-full also that it's the host's.  Any other mode is an error.
+fn tls(url: &str) -> Result<Tls<'_>, Box<dyn Error + Send + Sync>> {
+    let (base, query) = url.split_once('?').unwrap_or((url, ""));
+    let mut params = Vec::new();
+    let (mut mode, mut root_cert) = ("prefer", None);
+    for p in query.split('&').filter(|p| !p.is_empty()) {
+        match p.split_once('=') {
+            Some(("sslmode", m)) => mode = m,
+            Some(("sslrootcert", f)) => root_cert = Some(f),
+            _ => params.push(p),
+        }
+    }
+    let (sslmode, verify) = match (mode, root_cert) {
+        ("disable", _) => ("disable", Verify::None),
+        ("allow", _) | ("prefer", _) => ("prefer", Verify::None),
+        ("require", None) => ("require", Verify::None),
+        ("require", Some(_)) | ("verify-ca", _) => ("require", Verify::Ca),
+        ("verify-full", _) => ("require", Verify::Full),
+        (m, _) => return Err(format!("invalid sslmode {}", m).into()),
+    };
+    let sslmode = format!("sslmode={}", sslmode);
+    params.push(&sslmode);
+    Ok(Tls {
+        url: format!("{}?{}", base, params.join("&")),
+        verify,
+        root_cert,
+    })
+}
+/// Connects with TLS as the url's sslmode asks
+fn connect(url: &str) -> Result<Client, Box<dyn Error + Send + Sync>> {
+    let tls = tls(url)?;
+    let mut builder = SslConnector::builder(SslMethod::tls())?;
+    match (&tls.verify, tls.root_cert) {
+        (Verify::None, _) => builder.set_verify(SslVerifyMode::NONE),
+        (_, Some(f)) => builder.set_ca_file(f)?,
+        (_, None) => (),
+    }
+    let mut connector = MakeTlsConnector::new(builder.build());
+    if tls.verify == Verify::Ca {
+        connector.set_callback(|c, _| {
+            c.set_verify_hostname(false);
+            Ok(())
+        });
+    }
+    Ok(Client::connect(&tls.url, connector)?)
+}
+fn copy<T>(
+    client: &mut Transaction,
+    table: &str,
+    columns: &[&str],
+    rows: &[T],
+    record: fn(&T) -> Vec<Option<String>>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut writer = client.copy_in(
+        format!(
+            "COPY {} ({}) FROM STDIN WITH (FORMAT csv)",
+            table,
+            columns.join(", ")
+        )
+        .as_str(),
+    )?;
+    // an unfinished copy is cancelled when the writer is dropped
+    bulk::write_csv(&mut writer, rows, record, bulk::Format::Copy)?;
+    writer.finish()?;
+    Ok(())
+}
+/// Inserts the brands and food groups deferred since the last chunk
+fn insert_deferred(
+    tx: &mut Transaction,
+    deferred: &Deferred,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (brands, food_groups) = deferred.take();
+    for b in &brands {
+        tx.execute(
+            "INSERT INTO brands (id, owner, brand, subbrand) VALUES ($1, $2, $3, $4)",
+            &[&b.id, &b.owner, &b.brand, &b.subbrand],
+        )?;
+    }
+    for fg in &food_groups {
+        tx.execute(
+            "INSERT INTO food_groups (id, description) VALUES ($1, $2)",
+            &[&fg.id, &fg.description],
+        )?;
+    }
+    // the ids are given so keep the sequences past them
+    for (table, inserted) in &[("brands", brands.len()), ("food_groups", food_groups.len())] {
+        if *inserted > 0 {
+            tx.batch_execute(&format!(
+                "SELECT setval('{}_id_seq', (SELECT max(id) FROM {}))",
+                table, table
+            ))?;
+        }
+    }
+    Ok(())
+}
+/// Drops a table's indexes but those of its constraints, e.g. its primary key,
+/// and saves their definitions in ingest_indexes
+fn drop_indexes(client: &mut Client, table: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut tx = client.transaction()?;
+    let indexes: Vec<(String, String)> = tx
+        .query(
+            "SELECT indexname::text, indexdef FROM pg_indexes WHERE schemaname = 'public' \
+             AND tablename = $1 AND indexname NOT IN (SELECT conname FROM pg_constraint \
+             WHERE conrelid = ('public.' || $1)::regclass)",
+            &[&table],
+        )?
+        .iter()
+        .map(|r| (r.get(0), r.get(1)))
+        .collect();
+    for (name, definition) in &indexes {
+        tx.execute(
+            "INSERT INTO ingest_indexes (tablename, indexname, definition) VALUES ($1, $2, $3)",
+            &[&table, name, definition],
+        )?;
+        tx.batch_execute(&format!("DROP INDEX public.\"{}\"", name))?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+/// Creates the indexes saved in ingest_indexes for a table
+fn create_indexes(client: &mut Client, table: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut tx = client.transaction()?;
+    let definitions: Vec<String> = tx
+        .query(
+            "DELETE FROM ingest_indexes WHERE tablename = $1 RETURNING definition",
+            &[&table],
+        )?
+        .iter()
+        .map(|r| r.get(0))
+        .collect();
+    for definition in &definitions {
+        tx.batch_execute(definition)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+/// Saves a load's checkpoint, replacing the phase's last one
+fn save_checkpoint(
+    client: &mut Transaction,
+    at: &Checkpoint,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    client.execute(
+        "INSERT INTO ingest_checkpoints (phase, file, last_row, last_fdc_id) \
+         VALUES ($1, $2, $3, $4) ON CONFLICT (phase) DO UPDATE SET file = EXCLUDED.file, \
+         last_row = EXCLUDED.last_row, last_fdc_id = EXCLUDED.last_fdc_id",
+        &[&at.phase, &at.file, &at.last_row, &at.last_fdc_id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn read_sslmode() {
+        let base = "postgres://u@localhost/bfpd";
+        for (query, sslmode, verify) in &[
+            ("", "prefer", Verify::None),
+            ("?sslmode=disable", "disable", Verify::None),
+            ("?sslmode=allow", "prefer", Verify::None),
+            ("?sslmode=prefer", "prefer", Verify::None),
+            ("?sslmode=require", "require", Verify::None),
+            (
+                "?sslmode=require&sslrootcert=/ca.pem",
+                "require",
+                Verify::Ca,
+            ),
+            ("?sslmode=verify-ca", "require", Verify::Ca),
+            ("?sslmode=verify-full", "require", Verify::Full),
+        ] {
+            let url = format!("{}{}", base, query);
+            let tls = tls(&url).unwrap();
+            assert_eq!(
+                tls.url,
+                format!("{}?sslmode={}", base, sslmode),
+                "{}",
+                query
+            );
+            assert_eq!(&tls.verify, verify, "{}", query);
+        }
+        let url = format!(
+            "{}?connect_timeout=5&sslmode=verify-full&sslrootcert=/ca.pem",
+            base
+        );
+        let tls = tls(&url).unwrap();
+        assert_eq!(
+            tls.url,
+            format!("{}?connect_timeout=5&sslmode=require", base)
+        );
+        assert_eq!(tls.root_cert, Some("/ca.pem"));
+        assert!(super::tls(&format!("{}?sslmode=verify", base)).is_err());
+    }
+}
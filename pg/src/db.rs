@@ -1,17 +1,44 @@
 extern crate diesel;
-use std::env;
 
+use bfpd::adapter::Deferred;
+use diesel::connection::SimpleConnection;
 use diesel::pg::PgConnection;
-use diesel::r2d2::{ConnectionManager, Pool, PoolError};
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool, PoolError};
 
 pub type PgPool = Pool<ConnectionManager<PgConnection>>;
 
+/// PgStore runs the bfpd queries against a PostgreSQL connection pool
+#[derive(Clone)]
+pub struct PgStore {
+    pub pool: PgPool,
+    /// the database url, for the COPY bulk loader's own connection
+    pub url: String,
+    pub deferred: Deferred,
+}
+
+/// The shared schema declares the foods dates as timestamps, so keep the
+/// session in UTC to store and compare them the way timestamptz did.
+#[derive(Debug)]
+struct UtcSession;
+
+impl CustomizeConnection<PgConnection, diesel::r2d2::Error> for UtcSession {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.batch_execute("SET TIME ZONE 'UTC'")
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
 fn init(database_url: &str) -> Result<PgPool, PoolError> {
     let manager = ConnectionManager::<PgConnection>::new(database_url);
-    Pool::builder().build(manager)
+    Pool::builder()
+        .connection_customizer(Box::new(UtcSession))
+        .build(manager)
 }
 
-pub fn connect() -> PgPool {
-    let database_url = env::var("DATABASE_URL").expect("Bad url");
-    init(&database_url).expect("Error")
+pub fn connect(database_url: &str) -> PgStore {
+    PgStore {
+        pool: init(database_url).expect("Error"),
+        url: database_url.to_string(),
+        deferred: Deferred::default(),
+    }
 }
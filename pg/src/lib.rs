@@ -1,35 +1,5 @@
-pub mod csv;
+pub mod copy;
 pub mod db;
-pub mod models;
-pub mod schema;
-#[macro_use]
+pub mod store;
 extern crate diesel;
-extern crate diesel_full_text_search;
 extern crate dotenv;
-extern crate serde;
-extern crate serde_json;
-#[macro_use]
-extern crate serde_derive;
-use std::error::Error;
-pub trait Get {
-    type Item;
-    type Conn;
-    fn get(&self, c: &Self::Conn) -> Result<Vec<Self::Item>, Box<dyn Error + Send + Sync>>;
-}
-pub trait Browse {
-    type Item;
-    type Conn;
-    fn browse(
-        &self,
-        max: i64,
-        off: i64,
-        sort: String,
-        order: String,
-        c: &Self::Conn,
-    ) -> Result<Vec<Self::Item>, Box<dyn Error + Send + Sync>>;
-}
-pub trait Count {
-    type Item;
-    type Conn;
-    fn query_count(&self, c: &Self::Conn) -> Result<i64, Box<dyn Error + Send + Sync>>;
-}
@@ -0,0 +1,178 @@
+//! Runs the bfpd queries on PostgreSQL
+use crate::copy;
+use crate::db::PgStore;
+use bfpd::adapter::{Deferred, Dialect};
+use bfpd::bulk;
+use bfpd::csv::{Progress, Rows};
+use bfpd::filter::FoodFilter;
+use bfpd::fuzzy;
+use bfpd::models::{Brand, Food, Foodgroup, Nutrientdata};
+use bfpd::query::{self, FullText, Rank, Search};
+use bfpd::schema::{brands, food_groups};
+use bfpd::search::Field;
+use diesel::dsl::sql;
+use diesel::pg::{Pg, PgConnection};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Double, Text};
+use std::error::Error;
+
+/// full-text search on the foods kw_tsvector column, ranked with ts_rank_cd.
+/// Terms scoped to a field are matched against that column's tsvector.  A fuzzy
+/// search also matches descriptions with pg_trgm's <% operator, whose default
+/// word_similarity_threshold is fuzzy::MATCH_THRESHOLD.
+struct Fts;
+impl FullText<Pg> for Fts {
+    fn search(&self, filter: &FoodFilter) -> Option<Search<'static, Pg>> {
+        query::search_predicate(filter.query.as_ref()?, |t| {
+            let fts: Search<'static, Pg> = Box::new(
+                sql::<Bool>(&format!("{} @@ to_tsquery('english', ", tsvector(t.field)))
+                    .bind::<Text, _>(t.words.join(" <-> "))
+                    .sql(")"),
+            );
+            match filter.fuzzy && !t.exclude && t.in_description() {
+                true => Box::new(
+                    fts.or(sql::<Bool>("")
+                        .bind::<Text, _>(t.words.join(" "))
+                        .sql(" <% foods.description")),
+                ),
+                false => fts,
+            }
+        })
+    }
+    fn rank(&self, filter: &FoodFilter) -> Option<Rank<'static, Pg>> {
+        let q = filter.query.as_ref()?;
+        Some(Box::new(
+            sql::<Double>("ts_rank_cd(foods.kw_tsvector, to_tsquery('english', ")
+                .bind::<Text, _>(q.words().join(" | "))
+                .sql("))::float8"),
+        ))
+    }
+}
+fn tsvector(field: Option<Field>) -> String {
+    match field {
+        Some(f) => format!("to_tsvector('english', COALESCE(foods.{}, ''))", f.column()),
+        None => "foods.kw_tsvector".to_string(),
+    }
+}
+
+bfpd::diesel_store!(PgStore, Pg, Fts);
+
+impl Dialect for PgStore {
+    type Connection = PgConnection;
+    /// PostgreSQL compares case so the prefix is matched on the lower cased
+    /// columns, which are indexed with text_pattern_ops
+    const LOWER_PREFIX: bool = true;
+    /// TRUNCATE rather than DELETE as a temporary table isn't vacuumed
+    const CLEAR_HITS: &'static str = "CREATE TEMPORARY TABLE IF NOT EXISTS search_hits (id INTEGER PRIMARY KEY, score DOUBLE PRECISION NOT NULL); TRUNCATE search_hits";
+    fn owners_like(
+        &self,
+        owner: &str,
+        max: i64,
+    ) -> Result<Vec<(Brand, f64)>, Box<dyn Error + Send + Sync>> {
+        let conn = self.pool.get()?;
+        let score = || {
+            sql::<Double>("similarity(brands.owner, ")
+                .bind::<Text, _>(owner.to_string())
+                .sql(")::float8")
+        };
+        // an owner has a row for each of its brands so one is kept before the limit
+        Ok(brands::table
+            .filter(
+                sql::<Bool>("brands.id IN (SELECT min(id) FROM brands WHERE owner % ")
+                    .bind::<Text, _>(owner.to_string())
+                    .sql(" GROUP BY owner)"),
+            )
+            .filter(score().ge(fuzzy::SUGGEST_THRESHOLD))
+            .select((brands::all_columns, score()))
+            .order((score().desc(), brands::owner))
+            .limit(max)
+            .load::<(Brand, f64)>(&conn)?)
+    }
+    fn food_groups_like(
+        &self,
+        group: &str,
+        max: i64,
+    ) -> Result<Vec<(Foodgroup, f64)>, Box<dyn Error + Send + Sync>> {
+        let conn = self.pool.get()?;
+        let score = || {
+            sql::<Double>("similarity(food_groups.description, ")
+                .bind::<Text, _>(group.to_string())
+                .sql(")::float8")
+        };
+        Ok(food_groups::table
+            .filter(sql::<Bool>("food_groups.description % ").bind::<Text, _>(group.to_string()))
+            .filter(score().ge(fuzzy::SUGGEST_THRESHOLD))
+            .select((food_groups::all_columns, score()))
+            .order(score().desc())
+            .limit(max)
+            .load::<(Foodgroup, f64)>(&conn)?)
+    }
+    /// the id is given so keep the sequence past it
+    fn id_inserted(conn: &PgConnection, table: &str) -> QueryResult<()> {
+        diesel::sql_query(format!(
+            "SELECT setval('{}_id_seq', (SELECT max(id) FROM {}))",
+            table, table
+        ))
+        .execute(conn)?;
+        Ok(())
+    }
+    fn deferred(&self) -> Option<&Deferred> {
+        Some(&self.deferred)
+    }
+    fn bulk_load_foods(
+        &self,
+        foods: Rows<Food>,
+        at: Progress,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        self.deferred.during(|| {
+            copy::copy_in(
+                &self.url,
+                "foods",
+                &bulk::FOOD_COLUMNS,
+                foods,
+                bulk::food_record,
+                at,
+                Some(&self.deferred),
+            )
+        })
+    }
+    fn bulk_load_nutrient_data(
+        &self,
+        nd: Rows<Nutrientdata>,
+        at: Progress,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        copy::copy_in(
+            &self.url,
+            "nutrient_data",
+            &bulk::NUTRIENT_DATA_COLUMNS,
+            nd,
+            bulk::nutrientdata_record,
+            at,
+            None,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::connect;
+    use diesel::connection::SimpleConnection;
+    use std::env;
+
+    /// Copies the fixtures' csv files into the database TEST_DATABASE_URL names, one
+    /// created from data/pg/up.sql, whose rows are deleted first
+    #[test]
+    #[ignore]
+    fn copy_load() {
+        let db = connect(&env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL"));
+        db.pool
+            .get()
+            .unwrap()
+            .batch_execute(
+                "TRUNCATE nutrient_data, foods, brands, food_groups, nutrients, derivations, \
+                 ingest_checkpoints RESTART IDENTITY",
+            )
+            .unwrap();
+        bfpd::fixtures::bulk_load(&db, "copy");
+    }
+}
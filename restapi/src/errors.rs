@@ -11,6 +11,17 @@ pub enum CustomError {
     FoodSortError,
     MinMaxError,
     ReportSortError,
+    DateRangeError,
+    BrandNotFoundError(Vec<String>),
+    FoodGroupNotFoundError(Vec<String>),
+    CursorError,
+    SearchError(String),
+    KindError,
+    FacetError(String),
+    NutrientRangeError(String),
+    NutrientNotFoundError(String),
+    StatsError(String),
+    HistogramError(String),
     Unknown,
 }
 #[derive(Serialize)]
@@ -18,6 +29,9 @@ pub struct ErrorResponse {
     code: u16,
     error: String,
     message: String,
+    /// "did you mean" names for a failed lookup
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    suggestions: Vec<String>,
 }
 impl ErrorResponse {
     pub fn new(e: CustomError) -> Self {
@@ -26,39 +40,114 @@ impl ErrorResponse {
                 code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
                 error: "Unprocessable parameter".to_string(),
                 message: format!("Invalid max parameter. Must be > {} and <= {}", 0, MAX_RECS),
+                suggestions: Vec::new(),
             },
 
             CustomError::OrderError => Self {
                 code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
                 error: "Unprocessable parameter".to_string(),
                 message: "Invalid order parameter. Must be ASC or DESC".to_string(),
+                suggestions: Vec::new(),
             },
             CustomError::OffsetError => Self {
                 code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
                 error: "Unprocessable parameter".to_string(),
                 message: "Offset parameter must be >= 0".to_string(),
+                suggestions: Vec::new(),
             },
             CustomError::FoodSortError => Self {
                 code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
                 error: "Unprocessable parameter".to_string(),
-                message: "Invalid sort parameter. Must be 'description', 'id', 'fdcid' or 'upc'"
+                message: "Invalid sort parameter. Must be 'description', 'id', 'fdcid', 'upc', 'relevance' or 'nutrient:nbr', adding ':portion' for per serving values"
                     .to_string(),
+                suggestions: Vec::new(),
             },
             CustomError::MinMaxError => Self {
                 code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
                 error: "Unprocessable parameter".to_string(),
                 message: "Invalid parameter. minimum value must be less than maximum value"
                     .to_string(),
+                suggestions: Vec::new(),
             },
             CustomError::ReportSortError => Self {
                 code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
                 error: "Unprocessable parameter".to_string(),
                 message: "Invalid sor parameter. Must be 'value' or 'portion'".to_string(),
+                suggestions: Vec::new(),
+            },
+            CustomError::DateRangeError => Self {
+                code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                error: "Unprocessable parameter".to_string(),
+                message: "Invalid pubdate parameter. Must be YYYY-MM-DD or YYYY-MM-DD:YYYY-MM-DD"
+                    .to_string(),
+                suggestions: Vec::new(),
+            },
+            CustomError::BrandNotFoundError(suggestions) => Self {
+                code: StatusCode::NOT_FOUND.as_u16(),
+                error: "Not found".to_string(),
+                message: "Brand owner not found".to_string(),
+                suggestions,
+            },
+            CustomError::FoodGroupNotFoundError(suggestions) => Self {
+                code: StatusCode::NOT_FOUND.as_u16(),
+                error: "Not found".to_string(),
+                message: "Food group not found".to_string(),
+                suggestions,
+            },
+            CustomError::CursorError => Self {
+                code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                error: "Unprocessable parameter".to_string(),
+                message: "Invalid cursor parameter. Must be a next or prev cursor returned with the same sort and order and can't be used with offset"
+                    .to_string(),
+                suggestions: Vec::new(),
+            },
+            CustomError::SearchError(reason) => Self {
+                code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                error: "Unprocessable parameter".to_string(),
+                message: reason,
+                suggestions: Vec::new(),
+            },
+            CustomError::KindError => Self {
+                code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                error: "Unprocessable parameter".to_string(),
+                message: "Invalid kind parameter. Must be 'food', 'owner' or 'group'".to_string(),
+                suggestions: Vec::new(),
+            },
+            CustomError::FacetError(reason) => Self {
+                code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                error: "Unprocessable parameter".to_string(),
+                message: format!("Invalid facets parameter. {}", reason),
+                suggestions: Vec::new(),
+            },
+            CustomError::NutrientRangeError(reason) => Self {
+                code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                error: "Unprocessable parameter".to_string(),
+                message: format!("Invalid nutrients parameter. {}", reason),
+                suggestions: Vec::new(),
+            },
+            CustomError::NutrientNotFoundError(nbr) => Self {
+                code: StatusCode::NOT_FOUND.as_u16(),
+                error: "Not found".to_string(),
+                message: format!("Nutrient {} not found", nbr),
+                suggestions: Vec::new(),
+            },
+            CustomError::StatsError(reason) => Self {
+                code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                error: "Unprocessable parameter".to_string(),
+                message: reason,
+                suggestions: Vec::new(),
+            },
+            CustomError::HistogramError(reason) => Self {
+                code: StatusCode::UNPROCESSABLE_ENTITY.as_u16(),
+                error: "Unprocessable parameter".to_string(),
+                message: reason,
+                suggestions: Vec::new(),
             },
             CustomError::Unknown => Self {
                 code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
                 error: "Internal server error".to_string(),
                 message: "Unknown Internal Error".to_string(),
+                suggestions: Vec::new(),
             },
         }
     }
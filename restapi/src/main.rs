@@ -6,23 +6,44 @@ mod routes;
 mod views;
 use actix_web::{App, HttpServer};
 use dotenv::dotenv;
-#[cfg(feature = "maria")]
-use mariadb::db::connect;
-#[cfg(feature = "postgres")]
-use pg::db::connect;
-use routes::{food, foods, nutrient_report, Context};
+use bfpd::{Backend, Store};
+use routes::{
+    food, foods, histogram, nutrient_report, nutrient_stats, nutrients_report, suggest, Context,
+};
+use search_index::store::IndexedStore;
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
 
+/// connects to the backend named by the DATABASE_URL scheme.  Searches use
+/// the index in the SEARCH_INDEX directory if one is set.
+fn connect() -> Arc<dyn Store> {
+    let url = env::var("DATABASE_URL").expect("Bad url");
+    let db: Arc<dyn Store> = match Backend::from_url(&url).expect("Error") {
+        Backend::Postgres => Arc::new(pg::db::connect(&url)),
+        Backend::Mysql => Arc::new(mariadb::db::connect(&url)),
+        Backend::Sqlite => Arc::new(sqlite::db::connect(&url)),
+    };
+    match env::var("SEARCH_INDEX") {
+        Ok(path) => Arc::new(IndexedStore::open(db, Path::new(&path)).expect("Error opening index")),
+        Err(_e) => db,
+    }
+}
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
-    let pool = connect();
-    let context = Context { db: pool.clone() };
+    let db = connect();
+    let context = Context { db };
     HttpServer::new(move || {
         App::new()
             .data(context.clone())
             .service(food)
             .service(foods)
             .service(nutrient_report)
+            .service(nutrients_report)
+            .service(nutrient_stats)
+            .service(histogram)
+            .service(suggest)
     })
     .bind("0.0.0.0:8080")?
     .run()
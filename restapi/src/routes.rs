@@ -1,27 +1,23 @@
 use crate::errors::{CustomError, ErrorResponse};
 use crate::views::Foodview;
 use actix_web::{get, web, web::Data, Error, HttpResponse};
-#[cfg(feature = "maria")]
-use mariadb::db::MysqlPool;
-#[cfg(feature = "maria")]
-use mariadb::models::*;
-#[cfg(feature = "maria")]
-use mariadb::{Browse, Count, Get};
-#[cfg(feature = "postgres")]
-use pg::db::PgPool;
-#[cfg(feature = "postgres")]
-use pg::models::*;
-#[cfg(feature = "postgres")]
-use pg::{Browse, Get};
+use bfpd::cursor::{self, Cursor, Page};
+use bfpd::facet::{FacetField, Facets, DEFAULT_VALUES};
+use bfpd::filter::{Basis, DateRange, FoodFilter, NutrientRange, NutrientSort};
+use bfpd::fuzzy::NoMatchError;
+use bfpd::histogram::{Bins, DEFAULT_BINS};
+use bfpd::models::*;
+use bfpd::stats::{self, GroupBy, DEFAULT_GROUPS, DEFAULT_PERCENTILES};
+use bfpd::suggest::Kind;
+use bfpd::Store;
 use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 pub const MAX_RECS: i32 = 150;
+pub const MAX_SUGGESTIONS: i32 = 50;
 
 #[derive(Clone)]
 pub struct Context {
-    #[cfg(feature = "maria")]
-    pub db: MysqlPool,
-    #[cfg(feature = "postgres")]
-    pub db: PgPool,
+    pub db: Arc<dyn Store>,
 }
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Browsequery {
@@ -29,11 +25,118 @@ pub struct Browsequery {
     offset: Option<i32>,
     sort: Option<String>,
     order: Option<String>,
+    query: Option<String>,
+    fuzzy: Option<bool>,
+    pubdate: Option<String>,
+    owner: Option<String>,
+    brand: Option<String>,
+    subbrand: Option<String>,
+    fg: Option<String>,
+    country: Option<String>,
+    datasource: Option<String>,
+    serving_unit: Option<String>,
+    nutrients: Option<String>,
+    cursor: Option<String>,
+    facets: Option<String>,
+    page: Option<bool>,
+}
+impl Browsequery {
+    /// maps the filter parameters onto a FoodFilter, looking up the brand owner
+    /// and food group ids.  Bad parameters are added to errs.
+    fn food_filter(&self, db: &dyn Store, errs: &mut Vec<ErrorResponse>) -> FoodFilter {
+        let mut filter = FoodFilter::new();
+        if let Some(owner) = self.owner.as_ref().filter(|o| !o.is_empty()) {
+            let mut b = Brand::new();
+            b.owner = owner.to_string();
+            match b.find_similar_owner(db) {
+                Ok(data) => filter.owner = Some(data.owner),
+                Err(e) => errs.push(ErrorResponse::new(CustomError::BrandNotFoundError(
+                    suggestions(&*e),
+                ))),
+            };
+        }
+        if let Some(group) = self.fg.as_ref().filter(|g| !g.is_empty()) {
+            let mut fg = Foodgroup::new();
+            fg.description = group.to_string();
+            match fg.find_similar_description(db) {
+                Ok(data) => filter.food_group_id = Some(data.id),
+                Err(e) => errs.push(ErrorResponse::new(CustomError::FoodGroupNotFoundError(
+                    suggestions(&*e),
+                ))),
+            };
+        }
+        if let Some(pubdate) = self.pubdate.as_ref().filter(|p| !p.is_empty()) {
+            match DateRange::parse(pubdate) {
+                Ok(r) => filter.publication_date = Some(r),
+                Err(_e) => errs.push(ErrorResponse::new(CustomError::DateRangeError)),
+            };
+        }
+        if let Some(q) = &self.query {
+            if let Err(e) = filter.search(q) {
+                errs.push(ErrorResponse::new(CustomError::SearchError(e.to_string())));
+            }
+        }
+        if let Some(ranges) = &self.nutrients {
+            filter.nutrients = nutrient_ranges(ranges, db, errs);
+        }
+        filter.fuzzy = self.fuzzy.unwrap_or(false);
+        filter.country = self.country.clone().filter(|c| !c.is_empty());
+        filter.datasource = self.datasource.clone().filter(|d| !d.is_empty());
+        filter.serving_unit = self.serving_unit.clone().filter(|u| !u.is_empty());
+        filter.brand = self.brand.clone().filter(|b| !b.is_empty());
+        filter.subbrand = self.subbrand.clone().filter(|b| !b.is_empty());
+        filter
+    }
+}
+/// true if there's a nutrient numbered nbr
+fn nutrient_exists(nbr: &str, db: &dyn Store) -> bool {
+    let mut n = Nutrient::new();
+    n.nutrientno = nbr.to_string();
+    n.find_by_no(db).is_ok()
+}
+/// parses a comma separated list of nutrient ranges, e.g.
+/// "203:10:,307::140:portion", checking each nutrient exists.  Bad ranges are
+/// added to errs.
+fn nutrient_ranges(
+    ranges: &str,
+    db: &dyn Store,
+    errs: &mut Vec<ErrorResponse>,
+) -> Vec<NutrientRange> {
+    let mut list = Vec::new();
+    for r in ranges.split(',').filter(|r| !r.trim().is_empty()) {
+        match NutrientRange::parse(r.trim()) {
+            Ok(r) if nutrient_exists(&r.nutrientno, db) => list.push(r),
+            Ok(r) => errs.push(ErrorResponse::new(CustomError::NutrientNotFoundError(
+                r.nutrientno,
+            ))),
+            Err(e) => errs.push(ErrorResponse::new(CustomError::NutrientRangeError(
+                e.to_string(),
+            ))),
+        }
+    }
+    list
+}
+/// reads an order parameter, the default if there's none.  A bad order is added
+/// to errs.
+fn read_order(order: Option<&str>, default: &'static str, errs: &mut Vec<ErrorResponse>) -> String {
+    match cursor::parse_order(order, default) {
+        Some(o) => o.to_string(),
+        None => {
+            errs.push(ErrorResponse::new(CustomError::OrderError));
+            default.to_string()
+        }
+    }
+}
+/// the names a failed fuzzy lookup came closest to, if any
+fn suggestions(e: &(dyn std::error::Error + Send + Sync + 'static)) -> Vec<String> {
+    e.downcast_ref::<NoMatchError>()
+        .map(|n| n.suggestions.clone())
+        .unwrap_or_default()
 }
 
 #[get("/food/{fid}")]
 pub async fn food(ctx: Data<Context>, id: web::Path<String>) -> Result<HttpResponse, Error> {
-    let conn = ctx.db.get().expect("couldn't get DB connection from pool");
+    let db = ctx.db.clone();
     let mut f = Food::new();
     let fid = id.to_string();
     if fid.len() >= 10 {
@@ -41,7 +144,9 @@ pub async fn food(ctx: Data<Context>, id: web::Path<String>) -> Result<HttpRespo
     } else {
         f.fdc_id = fid;
     }
-    let data = web::block(move || f.get(&conn)).await.unwrap();
+    let data = web::block(move || db.get(&f))
+        .await
+        .map_err(|_| HttpResponse::InternalServerError())?;
     let nids: Vec<String> = Vec::new();
     Ok(web::block(move || Foodview::build_view(data, &nids, &ctx))
         .await
@@ -53,34 +158,41 @@ pub async fn foods(
     ctx: Data<Context>,
     browse: web::Query<Browsequery>,
 ) -> Result<HttpResponse, Error> {
-    let conn = ctx.db.get().expect("couldn't get DB connection from pool");
+    let db = ctx.db.clone();
     let mut errs: Vec<ErrorResponse> = Vec::new();
     let max = match browse.max {
         None => 50,
         _ => browse.max.unwrap(),
     };
-    let mut sort = match browse.sort {
+    let filter = browse.food_filter(&*db, &mut errs);
+    // searches are ranked most relevant first unless asked otherwise
+    let mut sort = match browse.sort.as_ref().filter(|s| !s.is_empty()) {
+        Some(s) => s.to_lowercase(),
+        None if filter.query.is_some() => "relevance".to_string(),
         None => "id".to_string(),
-        _ => browse.sort.as_ref().unwrap().to_string(),
     };
-    sort = sort.to_lowercase();
     sort = match &*sort {
         "description" => "description".to_string(),
         "id" => "id".to_string(),
         "fdcid" => "fdcId".to_string(),
         "upc" => "upc".to_string(),
-        _ => "".to_string(),
+        "relevance" => "relevance".to_string(),
+        s => match NutrientSort::parse(s) {
+            Some(by) if nutrient_exists(&by.nutrientno, &*db) => by.to_string(),
+            Some(by) => {
+                errs.push(ErrorResponse::new(CustomError::NutrientNotFoundError(
+                    by.nutrientno.to_string(),
+                )));
+                by.to_string()
+            }
+            None => "".to_string(),
+        },
     };
     if sort.is_empty() {
-        errs.push(ErrorResponse::new(CustomError::MaxValidationError));
-    }
-    let order = match browse.order {
-        None => "asc".to_string(),
-        _ => browse.order.as_ref().unwrap().to_string(),
-    };
-    if order.to_uppercase() != "ASC" && order.to_uppercase() != "DESC" {
-        errs.push(ErrorResponse::new(CustomError::OrderError));
+        errs.push(ErrorResponse::new(CustomError::FoodSortError));
     }
+    let default = if sort == "relevance" { "desc" } else { "asc" };
+    let order = read_order(browse.order.as_deref(), default, &mut errs);
     let offset = match browse.offset {
         None => 0,
         _ => browse.offset.unwrap(),
@@ -88,22 +200,107 @@ pub async fn foods(
     if offset < 0 {
         errs.push(ErrorResponse::new(CustomError::OffsetError));
     }
-    if max > MAX_RECS || max < 1 {
+    if !(1..=MAX_RECS).contains(&max) {
         errs.push(ErrorResponse::new(CustomError::MaxValidationError));
     }
-    if errs.len() > 0 {
+    let cursor = match browse.cursor.as_ref().filter(|c| !c.is_empty()) {
+        None => None,
+        Some(c) => match Cursor::decode(c, &sort, &order) {
+            Ok(cursor) if offset == 0 => Some(cursor),
+            _ => {
+                errs.push(ErrorResponse::new(CustomError::CursorError));
+                None
+            }
+        },
+    };
+    let fields = match &browse.facets {
+        None => Vec::new(),
+        Some(f) => FacetField::parse_list(f).unwrap_or_else(|e| {
+            errs.push(ErrorResponse::new(CustomError::FacetError(e.to_string())));
+            Vec::new()
+        }),
+    };
+    if !errs.is_empty() {
         return HttpResponse::BadRequest().json(errs).await;
     }
-    let mut f = Food::new();
-    f.description = "".to_string();
-    let data = web::block(move || f.browse(max as i64, offset as i64, sort, order, &conn))
-        .await
-        .unwrap();
+    // the foods come in a Foodpage when asked for, or when a cursor or facets
+    // are, otherwise as the plain list /foods has always returned
+    let paged = browse.page.unwrap_or(false) || cursor.is_some() || !fields.is_empty();
+    // count the facets before the filter moves into the page's closure
+    let facets = match fields.is_empty() {
+        true => None,
+        false => {
+            let db = db.clone();
+            let filter = filter.clone();
+            Some(
+                web::block(move || db.facets(&filter, &fields, DEFAULT_VALUES))
+                    .await
+                    .map_err(|_| HttpResponse::InternalServerError())?,
+            )
+        }
+    };
+    // page from a cursor if we have one, otherwise from the offset
+    let (page, snippets) = web::block(move || {
+        let page = match cursor {
+            Some(c) => db.browse_page(&filter, max as i64, Some(&c), sort, order)?,
+            None if offset == 0 => db.browse_page(&filter, max as i64, None, sort, order)?,
+            None => {
+                let data = db.browse(
+                    &filter,
+                    max as i64 + 1,
+                    offset as i64,
+                    sort.to_string(),
+                    order.to_string(),
+                )?;
+                match &*sort {
+                    // the rank and nutrient values aren't read with an offset
+                    // browse so there are no relevance or nutrient cursors
+                    s if s == "relevance" || NutrientSort::parse(s).is_some() => Page {
+                        items: data.into_iter().take(max as usize).collect(),
+                        next: None,
+                        prev: None,
+                    },
+                    _ => Page::create(data, max as i64, None, &sort, &order),
+                }
+            }
+        };
+        let snippets = db.highlights(&filter, &page.items)?;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>((page, snippets))
+    })
+    .await
+    .map_err(|_| HttpResponse::InternalServerError())?;
     let nids: Vec<String> = Vec::new();
-    Ok(web::block(move || Foodview::build_view(data, &nids, &ctx))
-        .await
-        .map(|fvs| HttpResponse::Ok().json(fvs))
-        .map_err(|_| HttpResponse::InternalServerError())?)
+    Ok(web::block(move || {
+        let Page { items, next, prev } = page;
+        Foodview::build_view(items, &nids, &ctx).map(|mut foods| {
+            for (f, s) in foods.iter_mut().zip(snippets) {
+                f.snippet = s;
+            }
+            Foodpage {
+                foods,
+                next,
+                prev,
+                facets,
+            }
+        })
+    })
+    .await
+    .map(|fp| match paged {
+        true => HttpResponse::Ok().json(fp),
+        false => HttpResponse::Ok().json(fp.foods),
+    })
+    .map_err(|_| HttpResponse::InternalServerError())?)
+}
+/// A page of foods with the cursors for the pages either side of it and any
+/// facets
+#[derive(Serialize, Debug)]
+pub struct Foodpage {
+    foods: Vec<Foodview>,
+    next: Option<String>,
+    prev: Option<String>,
+    /// counts of all the foods the filter matches, when asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    facets: Option<Facets>,
 }
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Reportquery {
@@ -121,14 +318,14 @@ pub async fn nutrient_report(
     ctx: Data<Context>,
     rq: web::Query<Reportquery>,
 ) -> Result<HttpResponse, Error> {
-    let conn = ctx.db.get().expect("couldn't get DB connection from pool");
+    let db = ctx.db.clone();
     let mut errs: Vec<ErrorResponse> = Vec::new();
     let mut n = Nutrient::new();
     let max = match rq.max {
         None => 50,
         _ => rq.max.unwrap(),
     };
-    if max > MAX_RECS || max < 1 {
+    if !(1..=MAX_RECS).contains(&max) {
         errs.push(ErrorResponse::new(CustomError::MaxValidationError));
     }
     let offset = match rq.offset {
@@ -139,7 +336,7 @@ pub async fn nutrient_report(
         errs.push(ErrorResponse::new(CustomError::OffsetError));
     }
     n.nutrientno = rq.nutrient.to_string();
-    let n = match n.find_by_no(&conn) {
+    let n = match n.find_by_no(&*db) {
         Ok(data) => data.id,
         Err(_e) => -1,
     };
@@ -159,26 +356,199 @@ pub async fn nutrient_report(
     if sort.is_empty() {
         errs.push(ErrorResponse::new(CustomError::ReportSortError));
     }
-    let mut order = match rq.order {
-        None =>"asc".to_string(),
-        _ => rq.order.as_ref().unwrap().to_string(),
-    };
-    order = order.to_lowercase();
-    order = match &*order {
-        "asc" => "asc".to_string(),
-        _ => "desc".to_string(),
-    };
+    let order = read_order(rq.order.as_deref(), "asc", &mut errs);
     if rq.vmin > rq.vmax {
         errs.push(ErrorResponse::new(CustomError::MinMaxError));
     }
-    if errs.len() > 0 {
+    if !errs.is_empty() {
         return HttpResponse::BadRequest().json(errs).await;
     }
     let f = Food::new();
     let data = web::block(move || {
-        f.get_report(max as i64, offset as i64, sort, order, rq.vmin, rq.vmax, n, &conn)
+        f.get_report(max as i64, offset as i64, sort, order, rq.vmin, rq.vmax, n, &*db)
     })
     .await
-    .unwrap();
+    .map_err(|_| HttpResponse::InternalServerError())?;
     Ok(HttpResponse::Ok().json(data))
 }
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Suggestquery {
+    prefix: String,
+    kind: Option<String>,
+    max: Option<i32>,
+}
+/// type-ahead completions of food descriptions, brand owners or food groups
+#[get("/suggest")]
+pub async fn suggest(
+    ctx: Data<Context>,
+    sq: web::Query<Suggestquery>,
+) -> Result<HttpResponse, Error> {
+    let db = ctx.db.clone();
+    let mut errs: Vec<ErrorResponse> = Vec::new();
+    let max = sq.max.unwrap_or(10);
+    if !(1..=MAX_SUGGESTIONS).contains(&max) {
+        errs.push(ErrorResponse::new(CustomError::MaxValidationError));
+    }
+    let kind = match Kind::parse(sq.kind.as_deref().unwrap_or("food")) {
+        Ok(k) => k,
+        Err(_e) => {
+            errs.push(ErrorResponse::new(CustomError::KindError));
+            Kind::Food
+        }
+    };
+    if !errs.is_empty() {
+        return HttpResponse::BadRequest().json(errs).await;
+    }
+    let prefix = sq.prefix.trim().to_string();
+    if prefix.is_empty() {
+        return HttpResponse::Ok().json(Vec::<String>::new()).await;
+    }
+    Ok(web::block(move || db.suggest(&prefix, kind, max as i64))
+        .await
+        .map(|names| HttpResponse::Ok().json(names))
+        .map_err(|_| HttpResponse::InternalServerError())?)
+}
+/// foods within a range of each of one or more nutrients.  The foods may be
+/// filtered with the other parameters /foods takes and are sorted by the
+/// value of the nutrient numbered sort, the first range's by default.
+#[get("/report/nutrients")]
+pub async fn nutrients_report(
+    ctx: Data<Context>,
+    rq: web::Query<Browsequery>,
+) -> Result<HttpResponse, Error> {
+    let db = ctx.db.clone();
+    let mut errs: Vec<ErrorResponse> = Vec::new();
+    let max = rq.max.unwrap_or(50);
+    if !(1..=MAX_RECS).contains(&max) {
+        errs.push(ErrorResponse::new(CustomError::MaxValidationError));
+    }
+    let offset = rq.offset.unwrap_or(0);
+    if offset < 0 {
+        errs.push(ErrorResponse::new(CustomError::OffsetError));
+    }
+    let filter = rq.food_filter(&*db, &mut errs);
+    if filter.nutrients.is_empty() && errs.is_empty() {
+        errs.push(ErrorResponse::new(CustomError::NutrientRangeError(
+            "At least one range is required".to_string(),
+        )));
+    }
+    // sorted by the first range's nutrient unless another is asked for
+    let sort = rq.sort.clone().unwrap_or_default();
+    if !sort.is_empty() && !filter.nutrients.iter().any(|r| r.nutrientno == sort) {
+        errs.push(ErrorResponse::new(CustomError::NutrientRangeError(format!(
+            "Sort {} must be one of the ranges' nutrients",
+            sort
+        ))));
+    }
+    let order = read_order(rq.order.as_deref(), "asc", &mut errs);
+    if !errs.is_empty() {
+        return HttpResponse::BadRequest().json(errs).await;
+    }
+    Ok(
+        web::block(move || db.nutrients_report(&filter, &sort, &order, max as i64, offset as i64))
+            .await
+            .map(|data| HttpResponse::Ok().json(data))
+            .map_err(|_| HttpResponse::InternalServerError())?,
+    )
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Statsquery {
+    nutrient: String,
+    by: Option<String>,
+    percentiles: Option<String>,
+}
+/// the distribution of a nutrient's per 100 g values in each food group, brand
+/// owner or country.  The foods may be filtered with the parameters /foods
+/// takes and max is the number of groups, those with the most foods.
+#[get("/stats")]
+pub async fn nutrient_stats(
+    ctx: Data<Context>,
+    browse: web::Query<Browsequery>,
+    sq: web::Query<Statsquery>,
+) -> Result<HttpResponse, Error> {
+    let db = ctx.db.clone();
+    let mut errs: Vec<ErrorResponse> = Vec::new();
+    let max = browse.max.unwrap_or(DEFAULT_GROUPS as i32);
+    if !(1..=MAX_RECS).contains(&max) {
+        errs.push(ErrorResponse::new(CustomError::MaxValidationError));
+    }
+    if !nutrient_exists(&sq.nutrient, &*db) {
+        errs.push(ErrorResponse::new(CustomError::NutrientNotFoundError(
+            sq.nutrient.to_string(),
+        )));
+    }
+    let by = match GroupBy::parse(sq.by.as_deref().unwrap_or("fg")) {
+        Ok(by) => by,
+        Err(e) => {
+            errs.push(ErrorResponse::new(CustomError::StatsError(e.to_string())));
+            GroupBy::FoodGroup
+        }
+    };
+    let percentiles = match &sq.percentiles {
+        None => DEFAULT_PERCENTILES.to_vec(),
+        Some(p) => stats::parse_percentiles(p).unwrap_or_else(|e| {
+            errs.push(ErrorResponse::new(CustomError::StatsError(e.to_string())));
+            Vec::new()
+        }),
+    };
+    let filter = browse.food_filter(&*db, &mut errs);
+    if !errs.is_empty() {
+        return HttpResponse::BadRequest().json(errs).await;
+    }
+    let nutrient = sq.nutrient.to_string();
+    Ok(
+        web::block(move || db.nutrient_stats(&filter, &nutrient, by, &percentiles, max as i64))
+            .await
+            .map(|data| HttpResponse::Ok().json(data))
+            .map_err(|_| HttpResponse::InternalServerError())?,
+    )
+}
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Histogramquery {
+    nutrient: String,
+    bins: Option<i64>,
+    edges: Option<String>,
+    log: Option<bool>,
+    portion: Option<bool>,
+}
+/// a histogram of a nutrient's values in the foods the parameters /foods takes
+/// filter, binned into a number of bins spanning the values or between edges
+#[get("/histogram")]
+pub async fn histogram(
+    ctx: Data<Context>,
+    browse: web::Query<Browsequery>,
+    hq: web::Query<Histogramquery>,
+) -> Result<HttpResponse, Error> {
+    let db = ctx.db.clone();
+    let mut errs: Vec<ErrorResponse> = Vec::new();
+    if !nutrient_exists(&hq.nutrient, &*db) {
+        errs.push(ErrorResponse::new(CustomError::NutrientNotFoundError(
+            hq.nutrient.to_string(),
+        )));
+    }
+    let bins = match (&hq.edges, hq.bins) {
+        (Some(_), Some(_)) => Err("Use either bins or edges, not both".into()),
+        (Some(edges), None) => Bins::parse_edges(edges),
+        (None, n) => Bins::count(n.unwrap_or(DEFAULT_BINS as i64)),
+    };
+    let bins = bins.unwrap_or_else(|e| {
+        errs.push(ErrorResponse::new(CustomError::HistogramError(e.to_string())));
+        Bins::Count(DEFAULT_BINS)
+    });
+    let basis = match hq.portion.unwrap_or(false) {
+        true => Basis::Portion,
+        false => Basis::Unit,
+    };
+    let filter = browse.food_filter(&*db, &mut errs);
+    if !errs.is_empty() {
+        return HttpResponse::BadRequest().json(errs).await;
+    }
+    let nutrient = hq.nutrient.to_string();
+    let log = hq.log.unwrap_or(false);
+    Ok(
+        web::block(move || db.histogram(&filter, &nutrient, basis, &bins, log))
+            .await
+            .map(|h| HttpResponse::Ok().json(h))
+            .map_err(|_| HttpResponse::InternalServerError())?,
+    )
+}
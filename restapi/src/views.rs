@@ -1,8 +1,6 @@
 use crate::routes::Context;
-#[cfg(feature = "maria")]
-use mariadb::models::*;
-#[cfg(feature = "postgres")]
-use pg::models::*;
+use bfpd::loader::FoodDetails;
+use bfpd::models::*;
 
 use serde::{Deserialize, Serialize};
 use std::error::Error;
@@ -16,36 +14,44 @@ pub struct Foodview {
     pub description: String,
     pub food_group: String,
     pub owner: String,
+    pub brand: Option<String>,
+    pub subbrand: Option<String>,
     pub datasource: String,
     pub serving_size: Option<f64>,
     pub serving_unit: Option<String>,
     pub serving_description: Option<String>,
     pub country: Option<String>,
     pub ingredients: Option<String>,
+    pub discontinued_date: Option<String>,
+    pub package_weight: Option<String>,
+    pub preparation_state_code: Option<String>,
+    pub trade_channel: Option<String>,
+    pub short_description: Option<String>,
+    pub fdc_release: Option<String>,
     pub nutrient_data: Vec<Nutrientdataview>,
+    /// the description or ingredients with the search terms in bold, when
+    /// searching a search index
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
 }
 
 impl Foodview {
+    /// Builds the views for a page of foods.  The page's nutrient data, brand
+    /// owners and food groups are loaded together rather than food by food.
     pub fn build_view(
         fd: Vec<Food>,
-        nids: &Vec<String>,
+        nids: &[String],
         context: &Context,
     ) -> Result<Vec<Foodview>, Box<dyn Error + Send + Sync>> {
-        let conn = context.db.get().unwrap();
+        let details = context.db.food_details(&fd, nids)?;
         let mut fv: Vec<Foodview> = Vec::new();
-        for i in &fd {
-            let f = &i;
-            let mut fdv = Foodview::create(&f, &context);
-            let nutform: Vec<NutrientdataForm> = f
-                .get_nutrient_data(nids, &conn)
-                .expect("error loading nutrient data");
-
+        for f in &fd {
+            let mut fdv = Foodview::create(f, &details);
             let mut ndv: Vec<Nutrientdataview> = Vec::new();
-            for j in &nutform {
-                let nf = &j;
-                let mut nv = Nutrientdataview::create(&nf);
+            for nf in details.nutrient_data(f) {
+                let mut nv = Nutrientdataview::create(nf);
                 nv.portion_value = match fdv.serving_size {
-                    Some(x) => (x as f64 / 100.0) * nv.value,
+                    Some(x) => (x / 100.0) * nv.value,
                     None => 0.0,
                 };
                 ndv.push(nv);
@@ -55,9 +61,7 @@ impl Foodview {
         }
         Ok(fv)
     }
-    /// creates a new food view from a food
-    pub fn create(f: &Food, context: &Context) -> Self {
-        let conn = context.db.get().unwrap();
+    pub fn create(f: &Food, details: &FoodDetails) -> Self {
         Self {
             publication_date: f.publication_date.format("%Y-%m-%d").to_string(),
             modified_date: f.modified_date.format("%Y-%m-%d").to_string(),
@@ -65,8 +69,10 @@ impl Foodview {
             upc: f.upc.to_string(),
             fdc_id: f.fdc_id.to_string(),
             description: f.description.to_string(),
-            food_group: f.get_food_group_name(&conn).unwrap(),
-            owner: f.get_owner_name(&conn).unwrap(),
+            food_group: details.food_group(f),
+            owner: details.owner(f),
+            brand: details.brand(f),
+            subbrand: details.subbrand(f),
             datasource: f.datasource.to_string(),
             serving_description: Some(
                 f.serving_description
@@ -93,7 +99,16 @@ impl Foodview {
                     .map(|n| n.to_string())
                     .unwrap_or("unknown".to_string()),
             ),
+            discontinued_date: f
+                .discontinued_date
+                .map(|d| d.format("%Y-%m-%d").to_string()),
+            package_weight: f.package_weight.clone(),
+            preparation_state_code: f.preparation_state_code.clone(),
+            trade_channel: f.trade_channel.clone(),
+            short_description: f.short_description.clone(),
+            fdc_release: f.fdc_release.clone(),
             nutrient_data: Vec::new(),
+            snippet: None,
         }
     }
 }
@@ -101,6 +116,12 @@ impl Foodview {
 pub struct Nutrientdataview {
     pub value: f64,
     pub portion_value: f64,
+    pub minimum: Option<f64>,
+    pub maximum: Option<f64>,
+    pub median: Option<f64>,
+    pub data_points: Option<i32>,
+    pub footnote: Option<String>,
+    pub min_year: Option<i32>,
     pub derivation: String,
     pub derivation_code: String,
     pub nutrient_no: String,
@@ -113,6 +134,12 @@ impl Nutrientdataview {
         Self {
             value: n.value,
             portion_value: 0.0,
+            minimum: n.minimum,
+            maximum: n.maximum,
+            median: n.median,
+            data_points: n.data_points,
+            footnote: n.footnote.clone(),
+            min_year: n.min_year,
             nutrient_no: n.nutrient_no.to_string(),
             nutrient: n.nutrient.to_string(),
             unit: n.unit.to_string(),
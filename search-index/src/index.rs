@@ -0,0 +1,295 @@
+//! The Tantivy index.  Each food is a document of its id, description,
+//! ingredients, brand owner and food group.  The text is stemmed so, e.g.,
+//! "peanuts" matches "peanut", and hits are ranked by BM25.
+use bfpd::cursor::Cursor;
+use bfpd::filter::FoodFilter;
+use bfpd::models::{Brand, Food, Foodgroup};
+use bfpd::search::{Field as SearchField, SearchQuery, Term as SearchTerm};
+use bfpd::Store;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use tantivy::collector::{Count, TopDocs};
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{
+    BooleanQuery, EmptyQuery, FuzzyTermQuery, Occur, PhraseQuery, Query, TermQuery,
+};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, FAST, INDEXED, STORED,
+};
+use tantivy::snippet::SnippetGenerator;
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+/// The foods read from the database at a time when building the index
+const BATCH: i64 = 1_000;
+/// The indexing memory budget
+const HEAP: usize = 50_000_000;
+/// The stemming tokenizer Tantivy registers for English
+const TOKENIZER: &str = "en_stem";
+
+/// A food search index in a directory
+pub struct FoodIndex {
+    index: Index,
+    reader: IndexReader,
+    id: Field,
+    description: Field,
+    ingredients: Field,
+    owner: Field,
+    food_group: Field,
+}
+impl FoodIndex {
+    /// Opens the index in a directory, creating it if need be
+    pub fn open(path: &Path) -> Result<FoodIndex, Box<dyn Error + Send + Sync>> {
+        fs::create_dir_all(path)?;
+        let index = Index::open_or_create(MmapDirectory::open(path)?, schema())?;
+        FoodIndex::create(index)
+    }
+    /// Creates an index held in memory, e.g. for testing
+    pub fn in_memory() -> Result<FoodIndex, Box<dyn Error + Send + Sync>> {
+        FoodIndex::create(Index::create_in_ram(schema()))
+    }
+    fn create(index: Index) -> Result<FoodIndex, Box<dyn Error + Send + Sync>> {
+        let s = index.schema();
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()?;
+        Ok(FoodIndex {
+            id: s.get_field("id")?,
+            description: s.get_field("description")?,
+            ingredients: s.get_field("ingredients")?,
+            owner: s.get_field("owner")?,
+            food_group: s.get_field("food_group")?,
+            index,
+            reader,
+        })
+    }
+    /// Replaces the index's documents with the foods in a store and returns the
+    /// number of foods indexed
+    pub fn rebuild<S: Store + ?Sized>(
+        &self,
+        db: &S,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let owners = all_owners(db)?;
+        let groups = all_food_groups(db)?;
+        let mut writer: IndexWriter = self.index.writer(HEAP)?;
+        writer.delete_all_documents()?;
+        let filter = FoodFilter::new();
+        let mut count = 0;
+        let mut cursor: Option<Cursor> = None;
+        loop {
+            let page = db.browse_page(
+                &filter,
+                BATCH,
+                cursor.as_ref(),
+                "id".to_string(),
+                "asc".to_string(),
+            )?;
+            for f in &page.items {
+                writer.add_document(self.document(f, &owners, &groups))?;
+            }
+            count += page.items.len();
+            cursor = match page.next {
+                Some(c) => Some(Cursor::decode(&c, "id", "asc")?),
+                None => break,
+            };
+        }
+        writer.commit()?;
+        self.reader.reload()?;
+        Ok(count)
+    }
+    fn document(
+        &self,
+        f: &Food,
+        owners: &HashMap<i32, String>,
+        groups: &HashMap<i32, String>,
+    ) -> TantivyDocument {
+        doc!(
+            self.id => f.id as i64,
+            self.description => f.description.as_str(),
+            self.ingredients => f.ingredients.as_deref().unwrap_or_default(),
+            self.owner => owners.get(&f.brand_id).map(|o| o.as_str()).unwrap_or_default(),
+            self.food_group => groups.get(&f.food_group_id).map(|g| g.as_str()).unwrap_or_default(),
+        )
+    }
+    /// Returns the ids of all the foods matching a search, most relevant first,
+    /// with their BM25 scores.  They're all returned so the database filters and
+    /// counts every hit rather than the most relevant.
+    pub fn search(
+        &self,
+        q: &SearchQuery,
+        fuzzy: bool,
+    ) -> Result<Vec<(i32, f64)>, Box<dyn Error + Send + Sync>> {
+        let searcher = self.reader.searcher();
+        let query = self.query(q, fuzzy, true)?;
+        let count = searcher.search(&query, &Count)?;
+        if count == 0 {
+            return Ok(Vec::new());
+        }
+        let docs = searcher.search(&query, &TopDocs::with_limit(count))?;
+        let mut hits = Vec::with_capacity(docs.len());
+        for (score, addr) in docs {
+            let ids = searcher
+                .segment_reader(addr.segment_ord)
+                .fast_fields()
+                .i64("id")?;
+            if let Some(id) = ids.first(addr.doc_id) {
+                hits.push((id as i32, score as f64));
+            }
+        }
+        Ok(hits)
+    }
+    /// Returns each food's description, or failing that its ingredients, with
+    /// the search's terms in bold, None if neither has them
+    pub fn highlight(
+        &self,
+        q: &SearchQuery,
+        foods: &[Food],
+    ) -> Result<Vec<Option<String>>, Box<dyn Error + Send + Sync>> {
+        let searcher = self.reader.searcher();
+        // excluded terms aren't highlighted
+        let query = self.query(q, false, false)?;
+        let description = SnippetGenerator::create(&searcher, &*query, self.description)?;
+        let ingredients = SnippetGenerator::create(&searcher, &*query, self.ingredients)?;
+        Ok(foods
+            .iter()
+            .map(|f| {
+                let s = description.snippet(&f.description);
+                if !s.is_empty() {
+                    return Some(s.to_html());
+                }
+                let s = ingredients.snippet(f.ingredients.as_deref().unwrap_or_default());
+                match s.is_empty() {
+                    true => None,
+                    false => Some(s.to_html()),
+                }
+            })
+            .collect())
+    }
+    /// Compiles a search:  every group must match and a group matches if any of
+    /// its terms do
+    fn query(
+        &self,
+        q: &SearchQuery,
+        fuzzy: bool,
+        exclude: bool,
+    ) -> Result<Box<dyn Query>, Box<dyn Error + Send + Sync>> {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for group in &q.groups {
+            if group[0].exclude {
+                if exclude {
+                    clauses.push((Occur::MustNot, self.term_query(&group[0], false)?));
+                }
+                continue;
+            }
+            let mut terms: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+            for t in group {
+                terms.push((Occur::Should, self.term_query(t, fuzzy)?));
+            }
+            clauses.push((Occur::Must, Box::new(BooleanQuery::new(terms))));
+        }
+        Ok(Box::new(BooleanQuery::new(clauses)))
+    }
+    /// Matches a term in its field or, if it has none, in any of the fields
+    fn term_query(
+        &self,
+        t: &SearchTerm,
+        fuzzy: bool,
+    ) -> Result<Box<dyn Query>, Box<dyn Error + Send + Sync>> {
+        let fields = match t.field {
+            Some(SearchField::Description) => vec![self.description],
+            Some(SearchField::Ingredients) => vec![self.ingredients],
+            None => vec![
+                self.description,
+                self.ingredients,
+                self.owner,
+                self.food_group,
+            ],
+        };
+        let mut queries: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for field in fields {
+            queries.push((Occur::Should, self.field_query(field, &t.words, fuzzy)?));
+        }
+        Ok(Box::new(BooleanQuery::new(queries)))
+    }
+    /// Matches a word, or a phrase of words, in a field.  The words are stemmed
+    /// as the field was.
+    fn field_query(
+        &self,
+        field: Field,
+        words: &[String],
+        fuzzy: bool,
+    ) -> Result<Box<dyn Query>, Box<dyn Error + Send + Sync>> {
+        let mut tokenizer = self.index.tokenizer_for_field(field)?;
+        let text = words.join(" ");
+        let mut stream = tokenizer.token_stream(&text);
+        let mut terms: Vec<Term> = Vec::new();
+        stream.process(&mut |t| terms.push(Term::from_field_text(field, &t.text)));
+        Ok(match terms.len() {
+            0 => Box::new(EmptyQuery),
+            1 if fuzzy => Box::new(FuzzyTermQuery::new(terms.remove(0), 1, true)),
+            1 => Box::new(TermQuery::new(
+                terms.remove(0),
+                IndexRecordOption::WithFreqs,
+            )),
+            _ => Box::new(PhraseQuery::new(terms)),
+        })
+    }
+}
+fn schema() -> Schema {
+    let text = TextOptions::default().set_indexing_options(
+        TextFieldIndexing::default()
+            .set_tokenizer(TOKENIZER)
+            .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+    );
+    let mut s = Schema::builder();
+    s.add_i64_field("id", INDEXED | STORED | FAST);
+    s.add_text_field("description", text.clone());
+    s.add_text_field("ingredients", text.clone());
+    s.add_text_field("owner", text.clone());
+    s.add_text_field("food_group", text);
+    s.build()
+}
+fn all_owners<S: Store + ?Sized>(
+    db: &S,
+) -> Result<HashMap<i32, String>, Box<dyn Error + Send + Sync>> {
+    let mut owners = HashMap::new();
+    let mut cursor: Option<Cursor> = None;
+    loop {
+        let page = db.browse_page(
+            &Brand::new(),
+            BATCH,
+            cursor.as_ref(),
+            "id".to_string(),
+            "asc".to_string(),
+        )?;
+        owners.extend(page.items.into_iter().map(|b| (b.id, b.owner)));
+        cursor = match page.next {
+            Some(c) => Some(Cursor::decode(&c, "id", "asc")?),
+            None => break,
+        };
+    }
+    Ok(owners)
+}
+fn all_food_groups<S: Store + ?Sized>(
+    db: &S,
+) -> Result<HashMap<i32, String>, Box<dyn Error + Send + Sync>> {
+    let mut groups = HashMap::new();
+    let mut cursor: Option<Cursor> = None;
+    loop {
+        let page = db.browse_page(
+            &Foodgroup::new(),
+            BATCH,
+            cursor.as_ref(),
+            "id".to_string(),
+            "asc".to_string(),
+        )?;
+        groups.extend(page.items.into_iter().map(|fg| (fg.id, fg.description)));
+        cursor = match page.next {
+            Some(c) => Some(Cursor::decode(&c, "id", "asc")?),
+            None => break,
+        };
+    }
+    Ok(groups)
+}
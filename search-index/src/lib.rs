@@ -0,0 +1,6 @@
+//! An optional Tantivy search index of the foods table.  IndexedStore wraps a
+//! database store and answers the query filter of food browses, counts and
+//! facets from the index, so searches are ranked and stemmed the same way
+//! whichever database holds the foods.
+pub mod index;
+pub mod store;
@@ -0,0 +1,379 @@
+//! IndexedStore, a Store whose food searches are answered by a FoodIndex
+use crate::index::FoodIndex;
+use bfpd::cursor::{Cursor, Page};
+use bfpd::facet::{FacetField, Facets};
+use bfpd::filter::{Basis, FoodFilter};
+use bfpd::histogram::{Bins, Histogram};
+use bfpd::loader::FoodDetails;
+use bfpd::models::{
+    Brand, Food, Foodgroup, Nutrient, Nutrientdata, NutrientdataForm, NutrientsReportForm,
+    ReportForm,
+};
+use bfpd::stats::{GroupBy, NutrientStats};
+use bfpd::suggest::Kind;
+use bfpd::{Browse, Count, Get, Store};
+use std::borrow::Cow;
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Runs everything but food searches on the database store it wraps.  A
+/// filter's search is replaced by the ids of the foods the index matches, which
+/// the database store reads from a table of them, so it applies the filter's
+/// other fields and ranks by the index's scores.
+pub struct IndexedStore {
+    pub db: Arc<dyn Store>,
+    pub index: FoodIndex,
+}
+impl IndexedStore {
+    /// Wraps a store with the index in a directory, creating it if need be
+    pub fn open(
+        db: Arc<dyn Store>,
+        path: &Path,
+    ) -> Result<IndexedStore, Box<dyn Error + Send + Sync>> {
+        Ok(IndexedStore {
+            db,
+            index: FoodIndex::open(path)?,
+        })
+    }
+    /// Returns the filter with its search replaced by the index's hits
+    fn resolve<'a>(
+        &self,
+        filter: &'a FoodFilter,
+    ) -> Result<Cow<'a, FoodFilter>, Box<dyn Error + Send + Sync>> {
+        match &filter.query {
+            None => Ok(Cow::Borrowed(filter)),
+            Some(q) => {
+                let mut f = filter.clone();
+                f.hits = Some(self.index.search(q, filter.fuzzy)?);
+                f.query = None;
+                Ok(Cow::Owned(f))
+            }
+        }
+    }
+}
+impl Get<Food> for IndexedStore {
+    type Item = Food;
+    fn get(&self, food: &Food) -> Result<Vec<Food>, Box<dyn Error + Send + Sync>> {
+        self.db.get(food)
+    }
+}
+impl Browse<FoodFilter> for IndexedStore {
+    type Item = Food;
+    fn browse(
+        &self,
+        filter: &FoodFilter,
+        max: i64,
+        off: i64,
+        sort: String,
+        order: String,
+    ) -> Result<Vec<Food>, Box<dyn Error + Send + Sync>> {
+        self.db
+            .browse(&*self.resolve(filter)?, max, off, sort, order)
+    }
+    fn browse_page(
+        &self,
+        filter: &FoodFilter,
+        max: i64,
+        cursor: Option<&Cursor>,
+        sort: String,
+        order: String,
+    ) -> Result<Page<Food>, Box<dyn Error + Send + Sync>> {
+        self.db
+            .browse_page(&*self.resolve(filter)?, max, cursor, sort, order)
+    }
+}
+impl Count<FoodFilter> for IndexedStore {
+    fn query_count(&self, filter: &FoodFilter) -> Result<i64, Box<dyn Error + Send + Sync>> {
+        self.db.query_count(&*self.resolve(filter)?)
+    }
+}
+impl Get<Brand> for IndexedStore {
+    type Item = Brand;
+    fn get(&self, b: &Brand) -> Result<Vec<Brand>, Box<dyn Error + Send + Sync>> {
+        self.db.get(b)
+    }
+}
+impl Browse<Brand> for IndexedStore {
+    type Item = Brand;
+    fn browse(
+        &self,
+        b: &Brand,
+        max: i64,
+        off: i64,
+        sort: String,
+        order: String,
+    ) -> Result<Vec<Brand>, Box<dyn Error + Send + Sync>> {
+        self.db.browse(b, max, off, sort, order)
+    }
+    fn browse_page(
+        &self,
+        b: &Brand,
+        max: i64,
+        cursor: Option<&Cursor>,
+        sort: String,
+        order: String,
+    ) -> Result<Page<Brand>, Box<dyn Error + Send + Sync>> {
+        self.db.browse_page(b, max, cursor, sort, order)
+    }
+}
+impl Get<Foodgroup> for IndexedStore {
+    type Item = Foodgroup;
+    fn get(&self, fg: &Foodgroup) -> Result<Vec<Foodgroup>, Box<dyn Error + Send + Sync>> {
+        self.db.get(fg)
+    }
+}
+impl Browse<Foodgroup> for IndexedStore {
+    type Item = Foodgroup;
+    fn browse(
+        &self,
+        fg: &Foodgroup,
+        max: i64,
+        off: i64,
+        sort: String,
+        order: String,
+    ) -> Result<Vec<Foodgroup>, Box<dyn Error + Send + Sync>> {
+        self.db.browse(fg, max, off, sort, order)
+    }
+    fn browse_page(
+        &self,
+        fg: &Foodgroup,
+        max: i64,
+        cursor: Option<&Cursor>,
+        sort: String,
+        order: String,
+    ) -> Result<Page<Foodgroup>, Box<dyn Error + Send + Sync>> {
+        self.db.browse_page(fg, max, cursor, sort, order)
+    }
+}
+impl Get<Nutrient> for IndexedStore {
+    type Item = Nutrient;
+    fn get(&self, n: &Nutrient) -> Result<Vec<Nutrient>, Box<dyn Error + Send + Sync>> {
+        self.db.get(n)
+    }
+}
+impl Browse<Nutrient> for IndexedStore {
+    type Item = Nutrient;
+    fn browse(
+        &self,
+        n: &Nutrient,
+        max: i64,
+        off: i64,
+        sort: String,
+        order: String,
+    ) -> Result<Vec<Nutrient>, Box<dyn Error + Send + Sync>> {
+        self.db.browse(n, max, off, sort, order)
+    }
+    fn browse_page(
+        &self,
+        n: &Nutrient,
+        max: i64,
+        cursor: Option<&Cursor>,
+        sort: String,
+        order: String,
+    ) -> Result<Page<Nutrient>, Box<dyn Error + Send + Sync>> {
+        self.db.browse_page(n, max, cursor, sort, order)
+    }
+}
+impl Browse<Nutrientdata> for IndexedStore {
+    type Item = Nutrientdata;
+    fn browse(
+        &self,
+        nd: &Nutrientdata,
+        max: i64,
+        off: i64,
+        sort: String,
+        order: String,
+    ) -> Result<Vec<Nutrientdata>, Box<dyn Error + Send + Sync>> {
+        self.db.browse(nd, max, off, sort, order)
+    }
+    fn browse_page(
+        &self,
+        nd: &Nutrientdata,
+        max: i64,
+        cursor: Option<&Cursor>,
+        sort: String,
+        order: String,
+    ) -> Result<Page<Nutrientdata>, Box<dyn Error + Send + Sync>> {
+        self.db.browse_page(nd, max, cursor, sort, order)
+    }
+}
+impl Store for IndexedStore {
+    fn nutrient_data(
+        &self,
+        food: &Food,
+        nids: &[String],
+    ) -> Result<Vec<NutrientdataForm>, Box<dyn Error + Send + Sync>> {
+        self.db.nutrient_data(food, nids)
+    }
+    fn food_details(
+        &self,
+        foods: &[Food],
+        nids: &[String],
+    ) -> Result<FoodDetails, Box<dyn Error + Send + Sync>> {
+        self.db.food_details(foods, nids)
+    }
+    fn similar_owners(
+        &self,
+        owner: &str,
+        max: i64,
+    ) -> Result<Vec<(Brand, f64)>, Box<dyn Error + Send + Sync>> {
+        self.db.similar_owners(owner, max)
+    }
+    fn similar_food_groups(
+        &self,
+        group: &str,
+        max: i64,
+    ) -> Result<Vec<(Foodgroup, f64)>, Box<dyn Error + Send + Sync>> {
+        self.db.similar_food_groups(group, max)
+    }
+    fn suggest(
+        &self,
+        prefix: &str,
+        kind: Kind,
+        max: i64,
+    ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+        self.db.suggest(prefix, kind, max)
+    }
+    fn facets(
+        &self,
+        filter: &FoodFilter,
+        fields: &[FacetField],
+        max: i64,
+    ) -> Result<Facets, Box<dyn Error + Send + Sync>> {
+        self.db.facets(&*self.resolve(filter)?, fields, max)
+    }
+    fn highlights(
+        &self,
+        filter: &FoodFilter,
+        foods: &[Food],
+    ) -> Result<Vec<Option<String>>, Box<dyn Error + Send + Sync>> {
+        match &filter.query {
+            Some(q) => self.index.highlight(q, foods),
+            None => Ok(vec![None; foods.len()]),
+        }
+    }
+    fn report(
+        &self,
+        max: i64,
+        off: i64,
+        sort: String,
+        ord: String,
+        min: f64,
+        mx: f64,
+        nid: i32,
+    ) -> Result<Vec<ReportForm>, Box<dyn Error + Send + Sync>> {
+        self.db.report(max, off, sort, ord, min, mx, nid)
+    }
+    fn nutrients_report(
+        &self,
+        filter: &FoodFilter,
+        sort: &str,
+        order: &str,
+        max: i64,
+        off: i64,
+    ) -> Result<Vec<NutrientsReportForm>, Box<dyn Error + Send + Sync>> {
+        self.db
+            .nutrients_report(&*self.resolve(filter)?, sort, order, max, off)
+    }
+    fn nutrient_stats(
+        &self,
+        filter: &FoodFilter,
+        nutrientno: &str,
+        by: GroupBy,
+        percentiles: &[f64],
+        max: i64,
+    ) -> Result<Vec<NutrientStats>, Box<dyn Error + Send + Sync>> {
+        self.db
+            .nutrient_stats(&*self.resolve(filter)?, nutrientno, by, percentiles, max)
+    }
+    fn histogram(
+        &self,
+        filter: &FoodFilter,
+        nutrientno: &str,
+        basis: Basis,
+        bins: &Bins,
+        log: bool,
+    ) -> Result<Histogram, Box<dyn Error + Send + Sync>> {
+        self.db
+            .histogram(&*self.resolve(filter)?, nutrientno, basis, bins, log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bfpd::cache::Cache;
+    use bfpd::csv::Ingest;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn search_index() {
+        let path = env::temp_dir().join(format!("bfpd-index-test-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let db = sqlite::db::connect(&format!("sqlite://{}", path.display()));
+        let mut cache = Cache::load(&db).unwrap();
+        cache.brand_id("Acme", None, None, &db).unwrap();
+        cache.food_group_id("Spreads", &db).unwrap();
+        let mut foods = Vec::new();
+        for (fdc, d, i) in &[
+            ("1", "CRUNCHY PEANUT BUTTER", "PEANUTS, SALT"),
+            ("2", "STRAWBERRY JAM", "STRAWBERRIES, SUGAR"),
+            (
+                "3",
+                "PEANUT BUTTER CUPS",
+                "PEANUT BUTTER, PEANUTS, PEANUT OIL",
+            ),
+        ] {
+            let mut f = Food::new();
+            f.fdc_id = fdc.to_string();
+            f.upc = format!("00000{}", fdc);
+            f.description = d.to_string();
+            f.ingredients = Some(i.to_string());
+            f.brand_id = 1;
+            f.food_group_id = 1;
+            f.datasource = "LI".to_string();
+            foods.push(f);
+        }
+        db.insert_foods(&foods).unwrap();
+        let db = IndexedStore {
+            db: Arc::new(db),
+            index: FoodIndex::in_memory().unwrap(),
+        };
+        assert_eq!(db.index.rebuild(&*db.db).unwrap(), 3);
+        let mut q = FoodFilter::new();
+        // stemmed, so strawberry matches strawberries
+        q.search("strawberries").unwrap();
+        assert_eq!(db.query_count(&q).unwrap(), 1);
+        // brands and food groups are indexed too
+        q.search("acme jam").unwrap();
+        assert_eq!(db.query_count(&q).unwrap(), 1);
+        q.search("\"butter crunchy\"").unwrap();
+        assert_eq!(db.query_count(&q).unwrap(), 0);
+        q.search("peanut -salt").unwrap();
+        assert_eq!(db.query_count(&q).unwrap(), 1);
+        q.search("peanut buter").unwrap();
+        assert_eq!(db.query_count(&q).unwrap(), 0);
+        q.fuzzy = true;
+        assert_eq!(db.query_count(&q).unwrap(), 2);
+        q.fuzzy = false;
+        // the food mentioning peanuts most is the most relevant
+        q.search("peanut").unwrap();
+        let (sort, order) = ("relevance".to_string(), "desc".to_string());
+        let p = db
+            .browse_page(&q, 1, None, sort.clone(), order.clone())
+            .unwrap();
+        assert_eq!(p.items[0].fdc_id, "3");
+        let next = Cursor::decode(&p.next.unwrap(), &sort, &order).unwrap();
+        let p = db.browse_page(&q, 1, Some(&next), sort, order).unwrap();
+        assert_eq!(p.items[0].fdc_id, "1");
+        assert!(p.next.is_none());
+        q.datasource = Some("GDSN".to_string());
+        assert_eq!(db.query_count(&q).unwrap(), 0);
+        q.datasource = None;
+        let h = db.highlights(&q, &p.items).unwrap();
+        assert_eq!(h, vec![Some("CRUNCHY <b>PEANUT</b> BUTTER".to_string())]);
+        let _ = fs::remove_file(&path);
+    }
+}
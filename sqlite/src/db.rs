@@ -0,0 +1,152 @@
+extern crate diesel;
+
+use diesel::connection::SimpleConnection;
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, CustomizeConnection, Pool, PoolError};
+use diesel::sql_types::{Bool, Text};
+use diesel::sqlite::SqliteConnection;
+
+pub type SqlitePool = Pool<ConnectionManager<SqliteConnection>>;
+
+/// The schema is applied when a database is opened so a new file is ready to load
+const SCHEMA: &str = include_str!("../../data/sqlite/up.sql");
+
+/// The columns added to the schema since the first files were created, which an
+/// earlier file is missing, with their definitions
+const ADDED_COLUMNS: [(&str, &str, &str); 10] = [
+    ("foods", "discontinued_date", "TIMESTAMP DEFAULT NULL"),
+    ("foods", "package_weight", "VARCHAR(64) DEFAULT NULL"),
+    (
+        "foods",
+        "preparation_state_code",
+        "VARCHAR(64) DEFAULT NULL",
+    ),
+    ("foods", "trade_channel", "VARCHAR(256) DEFAULT NULL"),
+    ("foods", "short_description", "VARCHAR(256) DEFAULT NULL"),
+    ("foods", "fdc_release", "VARCHAR(64) DEFAULT NULL"),
+    ("nutrient_data", "data_points", "INTEGER DEFAULT NULL"),
+    ("nutrient_data", "footnote", "TEXT DEFAULT NULL"),
+    ("nutrient_data", "min_year", "INTEGER DEFAULT NULL"),
+    ("nutrient_data", "fdc_release", "VARCHAR(64) DEFAULT NULL"),
+];
+
+/// SqliteStore runs the bfpd queries against a SQLite database file
+#[derive(Clone)]
+pub struct SqliteStore {
+    pub pool: SqlitePool,
+}
+
+/// Wait on a database locked by another connection, e.g. the ingest, rather than fail
+#[derive(Debug)]
+struct BusyTimeout;
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for BusyTimeout {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        conn.batch_execute("PRAGMA busy_timeout = 5000;")
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
+}
+
+fn init(database_url: &str) -> Result<SqlitePool, PoolError> {
+    let manager = ConnectionManager::<SqliteConnection>::new(database_url);
+    Pool::builder()
+        .connection_customizer(Box::new(BusyTimeout))
+        .build(manager)
+}
+
+/// Opens the database file named by a sqlite://path/to/bfpd.db url, creating
+/// the file and schema if need be
+pub fn connect(database_url: &str) -> SqliteStore {
+    let path = database_url.trim_start_matches("sqlite://");
+    let store = SqliteStore {
+        pool: init(path).expect("Error"),
+    };
+    let conn = store.pool.get().expect("Error");
+    let trigrams = diesel::select(sql::<Bool>(
+        "EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'foods_trigrams')",
+    ))
+    .get_result::<bool>(&conn)
+    .expect("Error reading schema");
+    conn.batch_execute(SCHEMA).expect("Error creating schema");
+    upgrade(&conn).expect("Error upgrading schema");
+    // a file from before the trigram index has foods to index
+    if !trigrams {
+        conn.batch_execute("INSERT INTO foods_trigrams (foods_trigrams) VALUES ('rebuild');")
+            .expect("Error indexing trigrams");
+    }
+    store
+}
+/// Brings a file created from an earlier schema up to date with it:  the
+/// columns added since are added and fdc_id's index is made unique, which fails
+/// if a food was loaded twice.  The schema's new tables are created with it.
+fn upgrade(conn: &SqliteConnection) -> QueryResult<()> {
+    for (table, column, definition) in ADDED_COLUMNS.iter() {
+        let exists = diesel::select(
+            sql::<Bool>("EXISTS (SELECT 1 FROM pragma_table_info(")
+                .bind::<Text, _>(table)
+                .sql(") WHERE name = ")
+                .bind::<Text, _>(column)
+                .sql(")"),
+        )
+        .get_result::<bool>(conn)?;
+        if !exists {
+            conn.batch_execute(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {};",
+                table, column, definition
+            ))?;
+        }
+    }
+    let unique = diesel::select(sql::<Bool>(
+        "EXISTS (SELECT 1 FROM pragma_index_list('foods') \
+         WHERE name = 'foods_fdc_id_idx' AND \"unique\" = 1)",
+    ))
+    .get_result::<bool>(conn)?;
+    if !unique {
+        conn.batch_execute(
+            "DROP INDEX foods_fdc_id_idx;
+             CREATE UNIQUE INDEX foods_fdc_id_idx ON foods (fdc_id);",
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn upgrade_schema() {
+        let path = env::temp_dir().join(format!("bfpd-upgrade-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let url = format!("sqlite://{}", path.display());
+        // a file from before fdc_id was unique and the releases were kept
+        connect(&url)
+            .pool
+            .get()
+            .unwrap()
+            .batch_execute(
+                "DROP INDEX foods_fdc_id_idx;
+                 CREATE INDEX foods_fdc_id_idx ON foods (fdc_id);
+                 ALTER TABLE foods DROP COLUMN fdc_release;
+                 ALTER TABLE nutrient_data DROP COLUMN fdc_release;
+                 DROP TABLE ingest_checkpoints;",
+            )
+            .unwrap();
+        let conn = connect(&url).pool.get().unwrap();
+        let columns = sql::<Bool>(
+            "(SELECT count(*) FROM pragma_table_info('foods') WHERE name = 'fdc_release') + \
+             (SELECT count(*) FROM pragma_table_info('nutrient_data') WHERE name = 'fdc_release') + \
+             (SELECT count(*) FROM pragma_table_info('ingest_checkpoints')) = 6",
+        );
+        assert!(diesel::select(columns).get_result::<bool>(&conn).unwrap());
+        let unique = "SELECT \"unique\" = 1 FROM pragma_index_list('foods') \
+                      WHERE name = 'foods_fdc_id_idx'";
+        assert!(diesel::select(sql::<Bool>(&format!("({})", unique)))
+            .get_result::<bool>(&conn)
+            .unwrap());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod db;
+pub mod store;
+extern crate diesel;
+extern crate dotenv;
@@ -0,0 +1,822 @@
+//! Runs the bfpd queries on SQLite
+use crate::db::SqliteStore;
+use bfpd::adapter::Dialect;
+use bfpd::filter::FoodFilter;
+use bfpd::fuzzy;
+use bfpd::models::{Brand, Foodgroup};
+use bfpd::query::{self, FullText, Rank, Search};
+use bfpd::search::{SearchQuery, Term};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Double, Text};
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use std::error::Error;
+
+/// full-text search on the foods_fts index of the food description and
+/// ingredients, ranked by bm25.  A fuzzy search also matches descriptions by
+/// their trigrams, of the foods the foods_trigrams index finds with one of them.
+struct Fts;
+impl FullText<Sqlite> for Fts {
+    fn search(&self, filter: &FoodFilter) -> Option<Search<'static, Sqlite>> {
+        let q = filter.query.as_ref()?;
+        let fts = fts_match(fts_query(q));
+        if !filter.fuzzy {
+            return Some(fts);
+        }
+        // FTS5 takes the whole search so misspellings are matched separately,
+        // term by term, and the excluded terms are left out of both again
+        let (excluded, included): (Vec<Vec<Term>>, Vec<Vec<Term>>) =
+            q.groups.iter().cloned().partition(|g| g[0].exclude);
+        let typos = query::search_predicate(&SearchQuery { groups: included }, |t| {
+            match t.in_description() {
+                true => query::ngram_description(t, trigrams(t)),
+                false => Box::new(sql::<Bool>("0")),
+            }
+        });
+        let found: Search<'static, Sqlite> = match typos {
+            Some(typos) => Box::new(fts.or(typos)),
+            None => fts,
+        };
+        match query::search_predicate(&SearchQuery { groups: excluded }, |t| {
+            fts_match(fts_term(t))
+        }) {
+            Some(exclusions) => Some(Box::new(found.and(exclusions))),
+            None => Some(found),
+        }
+    }
+    /// bm25 scores better matches lower so it's negated
+    fn rank(&self, filter: &FoodFilter) -> Option<Rank<'static, Sqlite>> {
+        let q = filter.query.as_ref()?;
+        Some(Box::new(
+            sql::<Double>("(SELECT -bm25(foods_fts) FROM foods_fts WHERE foods_fts MATCH ")
+                .bind::<Text, _>(fts_query(q))
+                .sql(" AND foods_fts.rowid = foods.id)"),
+        ))
+    }
+}
+/// Matches the descriptions with any of the trigrams of a term's words
+fn trigrams(t: &Term) -> Search<'static, Sqlite> {
+    let trigrams: Vec<String> = fuzzy::text_patterns(&t.words.join(" "))
+        .iter()
+        .map(|p| format!("\"{}\"", p.trim_matches('%')))
+        .collect();
+    Box::new(
+        sql::<Bool>("foods.id IN (SELECT rowid FROM foods_trigrams WHERE foods_trigrams MATCH ")
+            .bind::<Text, _>(trigrams.join(" OR "))
+            .sql(")"),
+    )
+}
+/// Matches the foods an FTS5 query finds
+fn fts_match(fts: String) -> Search<'static, Sqlite> {
+    Box::new(
+        sql::<Bool>("foods.id IN (SELECT rowid FROM foods_fts WHERE foods_fts MATCH ")
+            .bind::<Text, _>(fts)
+            .sql(")"),
+    )
+}
+/// Quotes a term as an FTS5 phrase, in its column if it has a field
+fn fts_term(t: &Term) -> String {
+    let phrase = format!("\"{}\"", t.words.join(" "));
+    match t.field {
+        Some(f) => format!("{} : {}", f.column(), phrase),
+        None => phrase,
+    }
+}
+/// Compiles a search to an FTS5 query.  Each term is quoted so FTS5 reads it
+/// as a phrase rather than query syntax, e.g. `(ingredients : "palm") NOT "sugar"`.
+fn fts_query(q: &SearchQuery) -> String {
+    let (excluded, groups): (Vec<&Vec<Term>>, Vec<&Vec<Term>>) =
+        q.groups.iter().partition(|g| g[0].exclude);
+    let mut fts = groups
+        .iter()
+        .map(|g| {
+            format!(
+                "({})",
+                g.iter().map(fts_term).collect::<Vec<String>>().join(" OR ")
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" AND ");
+    for g in excluded {
+        fts = format!("({}) NOT {}", fts, fts_term(&g[0]));
+    }
+    fts
+}
+
+bfpd::diesel_store!(SqliteStore, Sqlite, Fts);
+
+/// suggest matches prefixes as they are:  LIKE ignores case and the columns have NOCASE
+/// indexes
+impl Dialect for SqliteStore {
+    type Connection = SqliteConnection;
+    fn owners_like(
+        &self,
+        owner: &str,
+        max: i64,
+    ) -> Result<Vec<(Brand, f64)>, Box<dyn Error + Send + Sync>> {
+        let conn = self.pool.get()?;
+        let rows = query::owner_candidates::<Sqlite>(owner).load::<Brand>(&conn)?;
+        Ok(fuzzy::closest(owner, rows, |b| &b.owner, max as usize))
+    }
+    fn food_groups_like(
+        &self,
+        group: &str,
+        max: i64,
+    ) -> Result<Vec<(Foodgroup, f64)>, Box<dyn Error + Send + Sync>> {
+        let conn = self.pool.get()?;
+        let rows = query::food_group_candidates::<Sqlite>(group).load::<Foodgroup>(&conn)?;
+        Ok(fuzzy::closest(
+            group,
+            rows,
+            |fg| &fg.description,
+            max as usize,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fts_query;
+    use super::SqliteStore;
+    use crate::db::connect;
+    use bfpd::cache::Cache;
+    use bfpd::csv::{
+        process_derivations, process_foods, process_nutdata, process_nutrients, update_foods,
+        update_nutdata, Ingest, UpdateReport, BATCH_SIZE, FOODS, NUTRIENT_DATA,
+    };
+    use bfpd::cursor::Cursor;
+    use bfpd::facet::{FacetCount, FacetField};
+    use bfpd::filter::{Basis, FoodFilter, NutrientRange};
+    use bfpd::fixtures::{CsvDir, NUTDATA_HEADER, NUTRIENT_HEADER, PROTEIN};
+    use bfpd::fuzzy::NoMatchError;
+    use bfpd::histogram::Bins;
+    use bfpd::models::{Brand, Derivation, Food, Foodgroup, Nutrient, Nutrientdata};
+    use bfpd::reject::Rejects;
+    use bfpd::schema::nutrient_data;
+    use bfpd::search::SearchQuery;
+    use bfpd::stats::GroupBy;
+    use bfpd::suggest::Kind;
+    use bfpd::{Browse, Count, Store};
+    use diesel::connection::SimpleConnection;
+    use diesel::prelude::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    #[test]
+    fn compile_fts_query() {
+        let q =
+            SearchQuery::parse("\"whole wheat\" -sugar oat OR barley ingredients:palm").unwrap();
+        assert_eq!(
+            fts_query(&q),
+            "((\"whole wheat\") AND (\"oat\" OR \"barley\") AND (ingredients : \"palm\")) NOT \"sugar\""
+        );
+    }
+    /// A database file of a test's own, removed when it's dropped
+    struct TestFile(PathBuf);
+    impl TestFile {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("bfpd-{}-test-{}.db", name, process::id()));
+            let _ = fs::remove_file(&path);
+            TestFile(path)
+        }
+        fn connect(&self) -> SqliteStore {
+            connect(&format!("sqlite://{}", self.0.display()))
+        }
+    }
+    impl Drop for TestFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+    /// Returns a test's database of Acme's crunchy peanut butter, fdc_id 1, and
+    /// strawberry jam, 2, both spreads
+    fn spreads(name: &str) -> (TestFile, SqliteStore, Cache) {
+        let file = TestFile::new(name);
+        let db = file.connect();
+        let mut cache = Cache::load(&db).unwrap();
+        assert_eq!(cache.brand_id("Acme", None, None, &db).unwrap(), 1);
+        assert_eq!(cache.food_group_id("Spreads", &db).unwrap(), 1);
+        let mut foods = Vec::new();
+        for (fdc, d, i) in &[
+            ("1", "CRUNCHY PEANUT BUTTER", "PEANUTS, SALT"),
+            ("2", "STRAWBERRY JAM", "STRAWBERRIES, SUGAR"),
+        ] {
+            let mut f = Food::new();
+            f.fdc_id = fdc.to_string();
+            f.upc = format!("00000{}", fdc);
+            f.description = d.to_string();
+            f.ingredients = Some(i.to_string());
+            f.brand_id = 1;
+            f.food_group_id = 1;
+            f.datasource = "LI".to_string();
+            foods.push(f);
+        }
+        assert_eq!(db.insert_foods(&foods).unwrap(), 2);
+        (file, db, cache)
+    }
+    /// Adds peanut butter cups, fdc_id 3, sold under Acme's Crunch brand and
+    /// Cups sub-brand
+    fn add_cups(db: &SqliteStore, cache: &mut Cache) {
+        let (brand, subbrand) = (Some("Crunch".to_string()), Some("Cups".to_string()));
+        cache.brand_id("Acme", brand, subbrand, db).unwrap();
+        let mut b = Brand::new();
+        b.owner = "Acme".to_string();
+        b.brand = Some("Crunch".to_string());
+        b.subbrand = Some("Cups".to_string());
+        let mut f = Food::new();
+        f.fdc_id = "3".to_string();
+        f.upc = "000003".to_string();
+        f.description = "PEANUT BUTTER CUPS".to_string();
+        f.ingredients = Some("PEANUT BUTTER, PEANUTS, PEANUT OIL".to_string());
+        f.brand_id = b.find_by_brand(db).unwrap().id;
+        f.food_group_id = 1;
+        f.datasource = "LI".to_string();
+        db.insert_foods(&[f]).unwrap();
+    }
+    #[test]
+    fn search_grammar() {
+        let (_file, db, _) = spreads("grammar");
+        let mut q = FoodFilter::new();
+        q.search("peanut butter").unwrap();
+        let found = db
+            .browse(&q, 10, 0, "id".to_string(), "asc".to_string())
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].fdc_id, "1");
+        assert_eq!(db.query_count(&q).unwrap(), 1);
+        q.search("peanuts").unwrap();
+        assert_eq!(db.query_count(&q).unwrap(), 1);
+        q.search("sugar").unwrap();
+        assert_eq!(db.query_count(&q).unwrap(), 1);
+        // phrases, exclusions, OR and fields
+        for (search, count) in &[
+            ("\"peanut butter\"", 1),
+            ("\"butter peanut\"", 0),
+            ("butter -salt", 0),
+            ("jam OR butter", 2),
+            ("ingredients:sugar", 1),
+            ("description:sugar", 0),
+        ] {
+            q.search(search).unwrap();
+            assert_eq!(db.query_count(&q).unwrap(), *count, "{}", search);
+        }
+        // the search's other fields filter too
+        q.query = None;
+        assert_eq!(db.query_count(&q).unwrap(), 2);
+        q.datasource = Some("GDSN".to_string());
+        assert_eq!(db.query_count(&q).unwrap(), 0);
+    }
+    #[test]
+    fn fuzzy_search() {
+        let (file, db, _) = spreads("fuzzy");
+        // misspellings match when fuzzy
+        let mut q = FoodFilter::new();
+        q.search("peanut buter").unwrap();
+        assert_eq!(db.query_count(&q).unwrap(), 0);
+        q.fuzzy = true;
+        assert_eq!(db.query_count(&q).unwrap(), 1);
+        // and still leave out excluded terms
+        q.search("peanut buter -salt").unwrap();
+        assert_eq!(db.query_count(&q).unwrap(), 0);
+        q.search("peanut buter -sugar").unwrap();
+        assert_eq!(db.query_count(&q).unwrap(), 1);
+        // in a file from before the trigram index too
+        db.pool
+            .get()
+            .unwrap()
+            .batch_execute("DROP TABLE foods_trigrams;")
+            .unwrap();
+        let db = file.connect();
+        assert_eq!(db.query_count(&q).unwrap(), 1);
+    }
+    #[test]
+    fn similar_owners() {
+        let (_file, db, mut cache) = spreads("owners");
+        // misspelt brand owners are found, or there are suggestions, one an owner
+        cache
+            .brand_id("General Mills, Inc.", None, None, &db)
+            .unwrap();
+        cache
+            .brand_id(
+                "General Mills, Inc.",
+                Some("Cheerios".to_string()),
+                None,
+                &db,
+            )
+            .unwrap();
+        let mut b = Brand::new();
+        b.owner = "Genral Mills, Inc.".to_string();
+        assert_eq!(
+            b.find_similar_owner(&db).unwrap().owner,
+            "General Mills, Inc."
+        );
+        b.owner = "General Foods".to_string();
+        let e = b.find_similar_owner(&db).unwrap_err();
+        let e = e.downcast_ref::<NoMatchError>().unwrap();
+        assert_eq!(e.suggestions, vec!["General Mills, Inc.".to_string()]);
+    }
+    #[test]
+    fn cursor_paging() {
+        let (_file, db, mut cache) = spreads("paging");
+        // page through by description one food at a time and back again
+        let mut q = FoodFilter::new();
+        let (sort, order) = ("description".to_string(), "desc".to_string());
+        let p = db
+            .browse_page(&q, 1, None, sort.clone(), order.clone())
+            .unwrap();
+        assert_eq!(p.items[0].fdc_id, "2");
+        assert!(p.prev.is_none());
+        let next = Cursor::decode(&p.next.unwrap(), &sort, &order).unwrap();
+        let p = db
+            .browse_page(&q, 1, Some(&next), sort.clone(), order.clone())
+            .unwrap();
+        assert_eq!(p.items[0].fdc_id, "1");
+        assert!(p.next.is_none());
+        let prev = Cursor::decode(&p.prev.unwrap(), &sort, &order).unwrap();
+        let p = db.browse_page(&q, 1, Some(&prev), sort, order).unwrap();
+        assert_eq!(p.items[0].fdc_id, "2");
+        assert!(p.prev.is_none());
+        assert!(p.next.is_some());
+        // the more often a term appears the more relevant the food
+        add_cups(&db, &mut cache);
+        q.search("peanut").unwrap();
+        let (sort, order) = ("relevance".to_string(), "desc".to_string());
+        let p = db
+            .browse_page(&q, 1, None, sort.clone(), order.clone())
+            .unwrap();
+        assert_eq!(p.items[0].fdc_id, "3");
+        let next = Cursor::decode(&p.next.unwrap(), &sort, &order).unwrap();
+        let p = db.browse_page(&q, 1, Some(&next), sort, order).unwrap();
+        assert_eq!(p.items[0].fdc_id, "1");
+        assert!(p.next.is_none());
+    }
+    #[test]
+    fn brands_and_details() {
+        let (_file, db, mut cache) = spreads("brands");
+        let (b, fg) = (Brand::new(), Foodgroup::new());
+        let all = || ("".to_string(), "".to_string());
+        assert_eq!(db.browse(&b, 10, 0, all().0, all().1).unwrap().len(), 1);
+        assert_eq!(db.browse(&fg, 10, 0, all().0, all().1).unwrap().len(), 1);
+        add_cups(&db, &mut cache);
+        // a brand is found by its owner, name and sub-brand
+        let mut b = Brand::new();
+        b.owner = "Acme".to_string();
+        b.brand = Some("Crunch".to_string());
+        assert!(b.find_by_brand(&db).is_err());
+        // a page's owners and groups come from one load
+        let mut q = FoodFilter::new();
+        let all = db
+            .browse(&q, 10, 0, "id".to_string(), "asc".to_string())
+            .unwrap();
+        let details = db.food_details(&all, &[]).unwrap();
+        assert_eq!(details.owner(&all[1]), "Acme");
+        assert_eq!(details.owner(&all[2]), "Acme");
+        assert_eq!(details.brand(&all[2]), Some("Crunch".to_string()));
+        assert_eq!(details.subbrand(&all[1]), None);
+        assert_eq!(details.food_group(&all[0]), "Spreads");
+        assert!(details.nutrient_data(&all[0]).is_empty());
+        // an owner's foods are those of all its brands
+        q.owner = Some("Acme".to_string());
+        assert_eq!(db.query_count(&q).unwrap(), 3);
+        q.brand = Some("Crunch".to_string());
+        assert_eq!(db.query_count(&q).unwrap(), 1);
+        q.subbrand = Some("Bars".to_string());
+        assert_eq!(db.query_count(&q).unwrap(), 0);
+    }
+    #[test]
+    fn food_facets() {
+        let (_file, db, mut cache) = spreads("facets");
+        add_cups(&db, &mut cache);
+        // facets count all the foods the filter matches
+        let mut q = FoodFilter::new();
+        let facets = db.facets(&q, &FacetField::ALL, 10).unwrap();
+        let counts = |f: Option<Vec<FacetCount>>| -> Vec<(String, i64)> {
+            f.unwrap().into_iter().map(|c| (c.value, c.count)).collect()
+        };
+        assert_eq!(counts(facets.owners), vec![("Acme".to_string(), 3)]);
+        assert_eq!(counts(facets.countries), vec![("unknown".to_string(), 3)]);
+        assert_eq!(counts(facets.food_groups), vec![("Spreads".to_string(), 3)]);
+        q.search("jam OR cups").unwrap();
+        let facets = db.facets(&q, &[FacetField::Datasource], 10).unwrap();
+        assert_eq!(counts(facets.datasources), vec![("LI".to_string(), 2)]);
+        assert!(facets.food_groups.is_none());
+    }
+    #[test]
+    fn suggest_completions() {
+        let (_file, db, mut cache) = spreads("suggest");
+        add_cups(&db, &mut cache);
+        cache
+            .brand_id("General Mills, Inc.", None, None, &db)
+            .unwrap();
+        // completions are of prefixes, case insensitive, shortest first
+        assert_eq!(
+            db.suggest("peanut", Kind::Food, 5).unwrap(),
+            vec!["PEANUT BUTTER CUPS".to_string()]
+        );
+        assert_eq!(
+            db.suggest("gen", Kind::Owner, 5).unwrap(),
+            vec!["General Mills, Inc.".to_string()]
+        );
+        assert!(db.suggest("100%", Kind::Group, 5).unwrap().is_empty());
+    }
+    #[test]
+    fn index_hits() {
+        let (_file, db, _) = spreads("hits");
+        // more hits than SQLite allows bind parameters, most of them foods that
+        // aren't loaded
+        let mut q = FoodFilter::new();
+        let mut hits: Vec<(i32, f64)> = (4..40_000).map(|id| (id, 1.0)).collect();
+        hits.extend(vec![(2, 2.5), (1, 3.0)]);
+        q.hits = Some(hits);
+        assert_eq!(db.query_count(&q).unwrap(), 2);
+        let (sort, order) = ("relevance".to_string(), "desc".to_string());
+        let p = db
+            .browse_page(&q, 1, None, sort.clone(), order.clone())
+            .unwrap();
+        assert_eq!(p.items[0].fdc_id, "1");
+        let next = Cursor::decode(&p.next.unwrap(), &sort, &order).unwrap();
+        let p = db.browse_page(&q, 1, Some(&next), sort, order).unwrap();
+        assert_eq!(p.items[0].fdc_id, "2");
+        assert!(p.next.is_none());
+        let facets = db.facets(&q, &[FacetField::Datasource], 10).unwrap();
+        assert_eq!(
+            facets.datasources.unwrap(),
+            vec![FacetCount {
+                value: "LI".to_string(),
+                count: 2
+            }]
+        );
+        q.datasource = Some("GDSN".to_string());
+        assert_eq!(db.query_count(&q).unwrap(), 0);
+        // a search without hits matches nothing
+        q.hits = Some(Vec::new());
+        q.datasource = None;
+        assert_eq!(db.query_count(&q).unwrap(), 0);
+    }
+    #[test]
+    fn nutrients_report() {
+        let file = TestFile::new("report");
+        let db = file.connect();
+        let mut cache = Cache::load(&db).unwrap();
+        cache.brand_id("Acme", None, None, &db).unwrap();
+        cache.food_group_id("Snacks", &db).unwrap();
+        let mut d = Derivation::new();
+        d.id = 1;
+        db.insert_derivations(&[d]).unwrap();
+        let mut nutrients = Vec::new();
+        for (id, nbr, name) in &[(1, "203", "Protein"), (2, "307", "Sodium, Na")] {
+            let mut n = Nutrient::new();
+            n.id = *id;
+            n.nutrientno = nbr.to_string();
+            n.description = name.to_string();
+            nutrients.push(n);
+        }
+        db.insert_nutrients(&nutrients).unwrap();
+        let mut foods = Vec::new();
+        for fdc in &["1", "2", "3"] {
+            let mut f = Food::new();
+            f.fdc_id = fdc.to_string();
+            f.upc = format!("00000{}", fdc);
+            f.description = format!("BAR {}", fdc);
+            f.brand_id = 1;
+            f.food_group_id = 1;
+            f.datasource = "LI".to_string();
+            foods.push(f);
+        }
+        db.insert_foods(&foods).unwrap();
+        // protein and sodium per 100 g and per portion
+        let mut data = Vec::new();
+        for (food, nutrient, value, portion) in &[
+            (1, 1, 20.0, 8.0),
+            (1, 2, 300.0, 120.0),
+            (2, 1, 25.0, 10.0),
+            (2, 2, 500.0, 200.0),
+            (3, 1, 5.0, 2.0),
+        ] {
+            data.push(Nutrientdata {
+                id: 0,
+                value: *value,
+                portion_value: *portion,
+                standard_error: None,
+                minimum: None,
+                maximum: None,
+                median: None,
+                data_points: None,
+                footnote: None,
+                min_year: None,
+                derivation_id: 1,
+                nutrient_id: *nutrient,
+                food_id: *food,
+                fdc_release: None,
+            });
+        }
+        db.insert_nutrient_data(&data).unwrap();
+        let mut q = FoodFilter::new();
+        q.nutrients = vec![
+            NutrientRange::parse("203:10:").unwrap(),
+            NutrientRange::parse("307::250:portion").unwrap(),
+        ];
+        assert_eq!(db.query_count(&q).unwrap(), 2);
+        let r = db.nutrients_report(&q, "203", "desc", 10, 0).unwrap();
+        let fdc_ids: Vec<&str> = r.iter().map(|r| r.fdc_id.as_str()).collect();
+        assert_eq!(fdc_ids, vec!["2", "1"]);
+        assert_eq!(r[0].nutrients.len(), 2);
+        let r = db.nutrients_report(&q, "307", "asc", 10, 0).unwrap();
+        assert_eq!(r[0].fdc_id, "1");
+        q.nutrients[1] = NutrientRange::parse("307::150:portion").unwrap();
+        let r = db.nutrients_report(&q, "", "asc", 10, 0).unwrap();
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].fdc_id, "1");
+        // browses sort by a nutrient, foods without it last either way
+        let q = FoodFilter::new();
+        let (sort, order) = ("nutrient:307".to_string(), "desc".to_string());
+        let p = db
+            .browse_page(&q, 2, None, sort.clone(), order.clone())
+            .unwrap();
+        let fdc_ids: Vec<&str> = p.items.iter().map(|f| f.fdc_id.as_str()).collect();
+        assert_eq!(fdc_ids, vec!["2", "1"]);
+        let next = Cursor::decode(&p.next.unwrap(), &sort, &order).unwrap();
+        let p = db
+            .browse_page(&q, 2, Some(&next), sort.clone(), order)
+            .unwrap();
+        assert_eq!(p.items[0].fdc_id, "3");
+        assert!(p.next.is_none());
+        let order = "asc".to_string();
+        let p = db
+            .browse_page(&q, 2, None, sort.clone(), order.clone())
+            .unwrap();
+        let fdc_ids: Vec<&str> = p.items.iter().map(|f| f.fdc_id.as_str()).collect();
+        assert_eq!(fdc_ids, vec!["1", "2"]);
+        let next = Cursor::decode(&p.next.unwrap(), &sort, &order).unwrap();
+        let p = db
+            .browse_page(&q, 2, Some(&next), sort.clone(), order.clone())
+            .unwrap();
+        assert_eq!(p.items[0].fdc_id, "3");
+        let prev = Cursor::decode(&p.prev.unwrap(), &sort, &order).unwrap();
+        assert!(prev.key.is_none());
+        let p = db
+            .browse_page(&q, 2, Some(&prev), sort.clone(), order)
+            .unwrap();
+        let fdc_ids: Vec<&str> = p.items.iter().map(|f| f.fdc_id.as_str()).collect();
+        assert_eq!(fdc_ids, vec!["1", "2"]);
+        let p = db
+            .browse_page(
+                &q,
+                1,
+                None,
+                "nutrient:203:portion".to_string(),
+                "asc".to_string(),
+            )
+            .unwrap();
+        assert_eq!(p.items[0].fdc_id, "3");
+        // statistics are of the foods with the nutrient
+        let s = db
+            .nutrient_stats(&q, "307", GroupBy::FoodGroup, &[50.0], 10)
+            .unwrap();
+        assert_eq!((s[0].group.as_str(), s[0].count), ("Snacks", 2));
+        assert_eq!(
+            (s[0].mean, s[0].median, s[0].percentiles[0].value),
+            (400.0, 400.0, 400.0)
+        );
+        let s = db
+            .nutrient_stats(&q, "203", GroupBy::Country, &[], 10)
+            .unwrap();
+        assert_eq!(
+            (s[0].group.as_str(), s[0].count, s[0].max),
+            ("unknown", 3, 25.0)
+        );
+        // histograms are counted by the database
+        let h = db
+            .histogram(&q, "307", Basis::Unit, &Bins::Count(2), false)
+            .unwrap();
+        let counts: Vec<(f64, i64)> = h.bins.iter().map(|b| (b.lower, b.count)).collect();
+        assert_eq!(counts, vec![(300.0, 1), (400.0, 1)]);
+        let h = db
+            .histogram(
+                &q,
+                "203",
+                Basis::Portion,
+                &Bins::edges(vec![0.0, 9.0]).unwrap(),
+                false,
+            )
+            .unwrap();
+        assert_eq!((h.bins[0].count, h.above), (2, 1));
+        let h = db
+            .histogram(&q, "208", Basis::Unit, &Bins::Count(5), true)
+            .unwrap();
+        assert!(h.bins.is_empty());
+    }
+    #[test]
+    fn update_release() {
+        let (dir, db, mut rejects) = ingest("release");
+        dir.write("food.csv", &["fdc_id,description", "1,PEANUT BUTTER", "2,GRANOLA"]);
+        let branded = ["fdc_id,brand_owner,serving_size", "1,Acme,32", "2,Acme,40"];
+        dir.write("branded_food.csv", &branded);
+        dir.nutdata(&["1,1,203,25,,70,,,,,", "2,2,203,10,,70,,,,,"]);
+        let path = dir.path();
+        process_nutrients(path.clone(), &db, &mut rejects).unwrap();
+        process_derivations(path.clone(), &db, &mut rejects).unwrap();
+        assert_eq!(process_foods(path.clone(), "2021-04", false, &db, &mut rejects).unwrap(), 2);
+        assert_eq!(process_nutdata(path.clone(), "2021-04", false, &db, &mut rejects).unwrap(), 2);
+        // the next release changes a food, adds one and repeats one
+        let food = ["fdc_id,description", "1,PEANUT BUTTER", "2,OAT GRANOLA", "3,TRAIL MIX"];
+        dir.write("food.csv", &food);
+        let branded = ["fdc_id,brand_owner,serving_size", "1,Acme,32", "2,Acme,40", "3,Acme,30"];
+        dir.write("branded_food.csv", &branded);
+        dir.nutdata(&["3,2,203,12,,70,,,,,", "4,3,203,15,,70,,,,,"]);
+        assert_eq!(process_nutrients(path.clone(), &db, &mut rejects).unwrap(), 0);
+        let report = update_foods(path.clone(), "2021-10", &db, &mut rejects).unwrap();
+        assert_eq!(
+            report,
+            UpdateReport {
+                added: 1,
+                changed: 1,
+                unchanged: 1
+            }
+        );
+        assert_eq!(update_nutdata(path, "2021-10", &db, &mut rejects).unwrap(), 2);
+        let nd = rejects
+            .summary()
+            .files
+            .iter()
+            .find(|c| c.file == "food_nutrient.csv")
+            .unwrap();
+        assert_eq!((nd.release.as_deref(), nd.replaced), (Some("2021-10"), Some(1)));
+        let f = db.foods_by_fdc_id(&["1".to_string(), "2".to_string()]).unwrap();
+        let releases: Vec<(&str, &str)> = f
+            .iter()
+            .map(|f| (f.description.as_str(), f.fdc_release.as_deref().unwrap()))
+            .collect();
+        assert!(releases.contains(&("PEANUT BUTTER", "2021-04")));
+        assert!(releases.contains(&("OAT GRANOLA", "2021-10")));
+        let granola = f.iter().find(|f| f.fdc_id == "2").unwrap();
+        let values: Vec<f64> = db
+            .nutrient_data(granola, &[])
+            .unwrap()
+            .iter()
+            .map(|nd| nd.value)
+            .collect();
+        assert_eq!(values, vec![12.0]);
+        // as are the nutrient values
+        let releases: Vec<(i32, Option<String>)> = nutrient_data::table
+            .select((nutrient_data::food_id, nutrient_data::fdc_release))
+            .order(nutrient_data::food_id)
+            .load(&db.pool.get().unwrap())
+            .unwrap();
+        let release = |r: &str| Some(r.to_string());
+        assert_eq!(
+            releases,
+            vec![(1, release("2021-04")), (2, release("2021-10")), (3, release("2021-10"))]
+        );
+        let fdc_ids = ["1", "2", "3", "4"].map(String::from);
+        assert_eq!(db.food_ids(&fdc_ids).unwrap().len(), 3);
+    }
+    #[test]
+    fn resume_load() {
+        let (dir, db, mut rejects) = ingest("resume");
+        dir.write("food.csv", &["fdc_id,description", "1,PEANUT BUTTER"]);
+        dir.write("branded_food.csv", &["fdc_id,brand_owner,serving_size", "1,Acme,32"]);
+        // the first row can't be read, which the checkpoint counts all the same
+        let nutdata = |rows: &[&str]| {
+            let mut bytes = format!("{}\n", NUTDATA_HEADER).into_bytes();
+            bytes.extend_from_slice(b"1,1,203,\xff,,70,,,,,\n");
+            bytes.extend_from_slice(rows.join("\n").as_bytes());
+            fs::write(dir.join("food_nutrient.csv"), bytes).unwrap()
+        };
+        nutdata(&["2,1,203,25,,70,,,,,"]);
+        let path = dir.path();
+        process_nutrients(path.clone(), &db, &mut rejects).unwrap();
+        process_derivations(path.clone(), &db, &mut rejects).unwrap();
+        // a load that stopped after the first food and its nutrient value
+        assert_eq!(process_foods(path.clone(), "2021-04", false, &db, &mut rejects).unwrap(), 1);
+        assert_eq!(process_nutdata(path.clone(), "2021-04", false, &db, &mut rejects).unwrap(), 1);
+        let at = db.checkpoint(NUTRIENT_DATA).unwrap().unwrap();
+        assert_eq!((at.last_row, at.last_fdc_id.as_str()), (2, "1"));
+        let food = ["fdc_id,description", "1,PEANUT BUTTER", "2,GRANOLA", "3,TRAIL MIX"];
+        dir.write("food.csv", &food);
+        let branded = ["fdc_id,brand_owner,serving_size", "1,Acme,32", "2,Acme,40", "3,Acme,30"];
+        dir.write("branded_food.csv", &branded);
+        nutdata(&["2,1,203,25,,70,,,,,", "3,2,203,10,,70,,,,,"]);
+        assert_eq!(process_foods(path.clone(), "2021-04", true, &db, &mut rejects).unwrap(), 2);
+        assert_eq!(db.checkpoint(FOODS).unwrap().unwrap().last_fdc_id, "3");
+        assert_eq!(process_nutdata(path.clone(), "2021-04", true, &db, &mut rejects).unwrap(), 1);
+        let fdc_ids = ["1", "2", "3", "4"].map(String::from);
+        assert_eq!(db.food_ids(&fdc_ids).unwrap().len(), 3);
+        // once finished there's nothing left to resume
+        assert_eq!(process_foods(path.clone(), "2021-04", true, &db, &mut rejects).unwrap(), 0);
+        assert_eq!(process_nutdata(path, "2021-04", true, &db, &mut rejects).unwrap(), 0);
+    }
+    #[test]
+    fn resume_interrupted_load() {
+        let (dir, db, mut rejects) = ingest("interrupted");
+        dir.write("food.csv", &["fdc_id,description", "1,PEANUT BUTTER"]);
+        dir.write("branded_food.csv", &["fdc_id,brand_owner,serving_size", "1,Acme,32"]);
+        // a row past the first batch stops the load when no rejects are allowed
+        let nd: Vec<String> = (1..=2500)
+            .map(|i| {
+                let nutrient = if i == 2100 { 999 } else { 203 };
+                format!("{},1,{},{},,70,,,,,", i, nutrient, i)
+            })
+            .collect();
+        dir.nutdata(&nd.iter().map(String::as_str).collect::<Vec<_>>());
+        let path = dir.path();
+        process_nutrients(path.clone(), &db, &mut rejects).unwrap();
+        process_derivations(path.clone(), &db, &mut rejects).unwrap();
+        process_foods(path.clone(), "2021-04", false, &db, &mut rejects).unwrap();
+        let mut strict = Rejects::new(&dir.join("rejects.csv"), Some(0));
+        assert!(process_nutdata(path.clone(), "2021-04", false, &db, &mut strict).is_err());
+        // the first batch is in with its checkpoint
+        let at = db.checkpoint(NUTRIENT_DATA).unwrap().unwrap();
+        assert_eq!(at.last_row, BATCH_SIZE as i64);
+        let food = &db.foods_by_fdc_id(&["1".to_string()]).unwrap()[0];
+        assert_eq!(db.nutrient_data(food, &[]).unwrap().len(), BATCH_SIZE);
+        // resuming loads the rest once
+        assert_eq!(process_nutdata(path, "2021-04", true, &db, &mut rejects).unwrap(), 499);
+        let counts = rejects.summary().files.iter().find(|c| c.file == "food_nutrient.csv");
+        assert_eq!(counts.unwrap().resumed_after, Some(BATCH_SIZE as i64));
+        let values: Vec<f64> = db
+            .nutrient_data(food, &[])
+            .unwrap()
+            .iter()
+            .map(|nd| nd.value)
+            .collect();
+        assert_eq!(values.len(), 2499);
+        assert!((1..=2500).filter(|&i| i != 2100).all(|i| values.contains(&(i as f64))));
+    }
+    #[test]
+    fn reject_rows() {
+        let (dir, db, mut rejects) = ingest("reject");
+        dir.write("nutrient.csv", &[NUTRIENT_HEADER, PROTEIN, "1004,Fat,G,,800"]);
+        let food = [
+            "1,PEANUT BUTTER,2020-11-13",
+            "2,GRANOLA,2020-13-01",
+            "3,TRAIL MIX,",
+            "4,NUTS,",
+        ];
+        dir.write("food.csv", &[&["fdc_id,description,publication_date"], &food[..]].concat());
+        let branded = ["1,Acme,32", "2,Acme,40", "3,Acme,a lot", "4,Acme"];
+        let branded = [&["fdc_id,brand_owner,serving_size"], &branded[..]].concat();
+        dir.write("branded_food.csv", &branded);
+        dir.nutdata(&[
+            "1,1,203,25,,70,,,,,",
+            "2,1,999,1,,70,,,,,",
+            "3,9,203,1,,70,,,,,",
+            "4,1,203,1,,71,,,,,",
+            "5,1,203,lots,,70,,,,,",
+            "6,1,203",
+        ]);
+        let path = dir.path();
+        assert_eq!(process_nutrients(path.clone(), &db, &mut rejects).unwrap(), 1);
+        assert_eq!(process_derivations(path.clone(), &db, &mut rejects).unwrap(), 1);
+        assert_eq!(process_foods(path.clone(), "2021-04", false, &db, &mut rejects).unwrap(), 1);
+        assert_eq!(process_nutdata(path.clone(), "2021-04", false, &db, &mut rejects).unwrap(), 1);
+        let counts: Vec<(&str, usize, usize)> = rejects
+            .summary()
+            .files
+            .iter()
+            .map(|c| (c.file.as_str(), c.loaded, c.rejected))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                ("nutrient.csv", 1, 1),
+                ("food_nutrient_derivation.csv", 1, 0),
+                ("food.csv", 1, 3),
+                ("food_nutrient.csv", 1, 5)
+            ]
+        );
+        let rejected = fs::read_to_string(dir.join("rejects.csv")).unwrap();
+        let lines: Vec<&str> = rejected.lines().collect();
+        assert_eq!(lines[0], "file,line,reason");
+        assert_eq!(lines[1], "nutrient.csv,3,missing nutrient_nbr,1004,Fat,G,,800");
+        assert_eq!(
+            lines[2],
+            "food.csv,,invalid publication_date '2020-13-01',2,GRANOLA,2020-13-01,Acme,40"
+        );
+        assert_eq!(lines[3], "food.csv,,invalid serving_size 'a lot',3,TRAIL MIX,,Acme,a lot");
+        assert_eq!(lines[4], "food.csv,,\"expected 5 fields, found 4\",4,NUTS,,Acme");
+        assert!(lines[5].starts_with("food_nutrient.csv,3,unknown nutrient_id 999,"));
+        assert!(lines[6].starts_with("food_nutrient.csv,4,unknown fdc_id 9,"));
+        assert!(lines[7].starts_with("food_nutrient.csv,5,unknown derivation_id 71,"));
+        assert!(lines[8].starts_with("food_nutrient.csv,6,\"CSV deserialize error"));
+        assert_eq!(lines[9], "food_nutrient.csv,7,\"expected 11 fields, found 3\",6,1,203");
+        // a load stops once more rows are rejected than allowed
+        let mut rejects = Rejects::new(&dir.join("rejects.csv"), Some(2));
+        assert!(process_nutdata(path, "2021-04", false, &db, &mut rejects).is_err());
+    }
+    #[test]
+    fn load_batches() {
+        let file = TestFile::new("batches");
+        bfpd::fixtures::bulk_load(&file.connect(), "batches");
+    }
+    /// Returns a test's csv directory of the supporting files with a database
+    /// and reject csv in it
+    fn ingest(name: &str) -> (CsvDir, SqliteStore, Rejects) {
+        let dir = CsvDir::supporting(name);
+        let db = connect(&format!("sqlite://{}", dir.join("bfpd.db").display()));
+        let rejects = Rejects::new(&dir.join("rejects.csv"), None);
+        (dir, db, rejects)
+    }
+}